@@ -24,3 +24,100 @@ extend {
     is_meshable = true,
     color = {0.5, 0.3, 0.1}
 }
+
+extend {
+    type = "block",
+    name = "dirt_slab",
+    order = "a[blocks]-d[dirt-slab]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.5, 0.3, 0.1},
+    shape = "bottom_slab"
+}
+
+extend {
+    type = "block",
+    name = "glowstone",
+    order = "a[blocks]-e[glowstone]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {1, 0.8, 0.4},
+    emissive = 4
+}
+
+extend {
+    type = "block",
+    name = "tall_grass",
+    order = "a[blocks]-f[tall-grass]",
+    is_transparent = true,
+    is_meshable = true,
+    color = {0.3, 0.7, 0.2},
+    shape = "cross"
+}
+
+extend {
+    type = "block",
+    name = "bedrock",
+    order = "a[blocks]-g[bedrock]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.25, 0.25, 0.28}
+}
+
+extend {
+    type = "block",
+    name = "stone",
+    order = "a[blocks]-h[stone]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.5, 0.5, 0.5}
+}
+
+extend {
+    type = "block",
+    name = "sand",
+    order = "a[blocks]-i[sand]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.85, 0.8, 0.55}
+}
+
+extend {
+    type = "block",
+    name = "water",
+    order = "a[blocks]-j[water]",
+    is_transparent = false,
+    is_meshable = true,
+    is_fluid = true,
+    color = {0.2, 0.4, 0.8, 0.6}
+}
+
+extend {
+    type = "biome",
+    name = "desert",
+    order = "b[biomes]-a[desert]",
+    layers = {{name = "stone", max_y = 185}, {name = "sand"}}
+}
+
+extend {
+    type = "biome",
+    name = "mountains",
+    order = "b[biomes]-b[mountains]",
+    layers = {{name = "stone", max_y = 215}, {name = "grass"}}
+}
+
+extend {
+    type = "biome",
+    name = "plains",
+    order = "b[biomes]-c[plains]",
+    layers = {{name = "dirt", max_y = 195}, {name = "grass"}}
+}
+
+extend {
+    type = "recipe",
+    name = "dirt",
+    order = "a[blocks]-c[dirt]",
+    ingredients = {{name = "grass", amount = 1}},
+    results = {{name = "dirt", amount = 1}},
+    time = 0.5
+}
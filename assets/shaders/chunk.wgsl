@@ -14,10 +14,29 @@
 #import bevy_pbr::mesh_bindings::mesh
 #import bevy_pbr::pbr_types::pbr_input_new
 #import bevy_pbr::view_transformations::position_world_to_clip
+#import bevy_pbr::mesh_view_bindings::view
 
 @group(1) @binding(0)
 var<uniform> chunk_position: vec3<i32>;
 
+@group(2) @binding(0)
+var block_textures: texture_2d_array<f32>;
+@group(2) @binding(1)
+var block_sampler: sampler;
+// see `BlockPalette`, the emissive intensity of each block by id
+@group(2) @binding(2)
+var<storage, read> block_palette: array<f32>;
+
+// see `ChunkLighting`
+struct ChunkLighting {
+    reflectance: f32,
+    perceptual_roughness: f32,
+    metallic: f32,
+};
+
+@group(3) @binding(0)
+var<uniform> lighting: ChunkLighting;
+
 struct InstanceInput {
     @location(0) constant_quad: vec3<f32>,
 };
@@ -25,6 +44,8 @@ struct InstanceInput {
 struct VertexInput {
     @location(1) vert_data: u32,
     @location(2) color: u32,
+    @location(3) texture: u32,
+    @location(4) shading: u32,
 };
 
 var<private> ambient_lerps: vec4<f32> = vec4<f32>(1.0,0.7,0.5,0.15);
@@ -53,41 +74,76 @@ fn vertex(vertex: VertexInput, instance_input: InstanceInput) -> VertexOutput {
     var z = f32(vertex.vert_data >> 10u & x_positive_bits(5u)) + f32(chunk_position.z * 32);
     
     let normal_index = vertex.vert_data >> 15u & x_positive_bits(3u);
+    // texture coordinates span the quad once per voxel, so the texture repeats across merged quads
+    var uv = vec2<f32>(0.0, 0.0);
     switch normal_index {
         case 0u: { // left
             y += instance_input.constant_quad.x * f32(x_strech) - 1;
             z += instance_input.constant_quad.z * f32(y_strech);
+            uv = vec2<f32>(instance_input.constant_quad.z * f32(y_strech), instance_input.constant_quad.x * f32(x_strech));
         }
         case 1u: { // right
             x += 1.0;
             y += instance_input.constant_quad.z * f32(x_strech) - 1;
             z += instance_input.constant_quad.x * f32(y_strech);
+            uv = vec2<f32>(instance_input.constant_quad.x * f32(y_strech), instance_input.constant_quad.z * f32(x_strech));
         }
         case 2u: { // down
             x += instance_input.constant_quad.z * f32(y_strech);
             y += -1.0;
             z += instance_input.constant_quad.x * f32(x_strech);
+            uv = vec2<f32>(instance_input.constant_quad.z * f32(y_strech), instance_input.constant_quad.x * f32(x_strech));
         }
         case 3u, default: { // up
             x += instance_input.constant_quad.x * f32(y_strech);
             z += instance_input.constant_quad.z * f32(x_strech);
+            uv = vec2<f32>(instance_input.constant_quad.x * f32(y_strech), instance_input.constant_quad.z * f32(x_strech));
         }
         case 4u { // forward
             x += instance_input.constant_quad.x * f32(y_strech);
             y += instance_input.constant_quad.z * f32(x_strech) - 1;
+            uv = vec2<f32>(instance_input.constant_quad.x * f32(y_strech), instance_input.constant_quad.z * f32(x_strech));
         }
         case 5u { // backward
             x += instance_input.constant_quad.z * f32(y_strech);
             y += instance_input.constant_quad.x * f32(x_strech) - 1;
             z += 1.0;
+            uv = vec2<f32>(instance_input.constant_quad.z * f32(y_strech), instance_input.constant_quad.x * f32(x_strech));
+        }
+    }
+    // bottom slabs squash the voxel into its lower half. slab quads are never merged, so they span one voxel.
+    let shape = vertex.vert_data >> 30u & x_positive_bits(2u);
+    if shape == 1u {
+        let voxel_bottom = f32(vertex.vert_data >> 5u & x_positive_bits(5u)) + f32(chunk_position.y * 32) - 1.0;
+        y = voxel_bottom + (y - voxel_bottom) * 0.5;
+        if normal_index != 2u && normal_index != 3u {
+            uv.y *= 0.5;
         }
     }
-    let ao = vertex.vert_data >> 18u & x_positive_bits(2u);
+    // crossed quads run diagonally through their voxel, the normal picks the diagonal. 0 is x = z, 1 is x = 1 - z.
+    // both sides face the camera at some point, so they are lit as if facing up.
+    var normal = normals[normal_index];
+    if shape == 2u {
+        let u = instance_input.constant_quad.x;
+        let v = instance_input.constant_quad.z;
+        x = f32(vertex.vert_data & x_positive_bits(5u)) + f32(chunk_position.x * 32) + u;
+        y = f32(vertex.vert_data >> 5u & x_positive_bits(5u)) + f32(chunk_position.y * 32) - 1.0 + v;
+        z = f32(vertex.vert_data >> 10u & x_positive_bits(5u)) + f32(chunk_position.z * 32) + select(u, 1.0 - u, normal_index == 1u);
+        uv = vec2<f32>(u, 1.0 - v);
+        normal = normals[3];
+    }
+
+    // corner ao is interpolated across the quad, so merged quads get smooth shading.
+    let corner = u32(instance_input.constant_quad.x) + 2u * u32(instance_input.constant_quad.z);
+    let ao = vertex.shading >> (corner * 2u) & x_positive_bits(2u);
 
     var out: VertexOutput;
-    out.normal = normals[normal_index];
-    out.ambient = ao;
+    out.normal = normal;
+    out.ambient = ambient_lerps[ao];
     out.position = vec3<f32>(x,y,z);
+    out.uv = uv;
+    out.texture = vertex.texture & x_positive_bits(16u);
+    out.palette_index = vertex.shading >> 8u & x_positive_bits(16u);
     out.clip_position = position_world_to_clip(vec3<f32>(x,y,z));
     out.color = vec4<f32>(
         f32((vertex.color >> 24u) & 0xFFu) / 255.0,
@@ -104,7 +160,10 @@ struct VertexOutput {
     @location(0) normal: vec3<f32>,
     @location(1) position: vec3<f32>,
     @location(2) color: vec4<f32>,
-    @location(3) ambient: u32,
+    @location(3) ambient: f32,
+    @location(4) uv: vec2<f32>,
+    @location(5) @interpolate(flat) texture: u32,
+    @location(6) @interpolate(flat) palette_index: u32,
 };
 
 struct Light {
@@ -114,7 +173,15 @@ struct Light {
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = in.color;
+    // texture 0 means untextured. sample unconditionally to stay in uniform control flow.
+    let layer = i32(max(in.texture, 1u) - 1u);
+    let texture_color = textureSample(block_textures, block_sampler, in.uv, layer);
+    let object_color: vec4<f32> = select(in.color, texture_color, in.texture != 0u);
+#ifdef CUTOUT
+    if object_color.a < 0.5 {
+        discard;
+    }
+#endif
     
     let light = Light(
         vec3<f32>(0.0, 100.0, 0.0),
@@ -127,8 +194,21 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
     let light_dir = normalize(light.position - in.position);
 
     let diffuse_strength = max(dot(in.normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
-
-    let result = (ambient_color + diffuse_color) * object_color.xyz;
+    // metals have no diffuse reflection, their albedo tints the specular instead
+    let diffuse_color = light.color * diffuse_strength * (1.0 - lighting.metallic);
+
+    // normalized blinn-phong standing in for the pbr specular lobe, rougher surfaces spread it wider and dimmer
+    let view_dir = normalize(view.world_position - in.position);
+    let half_dir = normalize(light_dir + view_dir);
+    let roughness = max(lighting.perceptual_roughness * lighting.perceptual_roughness, 0.045);
+    let shininess = 2.0 / (roughness * roughness) - 2.0;
+    let f0 = mix(vec3<f32>(0.16 * lighting.reflectance * lighting.reflectance), object_color.xyz, lighting.metallic);
+    let specular_strength = pow(max(dot(in.normal, half_dir), 0.0), shininess) * (shininess + 8.0) / 25.1327 * diffuse_strength;
+    let specular_color = light.color * f0 * specular_strength;
+
+    let lit = ((ambient_color + diffuse_color) * object_color.xyz + specular_color) * in.ambient;
+    // emissive blocks ignore lighting, intensities above 1 bloom on hdr cameras.
+    let emissive = block_palette[in.palette_index];
+    let result = select(lit, object_color.xyz * emissive, emissive > 0.0);
     return vec4<f32>(result, object_color.a);
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+//! Compares meshing with owning `ChunkRefs` (27 `Arc` clones per chunk)
+//! against `ChunkRefsBorrowed` at render distance 16, and meshing with ambient occlusion on and off.
+
+use std::hint::black_box;
+use std::sync::Arc;
+
+use bevy::math::IVec2;
+use criterion::{Criterion, criterion_group, criterion_main};
+use talc::{
+    chunky::{
+        async_chunkloader::Chunks,
+        chunk::set_block_registry,
+        chunks_refs::{ChunkRefs, ChunkRefsBorrowed},
+        greedy_mesher_optimized::{
+            AoEnabled, GreedyMeshing, build_chunk_instance_data, build_chunk_instance_data_with_stats,
+        },
+        lod::Lod,
+        terrain::Terrain,
+    },
+    mod_manager::mod_loader::load_block_prototypes,
+    position::ChunkPosition,
+};
+
+const RENDER_DISTANCE: i32 = 16;
+/// The chunk layers around the terrain surface. Sky and underground chunks are skipped to keep setup fast.
+const SURFACE_LAYERS: std::ops::RangeInclusive<i32> = 5..=7;
+
+fn columns(radius: i32) -> impl Iterator<Item = IVec2> {
+    (-radius..=radius)
+        .flat_map(move |x| (-radius..=radius).map(move |z| IVec2::new(x, z)))
+        .filter(move |column| column.distance_squared(IVec2::ZERO) <= radius * radius)
+}
+
+fn generate_world() -> (Chunks, Vec<ChunkPosition>) {
+    let block_prototypes = load_block_prototypes();
+    set_block_registry(&block_prototypes);
+
+    let radius = RENDER_DISTANCE / 2;
+    let terrain = Terrain::default();
+    let mut chunks = Chunks::default();
+    // diagonal neighbours of the edge columns can be up to sqrt(2) further out
+    for column in columns(radius + 2) {
+        for y in SURFACE_LAYERS.start() - 1..=SURFACE_LAYERS.end() + 1 {
+            let chunk_position = ChunkPosition::new(column.x, y, column.y);
+            let chunk_data = terrain.0.generate(&block_prototypes, chunk_position);
+            chunks.0.insert(chunk_position, Arc::new(chunk_data));
+        }
+    }
+
+    let to_mesh = columns(radius)
+        .flat_map(|column| SURFACE_LAYERS.map(move |y| ChunkPosition::new(column.x, y, column.y)))
+        .collect();
+
+    (chunks, to_mesh)
+}
+
+fn bench_mesh(c: &mut Criterion, chunks: &Chunks, to_mesh: &[ChunkPosition]) {
+    let mut group = c.benchmark_group("mesh render distance 16");
+    group.sample_size(10);
+    group.bench_function("owned ChunkRefs", |b| {
+        b.iter(|| {
+            for &chunk_position in to_mesh {
+                let chunk_refs = ChunkRefs::try_new(chunks, chunk_position)
+                    .expect("All neighbours were generated.");
+                black_box(build_chunk_instance_data(&chunk_refs, Lod::default()));
+            }
+        });
+    });
+    group.bench_function("borrowed ChunkRefs", |b| {
+        b.iter(|| {
+            for &chunk_position in to_mesh {
+                let chunk_refs = ChunkRefsBorrowed::try_borrow(chunks, chunk_position)
+                    .expect("All neighbours were generated.");
+                black_box(build_chunk_instance_data(&chunk_refs, Lod::default()));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn bench_ao(c: &mut Criterion, chunks: &Chunks, to_mesh: &[ChunkPosition]) {
+    let mut group = c.benchmark_group("mesh ao render distance 16");
+    group.sample_size(10);
+    for ao_enabled in [AoEnabled(true), AoEnabled(false)] {
+        let name = if ao_enabled.0 { "ao on" } else { "ao off" };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for &chunk_position in to_mesh {
+                    let chunk_refs = ChunkRefsBorrowed::try_borrow(chunks, chunk_position)
+                        .expect("All neighbours were generated.");
+                    black_box(build_chunk_instance_data_with_stats(
+                        &chunk_refs,
+                        Lod::default(),
+                        ao_enabled,
+                        GreedyMeshing::default(),
+                    ));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_construct(c: &mut Criterion, chunks: &Chunks, to_mesh: &[ChunkPosition]) {
+    let mut group = c.benchmark_group("construct render distance 16");
+    group.bench_function("owned ChunkRefs", |b| {
+        b.iter(|| {
+            for &chunk_position in to_mesh {
+                black_box(ChunkRefs::try_new(chunks, chunk_position));
+            }
+        });
+    });
+    group.bench_function("borrowed ChunkRefs", |b| {
+        b.iter(|| {
+            for &chunk_position in to_mesh {
+                black_box(ChunkRefsBorrowed::try_borrow(chunks, chunk_position));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn bench_chunk_refs(c: &mut Criterion) {
+    let (chunks, to_mesh) = generate_world();
+    bench_mesh(c, &chunks, &to_mesh);
+    bench_ao(c, &chunks, &to_mesh);
+    bench_construct(c, &chunks, &to_mesh);
+}
+
+criterion_group!(benches, bench_chunk_refs);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Compares walking the columns of `build_face_masks` z-major (the `[z][x]` memory order)
+//! against x-major, on chunk shapes the mesher sees.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use talc::chunky::{
+    chunk::CHUNK_SIZE_P,
+    greedy_mesher_optimized::{ColumnOrder, build_face_masks},
+};
+
+type AxisCols = [[[u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3];
+
+/// Solid voxels on each axis wherever `is_solid(x, y, z)`, laid out like the mesher's `axis_cols`.
+fn axis_cols(is_solid: impl Fn(usize, usize, usize) -> bool) -> Box<AxisCols> {
+    #[allow(clippy::large_stack_arrays)]
+    let mut axis_cols = Box::new([[[0u64; CHUNK_SIZE_P]; CHUNK_SIZE_P]; 3]);
+    for z in 0..CHUNK_SIZE_P {
+        for y in 0..CHUNK_SIZE_P {
+            for x in 0..CHUNK_SIZE_P {
+                if is_solid(x, y, z) {
+                    axis_cols[0][z][x] |= 1 << y;
+                    axis_cols[1][y][z] |= 1 << x;
+                    axis_cols[2][y][x] |= 1 << z;
+                }
+            }
+        }
+    }
+    axis_cols
+}
+
+fn bench_face_masks(c: &mut Criterion) {
+    let chunks = [
+        ("terrain", axis_cols(|x, y, z| y < 10 + (x * 7 + z * 3) % 13)),
+        ("checkerboard", axis_cols(|x, y, z| (x + y + z) % 2 == 0)),
+        ("noise", axis_cols(|x, y, z| ((x * 73_856_093) ^ (y * 19_349_663) ^ (z * 83_492_791)) % 5 < 2)),
+    ];
+    for (name, axis_cols) in &chunks {
+        for (order_name, order) in [("z-major", ColumnOrder::ZMajor), ("x-major", ColumnOrder::XMajor)] {
+            c.bench_function(&format!("build_face_masks {name} {order_name}"), |b| {
+                b.iter(|| black_box(build_face_masks(black_box(axis_cols), order)));
+            });
+        }
+    }
+}
+
+criterion_group!(benches, bench_face_masks);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+//! Compares `get_block` on homogeneous chunks (such as the sky) against heterogeneous ones,
+//! both directly on `ChunkData` and through `ChunkRefs` as the mesher samples them.
+
+use std::hint::black_box;
+use std::sync::Arc;
+
+use bevy::math::IVec3;
+use criterion::{Criterion, criterion_group, criterion_main};
+use talc::{
+    chunky::{
+        async_chunkloader::Chunks,
+        chunk::{CHUNK_SIZE_I32, CHUNK_SIZE3, ChunkData, set_block_registry},
+        chunks_refs::ChunkRefsBorrowed,
+    },
+    mod_manager::{
+        mod_loader::load_block_prototypes,
+        prototypes::{BlockPrototype, Prototypes},
+    },
+    position::{ChunkPosition, Position},
+    utils::index_to_ivec3_bounds,
+};
+
+/// 27 air chunks around the origin. With `solid`, each gets one solid voxel so it is stored heterogeneous.
+fn sky_chunks(air: &BlockPrototype, solid: Option<&'static BlockPrototype>) -> Chunks {
+    let mut chunks = Chunks::default();
+    for i in 0..27 {
+        let chunk_position = ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE);
+        let mut chunk_data = ChunkData::filled(chunk_position, air);
+        if let Some(solid) = solid {
+            chunk_data.set_block(0.into(), solid);
+        }
+        chunks.0.insert(chunk_position, Arc::new(chunk_data));
+    }
+    chunks
+}
+
+fn bench_chunk_data(c: &mut Criterion, name: &str, chunks: &Chunks) {
+    let chunk_data = &chunks.0[&ChunkPosition::new(0, 0, 0)];
+    c.bench_function(&format!("ChunkData::get_block {name}"), |b| {
+        b.iter(|| {
+            for i in 0..CHUNK_SIZE3 {
+                black_box(chunk_data.get_block(black_box(i).into()));
+            }
+        });
+    });
+}
+
+fn bench_chunk_refs(c: &mut Criterion, name: &str, chunks: &Chunks) {
+    let chunk_refs = ChunkRefsBorrowed::try_borrow(chunks, ChunkPosition::new(0, 0, 0))
+        .expect("All 27 chunks were created.");
+    c.bench_function(&format!("ChunkRefs::get_block {name}"), |b| {
+        b.iter(|| {
+            // the center chunk and the one voxel border the mesher samples around it
+            for z in -1..=CHUNK_SIZE_I32 {
+                for y in -1..=CHUNK_SIZE_I32 {
+                    for x in -1..=CHUNK_SIZE_I32 {
+                        black_box(chunk_refs.get_block(black_box(Position::new(x, y, z))));
+                    }
+                }
+            }
+        });
+    });
+}
+
+fn bench_get_block(c: &mut Criterion) {
+    let block_prototypes = load_block_prototypes();
+    set_block_registry(&block_prototypes);
+    let air = block_prototypes.get("air").expect("The base mod registers air.");
+    let grass = block_prototypes.get("grass").expect("The base mod registers grass.");
+
+    let homogeneous = sky_chunks(air, None);
+    let heterogeneous = sky_chunks(air, Some(grass));
+
+    bench_chunk_data(c, "homogeneous", &homogeneous);
+    bench_chunk_data(c, "heterogeneous", &heterogeneous);
+    bench_chunk_refs(c, "homogeneous", &homogeneous);
+    bench_chunk_refs(c, "heterogeneous", &heterogeneous);
+}
+
+criterion_group!(benches, bench_get_block);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Main thread cost of joining chunk tasks in a frame where none of them finished,
+//! polling every task in flight against draining the result channel of `ChunkTasks`.
+//! Render distance 20 keeps the default budgets of 64 worldgen and 32 mesh tasks busy.
+
+use std::hint::black_box;
+
+use bevy::{
+    platform::collections::HashMap,
+    tasks::{AsyncComputeTaskPool, Task, TaskPool, block_on},
+};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use futures_lite::future;
+use talc::{chunky::chunk_tasks::ChunkTasks, position::ChunkPosition};
+
+fn chunk_positions(count: i32) -> impl Iterator<Item = ChunkPosition> {
+    (0..count).map(|x| ChunkPosition::new(x, 0, 0))
+}
+
+fn bench_join_tasks(c: &mut Criterion) {
+    let task_pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+
+    let mut group = c.benchmark_group("join unfinished tasks");
+    for in_flight in [96, 512] {
+        let mut polled: HashMap<ChunkPosition, Task<u32>> = chunk_positions(in_flight)
+            .map(|chunk_position| (chunk_position, task_pool.spawn(future::pending())))
+            .collect();
+        group.bench_function(BenchmarkId::new("poll every task", in_flight), |b| {
+            b.iter(|| {
+                polled.retain(|_, task| black_box(block_on(future::poll_once(task))).is_none());
+            });
+        });
+
+        let mut channelled = ChunkTasks::<u32>::default();
+        for chunk_position in chunk_positions(in_flight) {
+            channelled.spawn(chunk_position, future::pending());
+        }
+        group.bench_function(BenchmarkId::new("drain channel", in_flight), |b| {
+            b.iter(|| black_box(channelled.join(64)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_join_tasks);
+criterion_main!(benches);
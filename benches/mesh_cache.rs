@@ -0,0 +1,138 @@
+//! Meshes a flat world at render distance 24 with and without the `MeshCache`,
+//! and prints how many instance buffers and bytes each needs on the GPU.
+//! The noise terrain rarely repeats a chunk, it shows what hashing costs when the cache misses.
+
+use std::hint::black_box;
+use std::sync::Arc;
+
+use std::ops::RangeInclusive;
+
+use bevy::math::IVec2;
+use criterion::{Criterion, criterion_group, criterion_main};
+use talc::{
+    chunky::{
+        async_chunkloader::Chunks,
+        chunk::set_block_registry,
+        chunks_refs::ChunkRefs,
+        greedy_mesher_optimized::{AoEnabled, GreedyMeshing, MeshCache, build_chunk_instance_data_with_stats},
+        lod::Lod,
+        terrain::{FlatTerrain, NoiseTerrain, TerrainGenerator},
+    },
+    mod_manager::{mod_loader::load_block_prototypes, prototypes::BlockPrototypes},
+    position::ChunkPosition,
+    render::chunk_material::{PackedQuad, RenderableChunk},
+};
+
+const RENDER_DISTANCE: i32 = 24;
+
+fn columns(radius: i32) -> impl Iterator<Item = IVec2> {
+    (-radius..=radius)
+        .flat_map(move |x| (-radius..=radius).map(move |z| IVec2::new(x, z)))
+        .filter(move |column| column.distance_squared(IVec2::ZERO) <= radius * radius)
+}
+
+/// The chunk `layers` around the terrain surface, the rest of the ball is air or occluded.
+fn generate_world(
+    block_prototypes: &BlockPrototypes,
+    terrain: &impl TerrainGenerator,
+    layers: RangeInclusive<i32>,
+) -> (Chunks, Vec<ChunkPosition>) {
+    let radius = RENDER_DISTANCE / 2;
+    let mut chunks = Chunks::default();
+    // diagonal neighbours of the edge columns can be up to sqrt(2) further out
+    for column in columns(radius + 2) {
+        for y in layers.start() - 1..=layers.end() + 1 {
+            let chunk_position = ChunkPosition::new(column.x, y, column.y);
+            chunks.0.insert(chunk_position, Arc::new(terrain.generate(block_prototypes, chunk_position)));
+        }
+    }
+
+    let to_mesh = columns(radius)
+        .flat_map(|column| layers.clone().map(move |y| ChunkPosition::new(column.x, y, column.y)))
+        .collect();
+
+    (chunks, to_mesh)
+}
+
+/// Instance buffers and their bytes, counting buffers shared between chunks once.
+fn instance_buffers<'a>(meshes: impl Iterator<Item = &'a RenderableChunk>) -> (usize, usize) {
+    let mut unique: Vec<&RenderableChunk> = vec![];
+    for mesh in meshes {
+        if !unique.iter().any(|other| other.shares_quads_with(mesh)) {
+            unique.push(mesh);
+        }
+    }
+    let bytes = unique.iter().map(|mesh| size_of_val::<[PackedQuad]>(mesh.quads())).sum();
+    (unique.len(), bytes)
+}
+
+fn report_gpu_memory(chunks: &Chunks, to_mesh: &[ChunkPosition]) {
+    let mesh_cache = MeshCache::default();
+    let meshes: Vec<RenderableChunk> = to_mesh
+        .iter()
+        .flat_map(|&chunk_position| {
+            let chunk_refs = ChunkRefs::try_new(chunks, chunk_position).expect("All neighbours were generated.");
+            mesh_cache
+                .get_or_build(&chunk_refs, Lod::default(), AoEnabled::default(), GreedyMeshing::default())
+                .0
+                .into_values()
+        })
+        .collect();
+    let bytes: usize = meshes.iter().map(|mesh| size_of_val::<[PackedQuad]>(mesh.quads())).sum();
+    let (unique_buffers, unique_bytes) = instance_buffers(meshes.iter());
+    println!(
+        "flat world, render distance {RENDER_DISTANCE}: {} chunks, {} meshes",
+        to_mesh.len(),
+        meshes.len()
+    );
+    println!("  without mesh cache: {} instance buffers, {bytes} bytes", meshes.len());
+    println!("  with mesh cache:    {unique_buffers} instance buffers, {unique_bytes} bytes");
+}
+
+fn bench_mesh(c: &mut Criterion, name: &str, chunks: &Chunks, to_mesh: &[ChunkPosition]) {
+    let mut group = c.benchmark_group(format!("mesh {name} render distance 24"));
+    group.sample_size(10);
+    group.bench_function("no cache", |b| {
+        b.iter(|| {
+            for &chunk_position in to_mesh {
+                let chunk_refs = ChunkRefs::try_new(chunks, chunk_position).expect("All neighbours were generated.");
+                black_box(build_chunk_instance_data_with_stats(
+                    &chunk_refs,
+                    Lod::default(),
+                    AoEnabled::default(),
+                    GreedyMeshing::default(),
+                ));
+            }
+        });
+    });
+    group.bench_function("mesh cache", |b| {
+        b.iter(|| {
+            let mesh_cache = MeshCache::default();
+            for &chunk_position in to_mesh {
+                let chunk_refs = ChunkRefs::try_new(chunks, chunk_position).expect("All neighbours were generated.");
+                black_box(mesh_cache.get_or_build(
+                    &chunk_refs,
+                    Lod::default(),
+                    AoEnabled::default(),
+                    GreedyMeshing::default(),
+                ));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn bench_mesh_cache(c: &mut Criterion) {
+    let block_prototypes = load_block_prototypes();
+    set_block_registry(&block_prototypes);
+
+    let (chunks, to_mesh) = generate_world(&block_prototypes, &FlatTerrain { surface_y: 16 }, -1..=1);
+    report_gpu_memory(&chunks, &to_mesh);
+    bench_mesh(c, "flat world", &chunks, &to_mesh);
+
+    let (chunks, to_mesh) = generate_world(&block_prototypes, &NoiseTerrain::default(), 5..=7);
+    bench_mesh(c, "noise terrain", &chunks, &to_mesh);
+}
+
+criterion_group!(benches, bench_mesh_cache);
+criterion_main!(benches);
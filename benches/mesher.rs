@@ -0,0 +1,101 @@
+//! The full mesh build of a single chunk, from its 27 adjacent chunks to the packed quads of every layer.
+//! The noisy chunk is a worst case with faces of several blocks on every slice, the surface chunk is a typical one.
+//! The quad count of each chunk is printed once, so a change in speed can be told apart from a change in output.
+//! Each chunk is also meshed with [`GreedyMeshing`] off, the quad count there is what greedy merging saves.
+
+use std::hint::black_box;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use bevy::math::IVec3;
+use criterion::{Criterion, criterion_group, criterion_main};
+use talc::{
+    chunky::{
+        chunk::{CHUNK_SIZE, ChunkData, VoxelIndex, set_block_registry},
+        chunks_refs::ChunkRefs,
+        greedy_mesher_optimized::{AoEnabled, GreedyMeshing, build_chunk_instance_data_with_stats},
+        lod::Lod,
+        terrain::{NoiseTerrain, TerrainGenerator},
+    },
+    mod_manager::{
+        mod_loader::load_block_prototypes,
+        prototypes::{BlockPrototypes, Prototypes},
+    },
+    position::ChunkPosition,
+    utils::index_to_ivec3_bounds,
+};
+
+/// Air, stone and dirt scattered by a hash of the voxel position.
+struct Noisy;
+
+impl TerrainGenerator for Noisy {
+    fn generate(&self, block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition) -> ChunkData {
+        let block = |name| block_prototypes.get(name).expect("The base mod has air, stone and dirt.");
+        let blocks = [block("air"), block("air"), block("stone"), block("dirt")];
+
+        let mut chunk_data = ChunkData::filled(chunk_position, blocks[0]);
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let hash = (x * 73_856_093) ^ (y * 19_349_663) ^ (z * 83_492_791);
+                    chunk_data.set_block(VoxelIndex::new(x, y, z), blocks[hash % blocks.len()]);
+                }
+            }
+        }
+        chunk_data
+    }
+
+    fn vertical_extent(&self, _x: i32, _z: i32) -> RangeInclusive<i32> {
+        i32::MIN..=i32::MAX
+    }
+}
+
+fn chunk_refs(
+    block_prototypes: &BlockPrototypes,
+    generator: &dyn TerrainGenerator,
+    center: ChunkPosition,
+) -> ChunkRefs {
+    let adjacent_chunks = std::array::from_fn(|i| {
+        let chunk_position = center + ChunkPosition(index_to_ivec3_bounds(i as i32, 3) - IVec3::ONE);
+        Arc::new(generator.generate(block_prototypes, chunk_position))
+    });
+    ChunkRefs::new(adjacent_chunks, center)
+}
+
+fn bench_mesher(c: &mut Criterion) {
+    let block_prototypes = load_block_prototypes();
+    set_block_registry(&block_prototypes);
+
+    let chunks = [
+        (
+            "noisy chunk",
+            chunk_refs(&block_prototypes, &Noisy, ChunkPosition::new(0, 0, 0)),
+        ),
+        (
+            "surface chunk",
+            chunk_refs(&block_prototypes, &NoiseTerrain::default(), ChunkPosition::new(0, 6, 0)),
+        ),
+    ];
+    for (name, chunk_refs) in &chunks {
+        for greedy_meshing in [GreedyMeshing(true), GreedyMeshing(false)] {
+            let name = if greedy_meshing.0 { format!("{name} greedy") } else { format!("{name} per face") };
+            let (_, stats) =
+                build_chunk_instance_data_with_stats(chunk_refs, Lod::default(), AoEnabled::default(), greedy_meshing);
+            println!("{name}: {} quads", stats.quads());
+
+            c.bench_function(&format!("mesh build {name}"), |b| {
+                b.iter(|| {
+                    black_box(build_chunk_instance_data_with_stats(
+                        black_box(chunk_refs),
+                        Lod::default(),
+                        AoEnabled::default(),
+                        greedy_meshing,
+                    ))
+                });
+            });
+        }
+    }
+}
+
+criterion_group!(benches, bench_mesher);
+criterion_main!(benches);
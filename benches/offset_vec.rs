@@ -0,0 +1,50 @@
+//! Compares the counting sort in `make_offset_vec` against the comparison sort it replaced.
+
+use std::hint::black_box;
+
+use bevy::math::{IVec2, IVec3};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use talc::{player::render_distance::make_offset_vec, position::ChunkPosition};
+
+/// The previous implementation, kept as a reference point.
+fn make_offset_vec_comparison_sort(diameter: u32) -> Vec<ChunkPosition> {
+    let mut sampling_offsets = vec![];
+    let diameter = diameter as i32;
+    let radius = diameter / 2;
+    for x in -radius..radius {
+        for z in -radius..radius {
+            if IVec2::new(x, z).distance_squared(IVec2::ZERO) <= radius * radius {
+                for y in -radius..radius {
+                    sampling_offsets.push(ChunkPosition::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    sampling_offsets.sort_by(|a, b| {
+        a.distance_squared(IVec3::ZERO)
+            .cmp(&b.distance_squared(IVec3::ZERO))
+    });
+
+    sampling_offsets
+}
+
+fn bench_offset_vec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("make_offset_vec");
+    for distance in [12, 24, 48] {
+        group.bench_with_input(
+            BenchmarkId::new("comparison sort", distance),
+            &distance,
+            |b, &distance| b.iter(|| make_offset_vec_comparison_sort(black_box(distance))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("counting sort", distance),
+            &distance,
+            |b, &distance| b.iter(|| make_offset_vec(black_box(distance))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_offset_vec);
+criterion_main!(benches);
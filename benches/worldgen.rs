@@ -0,0 +1,74 @@
+//! Compares the default `Terrain` generator on surface chunks, with its noise set up once per chunk,
+//! against the per voxel loop it replaced.
+
+use std::hint::black_box;
+
+use bracket_noise::prelude::FastNoise;
+use criterion::{Criterion, criterion_group, criterion_main};
+use talc::{
+    chunky::{
+        chunk::{CHUNK_SIZE_I32, CHUNK_SIZE3, set_block_registry},
+        terrain::{SURFACE_AMPLITUDE, SURFACE_Y, Terrain},
+    },
+    mod_manager::{
+        mod_loader::load_block_prototypes,
+        prototypes::{BlockPrototypes, Prototypes},
+    },
+    position::{ChunkPosition, Position},
+};
+
+/// The previous implementation, kept as a reference point.
+fn generate_per_voxel(block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition) -> Vec<u16> {
+    let world_position = Position::from(chunk_position);
+    let mut fast_noise = FastNoise::new();
+    let mut voxels = Vec::with_capacity(CHUNK_SIZE3);
+    for z in 0..CHUNK_SIZE_I32 {
+        for y in 0..CHUNK_SIZE_I32 {
+            for x in 0..CHUNK_SIZE_I32 {
+                let wx = (x + world_position.x) as f32;
+                let wy = (y + world_position.y - SURFACE_Y) as f32;
+                let wz = (z + world_position.z) as f32;
+
+                fast_noise.set_frequency(0.0254);
+                let overhang = fast_noise.get_noise3d(wx, wy, wz) * 55.0;
+                fast_noise.set_frequency(0.002591);
+                let h = fast_noise.get_noise(wx + overhang, wz / 3.0) * SURFACE_AMPLITUDE as f32;
+
+                let name = if h > wy { "grass" } else { "air" };
+                voxels.push(block_prototypes.get(name).expect("Base mod has air and grass.").id);
+            }
+        }
+    }
+    voxels
+}
+
+fn bench_worldgen(c: &mut Criterion) {
+    let block_prototypes = load_block_prototypes();
+    set_block_registry(&block_prototypes);
+
+    let surface_chunks: Vec<ChunkPosition> = (0..4)
+        .map(|x| ChunkPosition::new(x, SURFACE_Y.div_euclid(CHUNK_SIZE_I32), 0))
+        .collect();
+
+    let terrain = Terrain::default();
+    let mut group = c.benchmark_group("generate surface chunks");
+    group.sample_size(20);
+    group.bench_function("per voxel noise", |b| {
+        b.iter(|| {
+            for &chunk_position in &surface_chunks {
+                black_box(generate_per_voxel(&block_prototypes, chunk_position));
+            }
+        });
+    });
+    group.bench_function("hoisted noise", |b| {
+        b.iter(|| {
+            for &chunk_position in &surface_chunks {
+                black_box(terrain.0.generate(&block_prototypes, chunk_position));
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_worldgen);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+//! Named sky presets for the camera's `Atmosphere`.
+//! Changing the [`AtmospherePreset`] resource swaps the scattering parameters of every atmosphere in the world.
+
+use bevy::{pbr::Atmosphere, prelude::*};
+
+/// The sky every `Atmosphere` component is set to. Defaults to earth.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum AtmospherePreset {
+    #[default]
+    Earth,
+    /// thin dusty air, a butterscotch sky with blue sunsets and no ozone.
+    Mars,
+    /// dense air scattering mostly green.
+    Alien,
+}
+
+impl AtmospherePreset {
+    /// The atmosphere parameters of this preset.
+    /// radii are shrunk from the real planets so the horizon curves within a few render distances.
+    #[must_use]
+    pub const fn atmosphere(self) -> Atmosphere {
+        match self {
+            Self::Earth => Atmosphere {
+                bottom_radius: 5_000.0,
+                top_radius: 64_600.0 * 3.,
+                ground_albedo: Vec3::splat(0.3),
+                rayleigh_density_exp_scale: 1.0 / 8_000.0,
+                rayleigh_scattering: Vec3::new(5.802e-5, 13.558e-5, 33.100e-5),
+                mie_density_exp_scale: 1.0 / 1_200.0,
+                mie_scattering: 3.996e-6,
+                mie_absorption: 0.444e-6,
+                mie_asymmetry: 0.8,
+                ozone_layer_altitude: 25_000.0,
+                ozone_layer_width: 30_000.0,
+                ozone_absorption: Vec3::new(0.650e-6, 1.881e-6, 0.085e-6),
+            },
+            Self::Mars => Atmosphere {
+                bottom_radius: 5_000.0,
+                top_radius: 64_600.0 * 3.,
+                ground_albedo: Vec3::new(0.45, 0.25, 0.15),
+                rayleigh_density_exp_scale: 1.0 / 11_000.0,
+                rayleigh_scattering: Vec3::new(19.918e-6, 13.57e-6, 5.75e-6),
+                mie_density_exp_scale: 1.0 / 11_000.0,
+                mie_scattering: 40.0e-6,
+                mie_absorption: 4.0e-6,
+                mie_asymmetry: 0.65,
+                ozone_layer_altitude: 25_000.0,
+                ozone_layer_width: 30_000.0,
+                ozone_absorption: Vec3::ZERO,
+            },
+            Self::Alien => Atmosphere {
+                bottom_radius: 5_000.0,
+                top_radius: 64_600.0 * 3.,
+                ground_albedo: Vec3::new(0.2, 0.3, 0.25),
+                rayleigh_density_exp_scale: 1.0 / 6_000.0,
+                rayleigh_scattering: Vec3::new(8.0e-5, 30.0e-5, 12.0e-5),
+                mie_density_exp_scale: 1.0 / 2_000.0,
+                mie_scattering: 8.0e-6,
+                mie_absorption: 1.0e-6,
+                mie_asymmetry: 0.75,
+                ozone_layer_altitude: 25_000.0,
+                ozone_layer_width: 30_000.0,
+                ozone_absorption: Vec3::new(1.5e-6, 0.2e-6, 1.5e-6),
+            },
+        }
+    }
+}
+
+pub struct AtmospherePresetPlugin;
+
+impl Plugin for AtmospherePresetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AtmospherePreset>()
+            .add_systems(Update, apply_atmosphere_preset);
+    }
+}
+
+/// Writes the preset into new atmospheres, and into all of them when the preset changes.
+#[allow(clippy::needless_pass_by_value)]
+fn apply_atmosphere_preset(preset: Res<AtmospherePreset>, mut atmospheres: Query<&mut Atmosphere>) {
+    for mut atmosphere in &mut atmospheres {
+        if preset.is_changed() || atmosphere.is_added() {
+            *atmosphere = preset.atmosphere();
+        }
+    }
+}
+
+#[test]
+fn switching_presets_updates_the_atmosphere() {
+    let mut app = App::new();
+    app.add_plugins(AtmospherePresetPlugin);
+    let camera = app.world_mut().spawn(AtmospherePreset::Alien.atmosphere()).id();
+
+    let atmosphere = |app: &App| {
+        app.world()
+            .get::<Atmosphere>(camera)
+            .expect("The camera has an atmosphere.")
+            .clone()
+    };
+
+    // new atmospheres take the current preset
+    app.update();
+    let earth = atmosphere(&app);
+    assert_eq!(earth.rayleigh_scattering, AtmospherePreset::Earth.atmosphere().rayleigh_scattering);
+    assert_eq!(earth.ozone_absorption, AtmospherePreset::Earth.atmosphere().ozone_absorption);
+
+    *app.world_mut().resource_mut::<AtmospherePreset>() = AtmospherePreset::Mars;
+    app.update();
+    let mars = atmosphere(&app);
+    let expected = AtmospherePreset::Mars.atmosphere();
+    assert_eq!(mars.rayleigh_scattering, expected.rayleigh_scattering);
+    assert_eq!(mars.ground_albedo, expected.ground_albedo);
+    assert_eq!(mars.ozone_absorption, Vec3::ZERO);
+    assert!((mars.mie_scattering - expected.mie_scattering).abs() < f32::EPSILON);
+    assert_ne!(mars.rayleigh_scattering, earth.rayleigh_scattering);
+}
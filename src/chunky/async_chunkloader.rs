@@ -1,28 +1,43 @@
-use std::{sync::Arc, vec::Drain};
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    sync::Arc,
+    time::Duration,
+    vec::Drain,
+};
 
 use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
     platform::collections::{HashMap, HashSet},
     prelude::*,
     render::primitives::Aabb,
-    tasks::{block_on, AsyncComputeTaskPool, Task},
 };
 
-use crate::mod_manager::prototypes::BlockPrototypes;
-use crate::position::{ChunkPosition, FloatingPosition};
+use crate::mod_manager::prototypes::{BiomePrototypes, BlockPrototype, BlockPrototypes};
+use crate::position::{ChunkPosition, FloatingPosition, Position, RelativePosition};
+use crate::utils::get_edging_chunks;
 use crate::{
     chunky::{
         chunk::{
             CHUNK_FLOAT_UP_BLOCKS_PER_SECOND, CHUNK_INITIAL_Y_OFFSET, CHUNK_SIZE_F32,
-            CHUNK_SIZE_I32, ChunkData,
+            CHUNK_SIZE_I32, ChunkData, VoxelIndex,
         },
-        lod::Lod,
+        lod::{Lod, LodPolicy},
+        terrain::{self, Terrain},
     },
-    render::chunk_material::RenderableChunk,
+    render::chunk_material::{ChunkLayer, ChunkLayers, RenderableChunk},
 };
-use crate::{player::render_distance::Scanner, smooth_transform::SmoothTransformTo};
-use futures_lite::future;
+use crate::player::key_bindings::{Action, KeyBindings};
+use crate::player::render_distance::{Scanner, scanner_chunk_position};
+use crate::smooth_transform::SmoothTransformTo;
 
-use super::{chunk::Chunk, chunks_refs::ChunkRefs, greedy_mesher_optimized};
+use super::{
+    chunk::Chunk,
+    chunk_saver::{self, ChunkSaver, read_chunk},
+    chunk_tasks::ChunkTasks,
+    chunks_refs::ChunkRefs,
+    greedy_mesher_optimized::{AoEnabled, ChunkMeshStats, GreedyMeshing, MeshCache},
+};
 
 pub struct AsyncChunkloaderPlugin;
 impl Plugin for AsyncChunkloaderPlugin {
@@ -32,72 +47,374 @@ impl Plugin for AsyncChunkloaderPlugin {
             "Default LOD must exactly equal the chunk size."
         );
 
-        app.add_systems(Update, start_worldgen_threads);
+        app.add_systems(
+            PostStartup,
+            (
+                terrain::apply_biomes.run_if(resource_exists::<BiomePrototypes>),
+                warm_start,
+            )
+                .chain(),
+        );
+        app.add_systems(Update, toggle_chunk_loading.before(start_worldgen_threads));
+        app.add_systems(Update, start_worldgen_threads.run_if(chunk_loading_running));
+        app.add_systems(
+            Update,
+            terrain::apply_biomes
+                .run_if(resource_exists_and_changed::<BiomePrototypes>)
+                .before(start_worldgen_threads),
+        );
         app.add_systems(Update, join_worldgen_threads);
-        app.add_systems(Update, start_mesh_threads);
+        app.add_systems(Update, apply_chunk_modifications.before(start_mesh_threads));
+        app.add_systems(Update, remesh_on_mesher_change.before(start_mesh_threads));
+        app.add_systems(Update, remesh_on_lod_change.before(start_mesh_threads));
+        app.add_systems(Update, start_mesh_threads.run_if(chunk_loading_running));
         app.add_systems(Update, join_mesh_threads);
-        app.add_systems(Update, unload_chunks);
-        app.add_systems(Update, unload_meshes);
+        app.add_systems(
+            Update,
+            release_pending_unloads
+                .before(unload_chunks)
+                .before(unload_meshes)
+                .run_if(chunk_loading_running),
+        );
+        app.add_systems(Update, unload_chunks.run_if(chunk_loading_running));
+        app.add_systems(Update, (chunk_saver::start_save_threads, chunk_saver::join_save_threads).after(unload_chunks));
+        app.add_systems(Update, unload_meshes.run_if(chunk_loading_running));
+        app.add_systems(Update, finish_sinking_chunks.before(join_mesh_threads));
+        app.add_systems(
+            Update,
+            reload_all_chunks
+                .before(start_worldgen_threads)
+                .before(join_worldgen_threads)
+                .before(join_mesh_threads),
+        );
+        app.add_event::<ChunkDataReady>();
+        app.add_event::<ChunkMeshed>();
         app.init_resource::<AsyncChunkloader>();
         app.init_resource::<Chunks>();
+        app.init_resource::<ChunkEntityIndex>();
+        app.init_resource::<ChunkLoaderBudget>();
+        app.init_resource::<ChunkLoadingPaused>();
+        app.init_resource::<ChunkUnloadAnimation>();
+        app.init_resource::<ChunkUnloadGrace>();
+        app.init_resource::<WarmStart>();
+        app.init_resource::<ChunkSaver>();
+        app.init_resource::<AoEnabled>();
+        app.init_resource::<GreedyMeshing>();
+        app.init_resource::<MeshCache>();
+        app.init_resource::<LodPolicy>();
+        app.init_resource::<KeyBindings>();
+        app.init_resource::<Terrain>();
+        app.register_diagnostic(Diagnostic::new(STALE_MESHES_DISCARDED).with_max_history_length(1));
+        app.register_diagnostic(Diagnostic::new(MESH_QUADS));
+        app.register_diagnostic(Diagnostic::new(MESH_EARLY_EXITS));
+        app.register_diagnostic(Diagnostic::new(MESH_CACHE_HITS));
+    }
+}
+
+/// Total mesh tasks whose result was thrown away, because their chunk left the mesh range while meshing.
+pub const STALE_MESHES_DISCARDED: DiagnosticPath =
+    DiagnosticPath::const_new("chunk_loader/stale_meshes_discarded");
+
+/// Quads per finished mesh task, averaged over the recent tasks.
+pub const MESH_QUADS: DiagnosticPath = DiagnosticPath::const_new("chunk_loader/mesh_quads");
+
+/// 1 for mesh tasks that exited early because all faces were culled, 0 otherwise. The average is the early exit rate.
+pub const MESH_EARLY_EXITS: DiagnosticPath = DiagnosticPath::const_new("chunk_loader/mesh_early_exits");
+
+/// 1 for mesh tasks that reused the quads of an identical chunk, 0 otherwise. See [`MeshCache`].
+pub const MESH_CACHE_HITS: DiagnosticPath = DiagnosticPath::const_new("chunk_loader/mesh_cache_hits");
+
+/// Sent when a chunk's voxels are in [`Chunks`], whether generated, reloaded or filled without a task.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkDataReady {
+    pub position: ChunkPosition,
+}
+
+/// Sent when a finished mesh is put on its chunk entity, including remeshes and meshes without any quads.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkMeshed {
+    pub position: ChunkPosition,
+}
+
+/// While true no chunk is queued, loaded, meshed or unloaded, so rendering can be profiled on a static world.
+/// Tasks already running still finish. The scanners catch up with the camera once loading resumes.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunkLoadingPaused(pub bool);
+
+/// Run condition of the systems [`ChunkLoadingPaused`] freezes.
+#[must_use]
+#[allow(clippy::needless_pass_by_value)]
+pub fn chunk_loading_running(paused: Option<Res<ChunkLoadingPaused>>) -> bool {
+    !paused.is_some_and(|paused| paused.0)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn toggle_chunk_loading(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    key_bindings: Res<KeyBindings>,
+    mut paused: ResMut<ChunkLoadingPaused>,
+) {
+    if keys.is_some_and(|keys| key_bindings.just_pressed(&keys, Action::ToggleChunkLoading)) {
+        paused.0 = !paused.0;
+        info!("Chunk loading {}.", if paused.0 { "paused" } else { "resumed" });
+    }
+}
+
+/// Limits how much chunk loading work runs at once.
+/// Insert this resource before adding [`AsyncChunkloaderPlugin`] to override the defaults.
+#[derive(Resource, Clone, Copy, Debug)]
+#[allow(clippy::struct_field_names)]
+pub struct ChunkLoaderBudget {
+    /// Worldgen tasks allowed in flight at once.
+    pub max_worldgen_tasks: usize,
+    /// Chunks filled on the main thread per frame, without a task, see [`TerrainGenerator::is_surface_chunk`].
+    ///
+    /// [`TerrainGenerator::is_surface_chunk`]: terrain::TerrainGenerator::is_surface_chunk
+    pub max_filled_chunks: usize,
+    /// Mesh tasks allowed in flight at once.
+    pub max_mesh_tasks: usize,
+    /// Scanners stop queueing chunks for worldgen while this many worldgen tasks are running.
+    pub max_scanner_data_tasks: usize,
+    /// Chunk positions each scanner resolves per frame.
+    pub max_scans: usize,
+    /// Chunk saves allowed in flight at once.
+    pub max_save_tasks: usize,
+    /// Finished worldgen tasks, and finished mesh tasks, handled per frame. The rest wait for the next frame.
+    pub max_joins: usize,
+}
+
+impl Default for ChunkLoaderBudget {
+    fn default() -> Self {
+        Self {
+            max_worldgen_tasks: 64,
+            max_filled_chunks: 1024,
+            max_mesh_tasks: 32,
+            max_scanner_data_tasks: 9,
+            max_scans: 26000,
+            max_save_tasks: 8,
+            max_joins: 64,
+        }
+    }
+}
+
+/// The most [`WarmStart::radius`] is allowed to be. The voxels reach a chunk further than the meshes,
+/// so radius 2 already generates up to 7³ = 343 chunks before the first frame, and radius 4 would be 11³ = 1331.
+pub const MAX_WARM_START_RADIUS: u32 = 2;
+
+/// Chunks generated and meshed on the main thread before the first frame, so the camera does not start out in empty space.
+/// Insert this resource before adding [`AsyncChunkloaderPlugin`] to override the default.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct WarmStart {
+    /// Chunks of a scanner's area up to this many chunks away from it along each axis are meshed at startup,
+    /// 0 turns it off. Capped at [`MAX_WARM_START_RADIUS`], the game hangs until they are done.
+    pub radius: u32,
+}
+
+impl Default for WarmStart {
+    fn default() -> Self {
+        Self { radius: 0 }
+    }
+}
+
+/// How chunks leave view when they fall out of the mesh range, mirroring how they float up when spawned.
+/// Insert this resource before adding [`AsyncChunkloaderPlugin`] to override the defaults.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ChunkUnloadAnimation {
+    /// Without the animation, meshes are dropped as soon as the chunk leaves the mesh range.
+    pub enabled: bool,
+    /// How far chunks sink before their meshes are dropped, in blocks.
+    pub depth: f32,
+    pub blocks_per_second: f32,
+}
+
+impl Default for ChunkUnloadAnimation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            depth: -CHUNK_INITIAL_Y_OFFSET,
+            blocks_per_second: CHUNK_FLOAT_UP_BLOCKS_PER_SECOND * 2.,
+        }
     }
 }
 
-pub const MAX_WORLDGEN_TASKS: usize = 64;
-pub const MAX_MESH_TASKS: usize = 32;
+/// How long a chunk stays loaded, and meshed, after leaving the unload range.
+/// A camera moving back and forth over the edge of the range keeps the chunks it passes instead of reloading them.
+/// Zero unloads chunks as soon as they leave. Insert this resource before adding [`AsyncChunkloaderPlugin`] to override it.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunkUnloadGrace(pub Duration);
+
+/// A chunk sinking out of view. Its meshes are dropped once it reaches the bottom, see [`ChunkUnloadAnimation`].
+#[derive(Component)]
+pub struct SinkingChunk;
 
 #[derive(Resource, Default)]
 pub struct Chunks(pub HashMap<ChunkPosition, Arc<ChunkData>>);
 
+impl Chunks {
+    /// Bytes held by every loaded chunk, see [`ChunkData::heap_bytes`].
+    #[must_use]
+    pub fn total_bytes(&self) -> usize {
+        self.0
+            .values()
+            .map(|chunk_data| size_of::<ChunkData>() + chunk_data.heap_bytes())
+            .sum()
+    }
+
+    /// World y of the highest meshable block at or below world y `top` in the column at world (`x`, `z`),
+    /// edits included. Walks down the loaded chunks of the column from `top`, and gives up with `None`
+    /// at the first chunk that is not loaded. See [`TerrainGenerator::surface_height`] for a prediction
+    /// which needs no loaded chunks.
+    ///
+    /// [`TerrainGenerator::surface_height`]: terrain::TerrainGenerator::surface_height
+    #[must_use]
+    pub fn surface_height(&self, x: i32, z: i32, top: i32) -> Option<i32> {
+        let (mut chunk_position, local) = Position::new(x, top, z).to_chunk_local();
+        let mut local_top = local.y;
+        while let Some(chunk_data) = self.0.get(&chunk_position) {
+            let local_y = (0..=local_top).rev().find(|&y| {
+                let index = VoxelIndex::from(RelativePosition::new(local.x, y, local.z));
+                chunk_data.get_block(index).is_meshable
+            });
+            if let Some(local_y) = local_y {
+                return Some(Position::from(chunk_position).y + local_y);
+            }
+            local_top = CHUNK_SIZE_I32 - 1;
+            chunk_position.0.y -= 1;
+        }
+        None
+    }
+}
+
+/// The `Chunk` entity spawned for each loaded chunk position.
+/// Kept in sync by the chunkloader so chunk entities can be found without scanning all of them.
+#[derive(Resource, Default)]
+pub struct ChunkEntityIndex {
+    pub entities: HashMap<ChunkPosition, Entity>,
+    /// See [`ChunkEntityIndex::lookups`].
+    #[cfg(test)]
+    lookups: AtomicUsize,
+}
+
+impl ChunkEntityIndex {
+    /// The entity of the loaded chunk at `chunk_position`, for attaching components to a specific chunk.
+    /// It carries a [`Chunk`] with the same position until the chunk unloads.
+    #[must_use]
+    pub fn get(&self, chunk_position: ChunkPosition) -> Option<Entity> {
+        #[cfg(test)]
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.entities.get(&chunk_position).copied()
+    }
+
+    #[must_use]
+    pub fn contains(&self, chunk_position: ChunkPosition) -> bool {
+        #[cfg(test)]
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.entities.contains_key(&chunk_position)
+    }
+
+    /// Indexes the entity of a newly spawned chunk, returning the entity it replaces.
+    pub fn insert(&mut self, chunk_position: ChunkPosition, entity_id: Entity) -> Option<Entity> {
+        #[cfg(test)]
+        {
+            *self.lookups.get_mut() += 1;
+        }
+        self.entities.insert(chunk_position, entity_id)
+    }
+
+    pub fn remove(&mut self, chunk_position: ChunkPosition) -> Option<Entity> {
+        #[cfg(test)]
+        {
+            *self.lookups.get_mut() += 1;
+        }
+        self.entities.remove(&chunk_position)
+    }
+
+    /// Calls to the methods above so far, each of which is a single hash map access.
+    /// Lets tests check that chunk operations cost a constant number of lookups instead of scanning every chunk.
+    /// Only counted in tests, so release builds don't pay for the atomic on every lookup.
+    #[cfg(test)]
+    #[must_use]
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct AsyncChunkloader {
     pub load_chunk_queue: Vec<ChunkPosition>,
     pub unload_chunk_queue: Vec<ChunkPosition>,
     pub load_mesh_queue: Vec<ChunkRefs>,
     pub unload_mesh_queue: Vec<ChunkPosition>,
-    pub worldgen_tasks: HashMap<ChunkPosition, Task<ChunkData>>,
-    pub mesh_tasks: HashMap<ChunkPosition, Task<Option<RenderableChunk>>>,
+    /// Chunks out of the unload range, by the [`Time::elapsed`] they left it at.
+    /// They move to `unload_chunk_queue` once out for the whole [`ChunkUnloadGrace`], and are dropped from here if they come back.
+    pub pending_chunk_unloads: HashMap<ChunkPosition, Duration>,
+    /// Same as `pending_chunk_unloads`, for `unload_mesh_queue`.
+    pub pending_mesh_unloads: HashMap<ChunkPosition, Duration>,
+    pub worldgen_tasks: ChunkTasks<ChunkData>,
+    pub mesh_tasks: ChunkTasks<(ChunkLayers, ChunkMeshStats)>,
+    /// Block edits waiting to be applied. Edits to a chunk that is not loaded yet are kept until it is.
+    pub chunk_modifications: HashMap<ChunkPosition, Vec<(RelativePosition, &'static BlockPrototype)>>,
+    /// See [`STALE_MESHES_DISCARDED`].
+    pub stale_meshes_discarded: u64,
+    /// The lod each chunk was last queued for meshing at, see [`LodPolicy`].
+    pub mesh_lods: HashMap<ChunkPosition, Lod>,
+}
+
+/// Squared distance from `origin` to the center of the chunk, in blocks.
+fn distance_to_chunk(origin: FloatingPosition, chunk_position: ChunkPosition) -> f32 {
+    let chunk_center = FloatingPosition::from(chunk_position).0 + CHUNK_SIZE_F32 / 2.;
+    origin.0.distance_squared(chunk_center)
 }
 
 impl AsyncChunkloader {
+    /// Nearest chunks to `prefetch_origin` first, see [`Scanner::prefetch_origin`].
+    /// Returns the chunks that `needs_task`, then the chunks filled without one, each within its budget.
+    /// The rest stay queued for later frames.
     fn get_chunks_to_load(
         &mut self,
-        player_position: FloatingPosition,
-    ) -> Drain<'_, ChunkPosition> {
-        let player_chunk_position: ChunkPosition = player_position.into();
-
-        let tasks_left = (MAX_WORLDGEN_TASKS as i32 - self.worldgen_tasks.len() as i32)
-            .min(self.load_chunk_queue.len() as i32)
-            .max(0) as usize;
+        budget: &ChunkLoaderBudget,
+        prefetch_origin: FloatingPosition,
+        needs_task: impl Fn(ChunkPosition) -> bool,
+    ) -> (Vec<ChunkPosition>, Vec<ChunkPosition>) {
+        let tasks_left = budget.max_worldgen_tasks.saturating_sub(self.worldgen_tasks.len());
 
         self.load_chunk_queue.sort_by(|a, b| {
-            a.0.distance_squared(player_chunk_position.0)
-                .cmp(&b.0.distance_squared(player_chunk_position.0))
+            distance_to_chunk(prefetch_origin, *a).total_cmp(&distance_to_chunk(prefetch_origin, *b))
         });
 
-        self.load_chunk_queue.drain(0..tasks_left)
+        let mut to_spawn = Vec::new();
+        let mut to_fill = Vec::new();
+        self.load_chunk_queue.retain(|&chunk_position| {
+            let (chunks, limit) = if needs_task(chunk_position) {
+                (&mut to_spawn, tasks_left)
+            } else {
+                (&mut to_fill, budget.max_filled_chunks)
+            };
+            if chunks.len() < limit {
+                chunks.push(chunk_position);
+                return false;
+            }
+            true
+        });
+        (to_spawn, to_fill)
     }
 
     fn get_chunks_to_unload(&mut self) -> Drain<'_, ChunkPosition> {
         self.unload_chunk_queue.drain(..)
     }
 
-    fn get_chunks_to_mesh(&mut self, player_position: FloatingPosition) -> Drain<'_, ChunkRefs> {
-        let player_chunk_position: ChunkPosition = player_position.into();
-
-        let tasks_left = (MAX_MESH_TASKS as i32 - self.mesh_tasks.len() as i32)
+    fn get_chunks_to_mesh(
+        &mut self,
+        budget: &ChunkLoaderBudget,
+        prefetch_origin: FloatingPosition,
+    ) -> Drain<'_, ChunkRefs> {
+        let tasks_left = (budget.max_mesh_tasks as i32 - self.mesh_tasks.len() as i32)
             .min(self.load_mesh_queue.len() as i32)
             .max(0) as usize;
 
         self.load_mesh_queue.sort_by(|a, b| {
-            a.center_chunk_position
-                .0
-                .distance_squared(player_chunk_position.0)
-                .cmp(
-                    &b.center_chunk_position
-                        .0
-                        .distance_squared(player_chunk_position.0),
-                )
+            distance_to_chunk(prefetch_origin, a.center_chunk_position)
+                .total_cmp(&distance_to_chunk(prefetch_origin, b.center_chunk_position))
         });
 
         self.load_mesh_queue.drain(0..tasks_left)
@@ -106,31 +423,30 @@ impl AsyncChunkloader {
     fn get_chunks_to_unmesh(&mut self) -> Drain<'_, ChunkPosition> {
         self.unload_mesh_queue.drain(..)
     }
+
+    fn discard_stale_meshes(&mut self, count: u64, diagnostics: &mut Diagnostics) {
+        if count == 0 {
+            return;
+        }
+        self.stale_meshes_discarded += count;
+        let total = self.stale_meshes_discarded;
+        diagnostics.add_measurement(&STALE_MESHES_DISCARDED, || total as f64);
+    }
 }
 
 fn spawn_chunk_as_bevy_entity(
     chunk_data: ChunkData,
     chunk_entities: &mut Chunks,
-    timer: &Time,
+    chunk_entity_index: &mut ChunkEntityIndex,
     commands: &mut Commands,
-    chunk_canididates: Query<(Entity, &Chunk)>,
 ) {
     let chunk_position = chunk_data.position;
-    for (entity_id, chunk) in chunk_canididates.iter() {
-        if chunk.position == chunk_position {
-            if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
-                entity_commands.despawn();
-                break;
-            }
-        }
-    }
 
-    commands.spawn((
+    let entity_id = commands.spawn((
         Chunk {
             position: chunk_position,
         },
         SmoothTransformTo::new(
-            timer,
             FloatingPosition::new(0., -CHUNK_INITIAL_Y_OFFSET, 0.),
             CHUNK_FLOAT_UP_BLOCKS_PER_SECOND,
         ),
@@ -140,147 +456,1267 @@ fn spawn_chunk_as_bevy_entity(
                 + FloatingPosition::new(0., CHUNK_INITIAL_Y_OFFSET, 0.))
             .0,
         ),
-    ));
+    )).id();
+
+    if let Some(old_entity_id) = chunk_entity_index.insert(chunk_position, entity_id) {
+        if let Ok(mut entity_commands) = commands.get_entity(old_entity_id) {
+            entity_commands.despawn();
+        }
+    }
 
     chunk_entities
         .0
         .insert(chunk_position, Arc::new(chunk_data));
+    commands.send_event(ChunkDataReady {
+        position: chunk_position,
+    });
 }
 
 #[allow(clippy::needless_pass_by_value)]
+#[allow(clippy::too_many_arguments)]
 fn start_worldgen_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
+    budget: Res<ChunkLoaderBudget>,
     block_prototypes: Res<BlockPrototypes>,
-    scanners: Query<&GlobalTransform, With<Scanner>>,
+    terrain: Res<Terrain>,
+    scanners: Query<(&GlobalTransform, &Scanner)>,
+    mut chunk_entities: ResMut<Chunks>,
+    mut chunk_entity_index: ResMut<ChunkEntityIndex>,
+    mut chunk_saver: ResMut<ChunkSaver>,
+    mut commands: Commands,
 ) {
-    let task_pool = AsyncComputeTaskPool::get();
-    let scanner = scanners.single().unwrap();
-    let player_position = FloatingPosition(scanner.translation());
+    let (scanner_transform, scanner) = scanners.single().unwrap();
+    let prefetch_origin = scanner.prefetch_origin(scanner_transform.translation());
+
+    // chunks unloaded before their edits were saved come back as they were.
+    chunkloader.load_chunk_queue.retain(|&chunk_position| {
+        let Some(chunk_data) = chunk_saver.reload(chunk_position) else {
+            return true;
+        };
+        spawn_chunk_as_bevy_entity(chunk_data, &mut chunk_entities, &mut chunk_entity_index, &mut commands);
+        false
+    });
+
+    // chunks entirely above or below the terrain surface are a single block, no task needed.
+    // saved chunks may have been edited into anything, so their task reads them back from disk.
+    let (to_load, to_fill) = chunkloader.get_chunks_to_load(&budget, prefetch_origin, |chunk_position| {
+        chunk_saver.saved_chunks.contains(&chunk_position) || terrain.0.is_surface_chunk(chunk_position)
+    });
+    for chunk_position in to_fill {
+        let _span = info_span!("worldgen_filled", chunk = %chunk_position).entered();
+        spawn_chunk_as_bevy_entity(
+            terrain.0.generate(&block_prototypes, chunk_position),
+            &mut chunk_entities,
+            &mut chunk_entity_index,
+            &mut commands,
+        );
+    }
 
-    let to_load: Vec<ChunkPosition> = chunkloader.get_chunks_to_load(player_position).collect();
     for chunk_position in to_load {
         let prototypes = block_prototypes.clone();
-        let task = task_pool.spawn(async move { ChunkData::generate(&prototypes, chunk_position) });
-        chunkloader.worldgen_tasks.insert(chunk_position, task);
+        let terrain = terrain.0.clone();
+        let saved_path = chunk_saver.saved_path(chunk_position);
+        chunkloader.worldgen_tasks.spawn(chunk_position, async move {
+            let _span = info_span!("worldgen", chunk = %chunk_position).entered();
+            saved_path
+                .and_then(|path| read_chunk(&path, &prototypes))
+                .unwrap_or_else(|| terrain.generate(&prototypes, chunk_position))
+        });
     }
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn join_worldgen_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
+    budget: Res<ChunkLoaderBudget>,
     mut chunk_entities: ResMut<Chunks>,
-    timer: Res<Time>,
+    mut chunk_entity_index: ResMut<ChunkEntityIndex>,
+    mut commands: Commands,
+) {
+    for (_, chunk_data) in chunkloader.worldgen_tasks.join(budget.max_joins) {
+        spawn_chunk_as_bevy_entity(chunk_data, &mut chunk_entities, &mut chunk_entity_index, &mut commands);
+    }
+}
+
+/// Loads and meshes the chunks around each scanner right away, see [`WarmStart`].
+/// They start at rest instead of floating up, the async loader takes over from the next frame.
+#[allow(clippy::needless_pass_by_value)]
+#[allow(clippy::too_many_arguments)]
+fn warm_start(
+    warm_start: Res<WarmStart>,
+    block_prototypes: Option<Res<BlockPrototypes>>,
+    terrain: Res<Terrain>,
+    mesh_cache: Res<MeshCache>,
+    (ao_enabled, greedy_meshing, lod_policy): (Res<AoEnabled>, Res<GreedyMeshing>, Res<LodPolicy>),
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    mut chunks: ResMut<Chunks>,
+    mut chunk_entity_index: ResMut<ChunkEntityIndex>,
+    mut chunk_saver: ResMut<ChunkSaver>,
+    scanners: Query<(&Transform, &Scanner)>,
     mut commands: Commands,
-    chunk_canididates: Query<(Entity, &Chunk)>,
 ) {
-    chunkloader.worldgen_tasks.retain(|_, task| {
-        // check on our worldgen task to see how it's doing :)
-        let status = block_on(future::poll_once(task));
+    let radius = warm_start.radius.min(MAX_WARM_START_RADIUS) as i32;
+    let Some(block_prototypes) = block_prototypes.filter(|_| radius > 0) else {
+        return;
+    };
+    let _span = info_span!("warm_start").entered();
 
-        // keep the entry in our task vector only if the task is not done yet
-        let retain = status.is_none();
+    for (scanner_transform, scanner) in &scanners {
+        let center = scanner_chunk_position(scanner_transform.translation);
+        // only chunks the scanner would load anyway, so they unload like any other once the scanner moves away
+        let near = |offsets: &[ChunkPosition], radius: i32| -> Vec<ChunkPosition> {
+            offsets
+                .iter()
+                .filter(|offset| offset.abs().max_element() <= radius)
+                .map(|offset| center + *offset)
+                .collect()
+        };
 
-        // if this task is done, handle the data it returned!
-        if let Some(chunk_component) = status {
-            spawn_chunk_as_bevy_entity(chunk_component, &mut chunk_entities, &timer, &mut commands, chunk_canididates);
+        // meshing samples the neighbours, so the voxels reach a chunk further than the meshes
+        for chunk_position in near(&scanner.worldgen_sampling_offsets, radius + 1) {
+            if chunks.0.contains_key(&chunk_position) {
+                continue;
+            }
+            let chunk_data = chunk_saver
+                .reload(chunk_position)
+                .or_else(|| {
+                    let path = chunk_saver.saved_path(chunk_position)?;
+                    read_chunk(&path, &block_prototypes)
+                })
+                .unwrap_or_else(|| terrain.0.generate(&block_prototypes, chunk_position));
+            spawn_chunk_as_bevy_entity(chunk_data, &mut chunks, &mut chunk_entity_index, &mut commands);
         }
 
-        retain
-    });
+        for chunk_position in near(&scanner.mesh_sampling_offsets, radius) {
+            let (Some(chunk_refs), Some(entity_id)) = (
+                ChunkRefs::try_new(&chunks, chunk_position),
+                chunk_entity_index.get(chunk_position),
+            ) else {
+                continue;
+            };
+            let lod = lod_policy.lod_of(center, chunk_position);
+            let (layers, _) = mesh_cache.get_or_build(&chunk_refs, lod, *ao_enabled, *greedy_meshing);
+            chunkloader.mesh_lods.insert(chunk_position, lod);
+
+            let mut entity_commands = commands.entity(entity_id);
+            entity_commands
+                .remove::<SmoothTransformTo>()
+                .insert(Transform::from_translation(FloatingPosition::from(chunk_position).0));
+            insert_chunk_layers(&mut entity_commands, layers);
+            commands.send_event(ChunkMeshed {
+                position: chunk_position,
+            });
+        }
+    }
+}
+
+/// Writes pending block edits into the loaded chunks, then queues the edited chunks for remeshing and marks them for saving.
+/// Chunks still shared with a mesh task are copied first, so running tasks keep their snapshot.
+pub(crate) fn apply_chunk_modifications(
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    mut chunks: ResMut<Chunks>,
+    mut chunk_saver: ResMut<ChunkSaver>,
+) {
+    if chunkloader.chunk_modifications.is_empty() {
+        return;
+    }
+
+    let mut to_remesh = HashSet::new();
+    chunkloader
+        .chunk_modifications
+        .retain(|chunk_position, modifications| {
+            let Some(chunk_data) = chunks.0.get_mut(chunk_position) else {
+                return true;
+            };
+            let chunk_data = Arc::make_mut(chunk_data);
+
+            to_remesh.insert(*chunk_position);
+            chunk_saver.dirty_chunks.insert(*chunk_position);
+            for (relative_position, block) in modifications.drain(..) {
+                chunk_data.set_block(relative_position.into(), block);
+                // Neighbours sample this voxel for face culling and AO.
+                for edging_chunk in get_edging_chunks(Position::from(relative_position)) {
+                    to_remesh.insert(*chunk_position + edging_chunk);
+                }
+            }
+
+            false
+        });
+
+    for chunk_position in to_remesh {
+        // Chunks missing a neighbour were never meshed, so there is nothing to update.
+        let Some(chunk_refs) = ChunkRefs::try_new(&chunks, chunk_position) else {
+            continue;
+        };
+        chunkloader
+            .load_mesh_queue
+            .retain(|queued| queued.center_chunk_position != chunk_position);
+        chunkloader.load_mesh_queue.push(chunk_refs);
+    }
 }
 
+/// Queues every meshed chunk for remeshing when [`AoEnabled`] or [`GreedyMeshing`] is toggled.
 #[allow(clippy::needless_pass_by_value)]
-fn start_mesh_threads(
+fn remesh_on_mesher_change(
     mut chunkloader: ResMut<AsyncChunkloader>,
+    ao_enabled: Res<AoEnabled>,
+    greedy_meshing: Res<GreedyMeshing>,
+    chunks: Res<Chunks>,
+) {
+    let toggled = (ao_enabled.is_changed() && !ao_enabled.is_added())
+        || (greedy_meshing.is_changed() && !greedy_meshing.is_added());
+    if !toggled {
+        return;
+    }
+    // loaded chunks outside the mesh range have no mesh to update, queued ones pick up the new settings anyway.
+    let to_remesh: Vec<ChunkRefs> = chunkloader
+        .mesh_lods
+        .keys()
+        .filter_map(|&chunk_position| ChunkRefs::try_new(&chunks, chunk_position))
+        .collect();
+    let AsyncChunkloader {
+        load_mesh_queue,
+        mesh_lods,
+        ..
+    } = &mut *chunkloader;
+    load_mesh_queue.retain(|queued| !mesh_lods.contains_key(&queued.center_chunk_position));
+    chunkloader.load_mesh_queue.extend(to_remesh);
+}
+
+/// Queues meshed chunks for remeshing when the scanner moves them across a [`LodPolicy`] threshold, or the policy changes.
+#[allow(clippy::needless_pass_by_value)]
+fn remesh_on_lod_change(
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    lod_policy: Res<LodPolicy>,
+    chunks: Res<Chunks>,
     scanners: Query<&GlobalTransform, With<Scanner>>,
+    mut last_scanner_chunk: Local<Option<ChunkPosition>>,
 ) {
-    let task_pool = AsyncComputeTaskPool::get();
-    let scanner = scanners.single().unwrap();
-    let player_position = FloatingPosition(scanner.translation());
+    let Ok(scanner_transform) = scanners.single() else {
+        return;
+    };
+    let scanner_chunk = scanner_chunk_position(scanner_transform.translation());
+    if *last_scanner_chunk == Some(scanner_chunk) && !lod_policy.is_changed() {
+        return;
+    }
+    *last_scanner_chunk = Some(scanner_chunk);
 
-    let to_mesh: Vec<ChunkRefs> = chunkloader.get_chunks_to_mesh(player_position).collect();
+    let to_remesh: Vec<ChunkRefs> = chunkloader
+        .mesh_lods
+        .iter()
+        .filter(|&(&chunk_position, &lod)| lod_policy.lod_of(scanner_chunk, chunk_position) != lod)
+        .filter_map(|(&chunk_position, _)| ChunkRefs::try_new(&chunks, chunk_position))
+        .collect();
+    for chunk_refs in to_remesh {
+        let chunk_position = chunk_refs.center_chunk_position;
+        chunkloader
+            .load_mesh_queue
+            .retain(|queued| queued.center_chunk_position != chunk_position);
+        chunkloader.load_mesh_queue.push(chunk_refs);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn start_mesh_threads(
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    budget: Res<ChunkLoaderBudget>,
+    ao_enabled: Res<AoEnabled>,
+    greedy_meshing: Res<GreedyMeshing>,
+    mesh_cache: Res<MeshCache>,
+    lod_policy: Res<LodPolicy>,
+    scanners: Query<(&GlobalTransform, &Scanner)>,
+) {
+    let (scanner_transform, scanner) = scanners.single().unwrap();
+    let prefetch_origin = scanner.prefetch_origin(scanner_transform.translation());
+    let scanner_chunk = scanner_chunk_position(scanner_transform.translation());
+
+    let to_mesh: Vec<ChunkRefs> = chunkloader.get_chunks_to_mesh(&budget, prefetch_origin).collect();
     for chunk_refs in to_mesh {
         let k = chunk_refs.center_chunk_position;
-        let task = task_pool.spawn(async move {
-            greedy_mesher_optimized::build_chunk_instance_data(
-                &chunk_refs,
-                super::lod::Lod::default(),
-            )
-        });
-        chunkloader.mesh_tasks.insert(k, task);
+        let lod = lod_policy.lod_of(scanner_chunk, k);
+        let ao_enabled = *ao_enabled;
+        let greedy_meshing = *greedy_meshing;
+        let mesh_cache = mesh_cache.clone();
+        chunkloader.mesh_lods.insert(k, lod);
+        chunkloader
+            .mesh_tasks
+            .spawn(k, async move { mesh_cache.get_or_build(&chunk_refs, lod, ao_enabled, greedy_meshing) });
     }
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn join_mesh_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    budget: Res<ChunkLoaderBudget>,
+    chunk_entity_index: Res<ChunkEntityIndex>,
+    sinking_chunks: Query<&Transform, With<SinkingChunk>>,
+    scanners: Query<(&GlobalTransform, &Scanner)>,
+    mut diagnostics: Diagnostics,
     mut commands: Commands,
 ) {
-    chunkloader.mesh_tasks.retain(|chunk_position, task| {
-        // check on our mesh task to see how it's doing :)
-        let status = block_on(future::poll_once(task));
+    let mut stale_meshes = 0;
+    for (chunk_position, (layers, stats)) in chunkloader.mesh_tasks.join(budget.max_joins) {
+        diagnostics.add_measurement(&MESH_QUADS, || f64::from(stats.quads()));
+        diagnostics.add_measurement(&MESH_EARLY_EXITS, || f64::from(u8::from(stats.early_exit)));
+        diagnostics.add_measurement(&MESH_CACHE_HITS, || f64::from(u8::from(stats.cached)));
 
-        // keep the entry in our task vector only if the task is not done yet
-        let Some(renderable_chunk_optional) = status else {
-            return true;
+        // the chunk may have been unloaded while meshing. if it was loaded again since, its terrain is unchanged and the mesh still fits.
+        let Some((entity_id, mut entity_commands)) = chunk_entity_index
+            .get(chunk_position)
+            .and_then(|entity_id| Some((entity_id, commands.get_entity(entity_id).ok()?)))
+        else {
+            stale_meshes += 1;
+            continue;
         };
 
-        // if this task is done, handle the data it returned!
-        if let Some(renderable_chunk) = renderable_chunk_optional {
-            // todo: refactor to use bevy indexes when the update drops.
-            for (entity_id, chunk) in chunk_canididates.iter() {
-                if chunk.position == *chunk_position {
-                    if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
-                        entity_commands.insert(renderable_chunk);
-                        break;
-                    }
-                }
+        // back in the mesh range before it finished sinking, so it floats back up instead.
+        // a remesh finishing while it is still out of range would only pull it back into view.
+        if let Ok(transform) = sinking_chunks.get(entity_id) {
+            let in_mesh_range = scanners.iter().any(|(scanner_transform, scanner)| {
+                scanner.retains_mesh(scanner_chunk_position(scanner_transform.translation()), chunk_position)
+            });
+            if !in_mesh_range {
+                stale_meshes += 1;
+                continue;
             }
+            let rest = FloatingPosition::from(chunk_position).0;
+            entity_commands.remove::<SinkingChunk>().insert(SmoothTransformTo::new(
+                FloatingPosition(rest - transform.translation),
+                CHUNK_FLOAT_UP_BLOCKS_PER_SECOND,
+            ));
         }
+        insert_chunk_layers(&mut entity_commands, layers);
+        commands.send_event(ChunkMeshed {
+            position: chunk_position,
+        });
+    }
+    chunkloader.discard_stale_meshes(stale_meshes, &mut diagnostics);
+}
 
-        false
-    });
+/// Replaces the meshes of a chunk entity.
+/// The opaque layer goes on the chunk entity itself, every other layer on a child entity.
+/// A remesh may find the chunk empty or occluded now, so layers missing from `layers` are dropped.
+fn insert_chunk_layers(entity_commands: &mut EntityCommands, mut layers: ChunkLayers) {
+    entity_commands.despawn_related::<Children>();
+    match layers.remove(&ChunkLayer::Opaque) {
+        Some(renderable_chunk) => entity_commands.insert(renderable_chunk),
+        None => entity_commands.try_remove::<RenderableChunk>(),
+    };
+    for renderable_chunk in layers.into_values() {
+        entity_commands.with_child(renderable_chunk);
+    }
 }
 
 #[allow(clippy::needless_pass_by_value)]
-fn unload_chunks(
+pub(crate) fn unload_chunks(
     mut chunkloader: ResMut<AsyncChunkloader>,
     mut chunk_entities: ResMut<Chunks>,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    mut chunk_entity_index: ResMut<ChunkEntityIndex>,
+    mut chunk_saver: ResMut<ChunkSaver>,
     mut commands: Commands,
 ) {
     let to_unload: HashSet<ChunkPosition> = chunkloader.get_chunks_to_unload().collect();
 
-    // todo: refactor to use bevy indexes when the update drops.
-    for (entity_id, chunk) in chunk_canididates.iter() {
-        if to_unload.contains(&chunk.position) {
+    for chunk_position in to_unload {
+        if let Some(entity_id) = chunk_entity_index.remove(chunk_position) {
             if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
                 entity_commands.despawn();
             }
         }
+        if let Some(chunk_data) = chunk_entities.0.remove(&chunk_position) {
+            chunk_saver.unload(chunk_data);
+        }
+        chunkloader.worldgen_tasks.remove(&chunk_position);
+        chunkloader.chunk_modifications.remove(&chunk_position);
     }
+}
 
-    for chunk_position in to_unload {
-        chunk_entities.0.remove(&chunk_position);
-        chunkloader.worldgen_tasks.remove(&chunk_position);
+/// Unloads every chunk, cancels every task and empties every queue, then has the scanners rescan their whole area.
+/// Tests the loader under memory pressure, and starts over fresh after a long session.
+/// Edited chunks go to the [`ChunkSaver`] like any unloaded chunk, and pending edits wait for their chunk to load again.
+/// The rescan loads edited chunks back from the save queue, or from their save file once written, never regenerating them.
+#[allow(clippy::needless_pass_by_value)]
+#[allow(clippy::too_many_arguments)]
+fn reload_all_chunks(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    key_bindings: Res<KeyBindings>,
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    mut chunks: ResMut<Chunks>,
+    mut chunk_entity_index: ResMut<ChunkEntityIndex>,
+    mut chunk_saver: ResMut<ChunkSaver>,
+    mesh_cache: Res<MeshCache>,
+    chunk_entities: Query<Entity, With<Chunk>>,
+    mut scanners: Query<&mut Scanner>,
+    mut commands: Commands,
+) {
+    if !keys.is_some_and(|keys| key_bindings.just_pressed(&keys, Action::ReloadAllChunks)) {
+        return;
+    }
+
+    // despawning a chunk entity despawns its layer children, which frees their GPU buffers.
+    for entity_id in &chunk_entities {
+        commands.entity(entity_id).despawn();
+    }
+    chunk_entity_index.entities.clear();
+    for (_, chunk_data) in chunks.0.drain() {
+        chunk_saver.unload(chunk_data);
+    }
+
+    // dropping the tasks cancels them.
+    let chunkloader = chunkloader.as_mut();
+    *chunkloader = AsyncChunkloader {
+        chunk_modifications: std::mem::take(&mut chunkloader.chunk_modifications),
+        stale_meshes_discarded: chunkloader.stale_meshes_discarded,
+        ..default()
+    };
+    mesh_cache.clear();
+
+    for mut scanner in &mut scanners {
+        scanner.rescan();
+    }
+}
+
+/// Queues the chunks that have been out of range for the whole [`ChunkUnloadGrace`].
+#[allow(clippy::needless_pass_by_value)]
+fn release_pending_unloads(
+    time: Res<Time>,
+    grace: Res<ChunkUnloadGrace>,
+    mut chunkloader: ResMut<AsyncChunkloader>,
+) {
+    let now = time.elapsed();
+    let AsyncChunkloader {
+        unload_chunk_queue,
+        unload_mesh_queue,
+        pending_chunk_unloads,
+        pending_mesh_unloads,
+        ..
+    } = chunkloader.as_mut();
+    for (pending, queue) in [
+        (pending_chunk_unloads, unload_chunk_queue),
+        (pending_mesh_unloads, unload_mesh_queue),
+    ] {
+        pending.retain(|&chunk_position, left_at| {
+            let expired = now.saturating_sub(*left_at) >= grace.0;
+            if expired {
+                queue.push(chunk_position);
+            }
+            !expired
+        });
     }
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn unload_meshes(
     mut chunkloader: ResMut<AsyncChunkloader>,
+    chunk_entity_index: Res<ChunkEntityIndex>,
+    mesh_cache: Res<MeshCache>,
+    animation: Res<ChunkUnloadAnimation>,
+    chunk_transforms: Query<&Transform, With<Chunk>>,
+    mut diagnostics: Diagnostics,
     mut commands: Commands,
-    chunk_canididates: Query<(Entity, &Chunk)>,
 ) {
     let to_unload: HashSet<ChunkPosition> = chunkloader.get_chunks_to_unmesh().collect();
+    // entries only the unmeshed chunks used are dropped, their entities keep drawing them while they sink.
+    let prune_mesh_cache = !to_unload.is_empty();
 
-    // todo: refactor to use bevy indexes when the update drops.
-    for (entity_id, chunk) in chunk_canididates.iter() {
-        if to_unload.contains(&chunk.position) {
-            if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
-                entity_commands.try_remove::<RenderableChunk>();
+    let mut stale_meshes = 0;
+    for chunk_position in to_unload {
+        // dropping the task cancels it. otherwise it would insert a mesh outside of the mesh range.
+        if chunkloader.mesh_tasks.remove(&chunk_position) {
+            stale_meshes += 1;
+        }
+        chunkloader.mesh_lods.remove(&chunk_position);
+
+        let Some(entity_id) = chunk_entity_index.get(chunk_position) else {
+            continue;
+        };
+        let Ok(mut entity_commands) = commands.get_entity(entity_id) else {
+            continue;
+        };
+        match chunk_transforms.get(entity_id) {
+            Ok(transform) if animation.enabled => {
+                let bottom = FloatingPosition::from(chunk_position).0 - Vec3::Y * animation.depth;
+                entity_commands.insert((
+                    SinkingChunk,
+                    SmoothTransformTo::new(
+                        FloatingPosition(bottom - transform.translation),
+                        animation.blocks_per_second,
+                    ),
+                ));
+            }
+            _ => insert_chunk_layers(&mut entity_commands, ChunkLayers::new()),
+        }
+    }
+    if prune_mesh_cache {
+        mesh_cache.prune(|chunk_position| chunkloader.mesh_lods.contains_key(&chunk_position));
+    }
+    chunkloader.discard_stale_meshes(stale_meshes, &mut diagnostics);
+}
+
+/// Drops the meshes of chunks which reached the bottom, and puts them back where they rest for when they are meshed again.
+fn finish_sinking_chunks(
+    mut sunk_chunks: Query<(Entity, &Chunk, &mut Transform), (With<SinkingChunk>, Without<SmoothTransformTo>)>,
+    mut commands: Commands,
+) {
+    for (entity_id, chunk, mut transform) in &mut sunk_chunks {
+        transform.translation = FloatingPosition::from(chunk.position).0;
+        let mut entity_commands = commands.entity(entity_id);
+        entity_commands.remove::<SinkingChunk>();
+        insert_chunk_layers(&mut entity_commands, ChunkLayers::new());
+    }
+}
+
+#[test]
+fn zero_budget_spawns_no_tasks() {
+    use crate::{
+        chunky::chunk::init_test_block_registry, mod_manager::prototypes::Prototypes,
+        player::render_distance::ScannerPlugin,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test blocks include air.");
+    let meshable = ChunkPosition::new(0, 0, 0);
+    let mut chunks = Chunks::default();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let neighbour = ChunkPosition::new(x, y, z);
+                chunks.0.insert(neighbour, Arc::new(ChunkData::filled(neighbour, air)));
+            }
+        }
+    }
+    let mesh_refs = ChunkRefs::try_new(&chunks, meshable).expect("Every neighbour is loaded.");
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AsyncChunkloaderPlugin, ScannerPlugin))
+        .insert_resource(ChunkLoaderBudget {
+            max_worldgen_tasks: 0,
+            max_filled_chunks: 0,
+            max_mesh_tasks: 0,
+            max_save_tasks: 0,
+            // the scanners keep queueing chunks, only the tasks are held back
+            ..default()
+        })
+        .insert_resource(block_prototypes.clone())
+        .insert_resource(chunks)
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.world_mut().spawn((Scanner::new(4), Transform::default()));
+    let unloaded = ChunkPosition::new(-3, -1, -1);
+    let mut chunkloader = app.world_mut().resource_mut::<AsyncChunkloader>();
+    chunkloader.load_chunk_queue.push(unloaded);
+    chunkloader.load_mesh_queue.push(mesh_refs);
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let chunkloader = app.world().resource::<AsyncChunkloader>();
+    assert!(chunkloader.worldgen_tasks.is_empty());
+    assert!(chunkloader.mesh_tasks.is_empty());
+    // the queued work waits for a budget instead of being dropped
+    assert!(chunkloader.load_chunk_queue.contains(&unloaded));
+    assert!(chunkloader
+        .load_mesh_queue
+        .iter()
+        .any(|queued| queued.center_chunk_position == meshable));
+    assert!(!app.world().resource::<Chunks>().0.contains_key(&unloaded));
+}
+
+#[test]
+fn corner_modification_remeshes_every_chunk_sampling_it() {
+    use crate::mod_manager::prototypes::Prototypes;
+
+    let stone = crate::mod_manager::prototypes::test_block_prototypes()
+        .get("stone")
+        .expect("Test blocks include stone.");
+
+    // Every chunk which could be remeshed needs all 26 neighbours loaded.
+    let mut chunks = Chunks::default();
+    for x in -2..=1 {
+        for y in -2..=1 {
+            for z in -2..=1 {
+                let chunk_position = ChunkPosition::new(x, y, z);
+                chunks
+                    .0
+                    .insert(chunk_position, Arc::new(ChunkData::filled(chunk_position, stone)));
+            }
+        }
+    }
+    let mut chunkloader = AsyncChunkloader::default();
+    chunkloader.chunk_modifications.insert(
+        ChunkPosition::new(0, 0, 0),
+        vec![(RelativePosition::new(0, 0, 0), stone)],
+    );
+
+    let mut app = App::new();
+    app.insert_resource(chunks)
+        .insert_resource(chunkloader)
+        .init_resource::<ChunkSaver>()
+        .add_systems(Update, apply_chunk_modifications);
+    app.update();
+
+    let queued: std::collections::HashSet<ChunkPosition> = app
+        .world()
+        .resource::<AsyncChunkloader>()
+        .load_mesh_queue
+        .iter()
+        .map(|chunk_refs| chunk_refs.center_chunk_position)
+        .collect();
+    // the face neighbours cull against it, the edge and corner neighbours sample it for ambient occlusion
+    let expected: std::collections::HashSet<ChunkPosition> = (-1..=0)
+        .flat_map(|x| (-1..=0).flat_map(move |y| (-1..=0).map(move |z| ChunkPosition::new(x, y, z))))
+        .collect();
+    assert_eq!(queued, expected);
+}
+
+#[test]
+fn chunk_entity_index_tracks_spawns_and_unloads() {
+    use crate::mod_manager::prototypes::Prototypes;
+
+    let stone = crate::mod_manager::prototypes::test_block_prototypes()
+        .get("stone")
+        .expect("Test blocks include stone.");
+    let chunk_positions: Vec<ChunkPosition> = (0..1000)
+        .map(|i| ChunkPosition::new(i % 10, i / 100, (i / 10) % 10))
+        .collect();
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<Chunks>()
+        .init_resource::<ChunkEntityIndex>()
+        .init_resource::<AsyncChunkloader>()
+        .init_resource::<ChunkSaver>()
+        .add_event::<ChunkDataReady>()
+        .add_systems(Update, unload_chunks);
+    app.update();
+
+    let world = app.world_mut();
+    world.resource_scope(|world, mut chunks: Mut<Chunks>| {
+        world.resource_scope(|world, mut chunk_entity_index: Mut<ChunkEntityIndex>| {
+            let mut commands = world.commands();
+            // Spawning the same position twice replaces the old entity.
+            for &chunk_position in chunk_positions.iter().chain(&chunk_positions[..10]) {
+                spawn_chunk_as_bevy_entity(
+                    ChunkData::filled(chunk_position, stone),
+                    &mut chunks,
+                    &mut chunk_entity_index,
+                    &mut commands,
+                );
+            }
+        });
+    });
+    world.flush();
+
+    let chunk_entity_index = world.resource::<ChunkEntityIndex>();
+    // one lookup per spawn, however many chunks are loaded already
+    let spawns = chunk_positions.len() + 10;
+    assert_eq!(chunk_entity_index.lookups(), spawns);
+    assert_eq!(chunk_entity_index.entities.len(), chunk_positions.len());
+    for &chunk_position in &chunk_positions {
+        let entity_id = chunk_entity_index.get(chunk_position).expect("Every spawned chunk is indexed.");
+        assert_eq!(world.get::<Chunk>(entity_id).map(|chunk| chunk.position), Some(chunk_position));
+    }
+    let entities: HashSet<Entity> = chunk_entity_index.entities.values().copied().collect();
+    assert_eq!(entities.len(), chunk_positions.len(), "No two positions share an entity.");
+    assert_eq!(world.query::<&Chunk>().iter(world).len(), chunk_positions.len());
+
+    let lookups_before_unload = world.resource::<ChunkEntityIndex>().lookups();
+    world
+        .resource_mut::<AsyncChunkloader>()
+        .unload_chunk_queue
+        .extend(&chunk_positions);
+    app.update();
+
+    let world = app.world_mut();
+    // and one per unload
+    let unload_lookups = world.resource::<ChunkEntityIndex>().lookups() - lookups_before_unload;
+    assert_eq!(unload_lookups, chunk_positions.len());
+    assert!(world.resource::<ChunkEntityIndex>().entities.is_empty());
+    assert!(world.resource::<Chunks>().0.is_empty());
+    assert_eq!(world.query::<&Chunk>().iter(world).len(), 0);
+}
+
+#[test]
+fn stale_meshes_are_discarded() {
+    let meshed = ChunkPosition::new(0, 0, 0);
+    let unloaded = ChunkPosition::new(1, 0, 0);
+    let out_of_mesh_range = ChunkPosition::new(2, 0, 0);
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<ChunkEntityIndex>()
+        .init_resource::<AsyncChunkloader>()
+        .init_resource::<ChunkLoaderBudget>()
+        .init_resource::<MeshCache>()
+        .init_resource::<ChunkUnloadAnimation>()
+        .register_diagnostic(Diagnostic::new(STALE_MESHES_DISCARDED))
+        .add_event::<ChunkMeshed>()
+        .add_systems(Update, (unload_meshes, join_mesh_threads).chain());
+
+    let world = app.world_mut();
+    for chunk_position in [meshed, out_of_mesh_range] {
+        let entity_id = world.spawn(Chunk { position: chunk_position }).id();
+        world.resource_mut::<ChunkEntityIndex>().insert(chunk_position, entity_id);
+    }
+    let mut chunkloader = world.resource_mut::<AsyncChunkloader>();
+    for chunk_position in [meshed, unloaded, out_of_mesh_range] {
+        chunkloader.mesh_tasks.spawn(chunk_position, async move {
+            let renderable_chunk = RenderableChunk::new(vec![], chunk_position, ChunkLayer::Opaque);
+            (
+                ChunkLayers::from([(ChunkLayer::Opaque, renderable_chunk)]),
+                ChunkMeshStats::default(),
+            )
+        });
+    }
+    chunkloader.unload_mesh_queue.push(out_of_mesh_range);
+
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<AsyncChunkloader>().mesh_tasks.is_empty() {
+            break;
+        }
+    }
+
+    let world = app.world_mut();
+    assert_eq!(world.resource::<AsyncChunkloader>().stale_meshes_discarded, 2);
+    let stale_meshes = world
+        .resource::<bevy::diagnostic::DiagnosticsStore>()
+        .get(&STALE_MESHES_DISCARDED)
+        .and_then(Diagnostic::value);
+    assert_eq!(stale_meshes, Some(2.));
+    let meshed_chunks: Vec<ChunkPosition> = world
+        .query_filtered::<&Chunk, With<RenderableChunk>>()
+        .iter(world)
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(meshed_chunks, [meshed]);
+}
+
+#[test]
+fn unmeshed_chunks_sink_before_losing_their_meshes() {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use crate::smooth_transform::smooth_transform;
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+        .init_resource::<ChunkEntityIndex>()
+        .init_resource::<AsyncChunkloader>()
+        .init_resource::<ChunkLoaderBudget>()
+        .init_resource::<MeshCache>()
+        .init_resource::<ChunkUnloadAnimation>()
+        .register_diagnostic(Diagnostic::new(STALE_MESHES_DISCARDED))
+        .add_event::<ChunkMeshed>()
+        .add_systems(
+            Update,
+            (unload_meshes, finish_sinking_chunks, join_mesh_threads, smooth_transform).chain(),
+        );
+    // centered on chunk (-1, -1, -1), keeping the chunks next to the origin meshed
+    app.world_mut().spawn((Scanner::new(4), GlobalTransform::default()));
+
+    let spawn_meshed_chunk = |app: &mut App, chunk_position: ChunkPosition| {
+        let world = app.world_mut();
+        let renderable_chunk = RenderableChunk::new(vec![], chunk_position, ChunkLayer::Opaque);
+        let rest = Transform::from_translation(FloatingPosition::from(chunk_position).0);
+        let entity_id = world.spawn((Chunk { position: chunk_position }, rest, renderable_chunk)).id();
+        world.resource_mut::<ChunkEntityIndex>().insert(chunk_position, entity_id);
+        world.resource_mut::<AsyncChunkloader>().unload_mesh_queue.push(chunk_position);
+        entity_id
+    };
+    let spawn_mesh_task = |app: &mut App, chunk_position: ChunkPosition| {
+        app.world_mut().resource_mut::<AsyncChunkloader>().mesh_tasks.spawn(chunk_position, async move {
+            let renderable_chunk = RenderableChunk::new(vec![], chunk_position, ChunkLayer::Opaque);
+            (
+                ChunkLayers::from([(ChunkLayer::Opaque, renderable_chunk)]),
+                ChunkMeshStats::default(),
+            )
+        });
+    };
+    let settle = |app: &mut App, entity_id: Entity| {
+        for _ in 0..100 {
+            app.update();
+            let entity = app.world().entity(entity_id);
+            let meshing = !app.world().resource::<AsyncChunkloader>().mesh_tasks.is_empty();
+            if !meshing && !entity.contains::<SinkingChunk>() && !entity.contains::<SmoothTransformTo>() {
+                break;
             }
         }
+    };
+
+    let sunk = ChunkPosition::new(0, 0, 0);
+    let entity_id = spawn_meshed_chunk(&mut app, sunk);
+    app.update();
+    let entity = app.world().entity(entity_id);
+    assert!(entity.contains::<SinkingChunk>() && entity.contains::<RenderableChunk>());
+
+    settle(&mut app, entity_id);
+    let entity = app.world().entity(entity_id);
+    assert!(!entity.contains::<SinkingChunk>() && !entity.contains::<RenderableChunk>());
+    let translation = entity.get::<Transform>().expect("Chunks have a transform.").translation;
+    assert_eq!(translation, FloatingPosition::from(sunk).0);
+
+    // meshed again halfway down, it floats back up with its new mesh
+    let returned = ChunkPosition::new(1, 0, 0);
+    let entity_id = spawn_meshed_chunk(&mut app, returned);
+    for _ in 0..3 {
+        app.update();
     }
+    spawn_mesh_task(&mut app, returned);
+    settle(&mut app, entity_id);
+
+    let entity = app.world().entity(entity_id);
+    assert!(!entity.contains::<SinkingChunk>() && entity.contains::<RenderableChunk>());
+    let translation = entity.get::<Transform>().expect("Chunks have a transform.").translation;
+    assert!(translation.distance(FloatingPosition::from(returned).0) < 1e-3, "{translation}");
+
+    // meshed again while still out of range, the mesh is dropped and it keeps sinking
+    let out_of_range = ChunkPosition::new(8, 0, 0);
+    let entity_id = spawn_meshed_chunk(&mut app, out_of_range);
+    for _ in 0..3 {
+        app.update();
+    }
+    spawn_mesh_task(&mut app, out_of_range);
+    settle(&mut app, entity_id);
+
+    let entity = app.world().entity(entity_id);
+    assert!(!entity.contains::<SinkingChunk>() && !entity.contains::<RenderableChunk>());
+    let translation = entity.get::<Transform>().expect("Chunks have a transform.").translation;
+    assert_eq!(translation, FloatingPosition::from(out_of_range).0);
+}
+
+#[test]
+fn loading_a_chunk_sends_data_ready_then_meshed() {
+    use crate::chunky::chunk::{TestHalfFilled, init_test_block_registry};
+
+    #[derive(Resource, Default)]
+    struct Received(Vec<(&'static str, ChunkPosition)>);
+
+    let center = ChunkPosition::new(0, 0, 0);
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AsyncChunkloaderPlugin))
+        .insert_resource(init_test_block_registry().clone())
+        .insert_resource(Terrain(Arc::new(TestHalfFilled)))
+        .init_resource::<Received>()
+        .add_systems(
+            Last,
+            |mut data_ready: EventReader<ChunkDataReady>, mut meshed: EventReader<ChunkMeshed>, mut received: ResMut<Received>| {
+                received.0.extend(data_ready.read().map(|event| ("data ready", event.position)));
+                received.0.extend(meshed.read().map(|event| ("meshed", event.position)));
+            },
+        );
+    app.world_mut().spawn((Scanner::new(4), Transform::default()));
+    app.world_mut()
+        .resource_mut::<AsyncChunkloader>()
+        .load_chunk_queue
+        .extend((0..27).map(|i| center + ChunkPosition(crate::utils::index_to_ivec3_bounds(i, 3) - IVec3::ONE)));
+
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<Chunks>().0.len() == 27 {
+            break;
+        }
+    }
+    let chunk_refs = ChunkRefs::try_new(app.world().resource::<Chunks>(), center).expect("All neighbours loaded.");
+    app.world_mut().resource_mut::<AsyncChunkloader>().load_mesh_queue.push(chunk_refs);
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<Received>().0.contains(&("meshed", center)) {
+            break;
+        }
+    }
+
+    let received = &app.world().resource::<Received>().0;
+    let data_ready = received.iter().position(|&event| event == ("data ready", center));
+    let meshed = received.iter().position(|&event| event == ("meshed", center));
+    assert!(data_ready.is_some() && meshed.is_some(), "{received:?}");
+    assert!(data_ready < meshed);
+    assert_eq!(received.iter().filter(|(kind, _)| *kind == "data ready").count(), 27);
+}
+
+#[test]
+fn reloading_all_chunks_converges_to_the_same_loaded_set() {
+    use crate::{
+        chunky::chunk::{TestSky, init_test_block_registry, settle_chunk_loading},
+        player::render_distance::ScannerPlugin,
+    };
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AsyncChunkloaderPlugin, ScannerPlugin))
+        .insert_resource(init_test_block_registry().clone())
+        .insert_resource(Terrain(Arc::new(TestSky)))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.world_mut().spawn((Scanner::new(4), Transform::default()));
+
+    let settle = |app: &mut App| {
+        let loaded = settle_chunk_loading(app);
+        let world = app.world_mut();
+        let chunk_entities = world.query_filtered::<(), With<Chunk>>().iter(world).count();
+        assert_eq!(chunk_entities, loaded.len());
+        let chunk_entity_index = world.resource::<ChunkEntityIndex>();
+        assert_eq!(chunk_entity_index.entities.len(), loaded.len());
+        for &chunk_position in &loaded {
+            let entity_id = chunk_entity_index.get(chunk_position).expect("Every loaded chunk has an entity.");
+            assert_eq!(world.get::<Chunk>(entity_id).map(|chunk| chunk.position), Some(chunk_position));
+        }
+        loaded
+    };
+
+    let loaded = settle(&mut app);
+    assert!(!loaded.is_empty());
+
+    let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    keys.press(Action::ReloadAllChunks.default_keys()[0]);
+    app.update();
+    app.world_mut().resource_mut::<ButtonInput<KeyCode>>().clear();
+
+    let world = app.world_mut();
+    assert_eq!(world.query_filtered::<(), With<Chunk>>().iter(world).count(), 0);
+    assert!(world.resource::<Chunks>().0.is_empty());
+    assert!(world.resource::<ChunkEntityIndex>().entities.is_empty());
+    let chunkloader = world.resource::<AsyncChunkloader>();
+    assert!(chunkloader.worldgen_tasks.is_empty() && chunkloader.mesh_tasks.is_empty());
+
+    assert_eq!(settle(&mut app), loaded);
+}
+
+#[test]
+fn edits_survive_reloading_all_chunks() {
+    use crate::{
+        chunky::chunk::{TestSky, init_test_block_registry},
+        mod_manager::prototypes::Prototypes,
+        player::render_distance::ScannerPlugin,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let stone = block_prototypes.get("stone").expect("Test blocks include stone.");
+    let directory = std::env::temp_dir().join(format!("talc_reload_edits_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AsyncChunkloaderPlugin, ScannerPlugin))
+        .insert_resource(block_prototypes.clone())
+        .insert_resource(Terrain(Arc::new(TestSky)))
+        .insert_resource(ChunkSaver::new(&directory))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.world_mut().spawn((Scanner::new(4), Transform::default()));
+
+    // below the surface, so without its save it would be filled with air on the main thread
+    let edited = ChunkPosition::new(0, -1, 0);
+    let edited_block = RelativePosition::new(1, 2, 3);
+    let wait_until = |app: &mut App, done: &dyn Fn(&World) -> bool| {
+        for _ in 0..1000 {
+            app.update();
+            if done(app.world()) {
+                return;
+            }
+        }
+        panic!("Timed out.");
+    };
+    wait_until(&mut app, &|world| world.resource::<Chunks>().0.contains_key(&edited));
+    app.world_mut()
+        .resource_mut::<AsyncChunkloader>()
+        .chunk_modifications
+        .insert(edited, vec![(edited_block, stone)]);
+    app.update();
+
+    // paused, so the chunk only loads again once its save is written
+    app.world_mut().resource_mut::<ChunkLoadingPaused>().0 = true;
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(Action::ReloadAllChunks.default_keys()[0]);
+    app.update();
+    app.world_mut().resource_mut::<ButtonInput<KeyCode>>().clear();
+    wait_until(&mut app, &|world| {
+        let chunk_saver = world.resource::<ChunkSaver>();
+        chunk_saver.unsaved.is_empty() && chunk_saver.save_tasks.is_empty()
+    });
+    assert!(app.world().resource::<ChunkSaver>().saved_chunks.contains(&edited));
+
+    app.world_mut().resource_mut::<ChunkLoadingPaused>().0 = false;
+    wait_until(&mut app, &|world| world.resource::<Chunks>().0.contains_key(&edited));
+    let world = app.world();
+    let chunk_data = &world.resource::<Chunks>().0[&edited];
+    assert_eq!(chunk_data.get_block(edited_block.into()).name, stone.name);
+    // read back from the file, which it still matches
+    assert!(!world.resource::<ChunkSaver>().dirty_chunks.contains(&edited));
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn chunks_ahead_of_a_moving_scanner_load_first() {
+    let behind = ChunkPosition::new(-2, 0, 0);
+    let ahead = ChunkPosition::new(2, 0, 0);
+    let beside = ChunkPosition::new(0, 0, 2);
+    let budget = ChunkLoaderBudget {
+        max_worldgen_tasks: 3,
+        ..default()
+    };
+    let translation = Vec3::splat(CHUNK_SIZE_F32 / 2.);
+
+    let mut scanner = Scanner::new(4);
+    let mut chunkloader = AsyncChunkloader::default();
+    let mut load_order = |scanner: &Scanner| {
+        chunkloader.load_chunk_queue = vec![behind, beside, ahead];
+        chunkloader
+            .get_chunks_to_load(&budget, scanner.prefetch_origin(translation), |_| true)
+            .0
+    };
+
+    // standing still, equidistant chunks keep their queue order
+    assert_eq!(load_order(&scanner), [behind, beside, ahead]);
+
+    scanner.velocity = Vec3::new(10., 0., 0.);
+    assert_eq!(load_order(&scanner), [ahead, beside, behind]);
+
+    // the lead is capped, so a teleport does not skip the chunks around the scanner
+    scanner.velocity = Vec3::new(0., 0., -10_000.);
+    let lead = translation.z - scanner.prefetch_origin(translation).0.z;
+    assert!((lead - scanner.prefetch_lead).abs() < 1e-3);
+}
+
+#[test]
+fn crossing_a_lod_threshold_remeshes_the_chunk() {
+    use crate::{chunky::chunk::init_test_block_registry, mod_manager::prototypes::Prototypes};
+
+    let air = init_test_block_registry().get("air").expect("Test blocks include air.");
+    let chunk_position = ChunkPosition::new(10, 0, 0);
+    let mut chunks = Chunks::default();
+    for x in 9..=11 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let neighbour = ChunkPosition::new(x, y, z);
+                chunks.0.insert(neighbour, Arc::new(ChunkData::filled(neighbour, air)));
+            }
+        }
+    }
+
+    let mut app = App::new();
+    app.insert_resource(chunks)
+        .insert_resource(LodPolicy::tiered())
+        .init_resource::<AsyncChunkloader>()
+        .add_systems(Update, remesh_on_lod_change);
+    let at_chunk = |x: i32| {
+        let chunk_center = FloatingPosition::from(ChunkPosition::new(x, 0, 0)).0 + CHUNK_SIZE_F32 / 2.;
+        GlobalTransform::from_translation(chunk_center)
+    };
+    let scanner = app.world_mut().spawn((Scanner::new(4), at_chunk(0))).id();
+    app.world_mut()
+        .resource_mut::<AsyncChunkloader>()
+        .mesh_lods
+        .insert(chunk_position, Lod::L16);
+
+    let queued = |app: &mut App| {
+        app.update();
+        let mut chunkloader = app.world_mut().resource_mut::<AsyncChunkloader>();
+        chunkloader
+            .load_mesh_queue
+            .drain(..)
+            .map(|queued| queued.center_chunk_position)
+            .collect::<Vec<_>>()
+    };
+
+    // 10 chunks away it is meshed at the right lod already
+    assert!(queued(&mut app).is_empty());
+    // 9 chunks away is still within the same level
+    *app.world_mut().get_mut::<GlobalTransform>(scanner).expect("The scanner has a transform.") = at_chunk(1);
+    assert!(queued(&mut app).is_empty());
+    // 5 chunks away it needs full detail
+    *app.world_mut().get_mut::<GlobalTransform>(scanner).expect("The scanner has a transform.") = at_chunk(5);
+    assert_eq!(queued(&mut app), [chunk_position]);
+
+    // a new policy remeshes without the scanner moving
+    app.world_mut().resource_mut::<AsyncChunkloader>().mesh_lods.insert(chunk_position, Lod::L32);
+    app.world_mut().insert_resource(LodPolicy::default());
+    assert!(queued(&mut app).is_empty());
+    app.world_mut().insert_resource(LodPolicy::tiered());
+    app.world_mut().resource_mut::<AsyncChunkloader>().mesh_lods.insert(chunk_position, Lod::L16);
+    assert_eq!(queued(&mut app), [chunk_position]);
+}
+
+#[test]
+fn toggling_the_mesher_remeshes_only_meshed_chunks() {
+    use crate::{chunky::chunk::init_test_block_registry, mod_manager::prototypes::Prototypes};
+
+    let air = init_test_block_registry().get("air").expect("Test blocks include air.");
+    let meshed = ChunkPosition::new(0, 0, 0);
+    let mut chunks = Chunks::default();
+    // chunk (1, 0, 0) is loaded with all its neighbours too, but outside the mesh range
+    for x in -1..=2 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let neighbour = ChunkPosition::new(x, y, z);
+                chunks.0.insert(neighbour, Arc::new(ChunkData::filled(neighbour, air)));
+            }
+        }
+    }
+
+    let mut app = App::new();
+    app.insert_resource(chunks)
+        .init_resource::<AoEnabled>()
+        .init_resource::<GreedyMeshing>()
+        .init_resource::<AsyncChunkloader>()
+        .add_systems(Update, remesh_on_mesher_change);
+    app.world_mut().resource_mut::<AsyncChunkloader>().mesh_lods.insert(meshed, Lod::L32);
+    app.update();
+    assert!(app.world().resource::<AsyncChunkloader>().load_mesh_queue.is_empty());
+
+    app.world_mut().resource_mut::<AoEnabled>().0 ^= true;
+    app.update();
+    let queued: Vec<ChunkPosition> = app
+        .world()
+        .resource::<AsyncChunkloader>()
+        .load_mesh_queue
+        .iter()
+        .map(|queued| queued.center_chunk_position)
+        .collect();
+    assert_eq!(queued, [meshed]);
+}
+
+#[test]
+fn paused_chunk_loading_resumes_from_the_current_position() {
+    use crate::{
+        chunky::chunk::{TestSky, init_test_block_registry, settle_chunk_loading},
+        player::render_distance::ScannerPlugin,
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        bevy::transform::TransformPlugin,
+        AsyncChunkloaderPlugin,
+        ScannerPlugin,
+    ))
+    .insert_resource(init_test_block_registry().clone())
+    .insert_resource(Terrain(Arc::new(TestSky)))
+    .init_resource::<ButtonInput<KeyCode>>();
+    let scanner = app.world_mut().spawn((Scanner::new(4), Transform::default())).id();
+
+    let toggle = |app: &mut App| {
+        let key = Action::ToggleChunkLoading.default_keys()[0];
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(key);
+        keys.clear();
+        keys.press(key);
+        app.update();
+        app.world().resource::<ChunkLoadingPaused>().0
+    };
+
+    let home = settle_chunk_loading(&mut app);
+    assert!(!home.is_empty());
+
+    assert!(toggle(&mut app));
+    let far = Vec3::new(20. * CHUNK_SIZE_I32 as f32, 0., 0.);
+    app.world_mut()
+        .get_mut::<Transform>(scanner)
+        .expect("The scanner has a transform.")
+        .translation = far;
+    for _ in 0..10 {
+        app.update();
+    }
+    let world = app.world();
+    assert_eq!(world.resource::<Chunks>().0.keys().copied().collect::<HashSet<_>>(), home);
+    assert!(world.resource::<AsyncChunkloader>().worldgen_tasks.is_empty());
+
+    assert!(!toggle(&mut app));
+    let moved = settle_chunk_loading(&mut app);
+    assert!(moved.contains(&scanner_chunk_position(far)));
+    assert!(moved.is_disjoint(&home), "The old area is unloaded.");
+}
+
+#[test]
+fn chunks_back_in_range_within_the_grace_period_stay_loaded() {
+    use crate::{
+        chunky::chunk::{TestSky, init_test_block_registry, settle_chunk_loading},
+        player::render_distance::ScannerPlugin,
+    };
+
+    let mut app = App::new();
+    app.insert_resource(ChunkUnloadGrace(Duration::from_secs(3600)))
+        .add_plugins((
+            MinimalPlugins,
+            bevy::transform::TransformPlugin,
+            AsyncChunkloaderPlugin,
+            ScannerPlugin,
+        ))
+        .insert_resource(init_test_block_registry().clone())
+        .insert_resource(Terrain(Arc::new(TestSky)));
+    let scanner = app.world_mut().spawn((Scanner::new(4), Transform::default())).id();
+
+    settle_chunk_loading(&mut app);
+    let home = app.world().resource::<ChunkEntityIndex>().entities.clone();
+    assert!(!home.is_empty());
+
+    let move_to = |app: &mut App, translation: Vec3| {
+        app.world_mut()
+            .get_mut::<Transform>(scanner)
+            .expect("The scanner has a transform.")
+            .translation = translation;
+        for _ in 0..10 {
+            app.update();
+            let index = app.world().resource::<ChunkEntityIndex>();
+            for (chunk_position, entity) in &home {
+                assert_eq!(index.get(*chunk_position), Some(*entity), "{chunk_position} was unloaded.");
+            }
+        }
+    };
+
+    move_to(&mut app, Vec3::new(5.5 * CHUNK_SIZE_F32, 0., 0.));
+    let pending = &app.world().resource::<AsyncChunkloader>().pending_chunk_unloads;
+    assert!(!pending.is_empty());
+    assert!(pending.keys().all(|chunk_position| home.contains_key(chunk_position)));
+
+    move_to(&mut app, Vec3::ZERO);
+    let chunkloader = app.world().resource::<AsyncChunkloader>();
+    assert!(chunkloader.pending_chunk_unloads.is_empty());
+    assert!(chunkloader.pending_mesh_unloads.is_empty());
+    assert!(chunkloader.unload_chunk_queue.is_empty());
+}
+
+#[test]
+fn warm_start_loads_the_spawn_area_before_the_first_frame() {
+    use crate::chunky::chunk::{TestHalfFilled, init_test_block_registry};
+
+    let mut app = App::new();
+    app.insert_resource(WarmStart { radius: 1 })
+        .add_plugins((MinimalPlugins, bevy::transform::TransformPlugin, AsyncChunkloaderPlugin))
+        .insert_resource(init_test_block_registry().clone())
+        .insert_resource(Terrain(Arc::new(TestHalfFilled)))
+        // only the warm start loads anything
+        .insert_resource(ChunkLoadingPaused(true))
+        .init_resource::<ButtonInput<KeyCode>>();
+    let center = ChunkPosition::new(2, 0, -1);
+    app.world_mut().spawn((
+        Scanner::new(4),
+        Transform::from_translation(FloatingPosition::from(center).0 + CHUNK_SIZE_F32 / 2.),
+    ));
+    app.update();
+
+    let world = app.world();
+    let chunks = world.resource::<Chunks>();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                assert!(chunks.0.contains_key(&(center + ChunkPosition::new(x, y, z))));
+            }
+        }
+    }
+    let scanner = Scanner::new(4);
+    let worldgen_area: HashSet<ChunkPosition> =
+        scanner.worldgen_sampling_offsets.iter().map(|offset| center + *offset).collect();
+    assert!(chunks.0.keys().all(|chunk_position| worldgen_area.contains(chunk_position)));
+
+    let entity = world
+        .resource::<ChunkEntityIndex>()
+        .get(center)
+        .expect("The center chunk has an entity.");
+    assert!(world.get::<RenderableChunk>(entity).is_some(), "The center chunk is meshed.");
+    assert!(world.get::<SmoothTransformTo>(entity).is_none());
+    assert_eq!(
+        world.get::<Transform>(entity).map(|transform| transform.translation),
+        Some(FloatingPosition::from(center).0)
+    );
 }
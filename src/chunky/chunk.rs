@@ -1,14 +1,21 @@
-use std::sync::OnceLock;
+use std::{ops::RangeInclusive, sync::OnceLock};
 
 use bevy::prelude::*;
-use bracket_noise::prelude::*;
-
 use crate::{
-    mod_manager::prototypes::{BlockPrototype, BlockPrototypes, Prototypes},
-    position::{ChunkPosition, Position},
+    chunky::{
+        noise::{Noise, Octaves},
+        terrain::{SURFACE_AMPLITUDE, SURFACE_Y},
+    },
+    mod_manager::prototypes::{
+        BiomePrototype, BiomePrototypes, BlockPrototype, BlockPrototypes, BlockShape,
+        MAX_BLOCK_PROTOTYPES, Prototypes,
+    },
+    position::{ChunkPosition, Position, RelativePosition},
 };
 
 /// 32^3 voxels per chunk is a great compromise as it allows each vertex to be only 32 bits when sent to wgsl.
+///
+/// Other sizes need wider `PackedQuad` fields and shader changes, see `chunk_material::QUAD_AXIS_BITS`.
 pub const CHUNK_SIZE: usize = 32;
 pub const CHUNK_SIZE_F32: f32 = CHUNK_SIZE as f32;
 pub const CHUNK_SIZE_U16: u16 = CHUNK_SIZE as u16;
@@ -31,10 +38,12 @@ pub struct Chunk {
     pub position: ChunkPosition,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ChunkData {
     pub position: ChunkPosition,
     voxels: Voxels,
+    /// See [`ChunkData::content_hash`]. Cleared by `set_block`.
+    content_hash: OnceLock<u64>,
 }
 
 #[derive(Clone, Debug)]
@@ -55,6 +64,7 @@ impl ChunkData {
     }
 
     pub fn set_block(&mut self, index: VoxelIndex, block_type: &'static BlockPrototype) {
+        self.content_hash.take();
         match &mut self.voxels {
             Voxels::Homogeneous(old_block_type) => {
                 let mut new_voxels: Box<[ThinBlockPointer]> =
@@ -67,18 +77,169 @@ impl ChunkData {
 
                 let homogeneous = voxels.iter().all(|&block| block == block_type.id);
                 if homogeneous {
-                    todo!("woo hoo");
-                    //self.voxels = Voxels::Homogeneous(block_type);
+                    self.voxels = Voxels::Homogeneous(block_type.id);
                 }
             }
         }
     }
 
+    /// Sets every voxel to `block`, without allocating the per voxel storage.
+    pub fn fill(&mut self, block: &'static BlockPrototype) {
+        self.content_hash.take();
+        self.voxels = Voxels::Homogeneous(block.id);
+    }
+
+    /// Sets every voxel from `min` to `max` inclusive to `block`. The region is clamped to the chunk.
+    /// The chunk stays homogeneous if the region covers all of it or writes the block it is already filled with,
+    /// and becomes homogeneous again if the region overwrites every other block.
+    pub fn set_region(&mut self, min: RelativePosition, max: RelativePosition, block: &'static BlockPrototype) {
+        let min = min.0.max(IVec3::ZERO);
+        let max = max.0.min(IVec3::splat(CHUNK_SIZE_I32 - 1));
+        if min.cmpgt(max).any() {
+            return;
+        }
+        if min == IVec3::ZERO && max == IVec3::splat(CHUNK_SIZE_I32 - 1) {
+            self.fill(block);
+            return;
+        }
+
+        let voxels = match &mut self.voxels {
+            Voxels::Homogeneous(old_block) if *old_block == block.id => return,
+            Voxels::Homogeneous(old_block) => {
+                self.voxels = Voxels::Heterogeneous(vec![*old_block; CHUNK_SIZE3].into_boxed_slice());
+                let Voxels::Heterogeneous(voxels) = &mut self.voxels else {
+                    unreachable!("The voxels were just made heterogeneous.");
+                };
+                voxels
+            }
+            Voxels::Heterogeneous(voxels) => voxels,
+        };
+        self.content_hash.take();
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                let row = VoxelIndex::new(min.x as usize, y as usize, z as usize).i();
+                voxels[row..=row + (max.x - min.x) as usize].fill(block.id);
+            }
+        }
+
+        if voxels.iter().all(|&voxel| voxel == block.id) {
+            self.voxels = Voxels::Homogeneous(block.id);
+        }
+    }
+
+    /// Bytes allocated for the voxels, on top of the `ChunkData` itself. Homogeneous chunks allocate nothing.
+    #[must_use]
+    pub const fn heap_bytes(&self) -> usize {
+        match &self.voxels {
+            Voxels::Homogeneous(_) => 0,
+            Voxels::Heterogeneous(voxels) => voxels.len() * size_of::<ThinBlockPointer>(),
+        }
+    }
+
+    /// Sets every `from` voxel from `min` to `max` inclusive to `to`, leaving other blocks as they are.
+    /// The region is clamped to the chunk.
+    pub fn replace_region(
+        &mut self,
+        min: RelativePosition,
+        max: RelativePosition,
+        from: &'static BlockPrototype,
+        to: &'static BlockPrototype,
+    ) {
+        match self.voxels {
+            Voxels::Homogeneous(block) if block == from.id => {
+                self.set_region(min, max, to);
+                return;
+            }
+            Voxels::Homogeneous(_) => return,
+            Voxels::Heterogeneous(_) => {}
+        }
+        let min = min.0.max(IVec3::ZERO);
+        let max = max.0.min(IVec3::splat(CHUNK_SIZE_I32 - 1));
+        if min.cmpgt(max).any() {
+            return;
+        }
+
+        self.content_hash.take();
+        let Voxels::Heterogeneous(voxels) = &mut self.voxels else {
+            unreachable!("Homogeneous chunks returned above.");
+        };
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                let row = VoxelIndex::new(min.x as usize, y as usize, z as usize).i();
+                for voxel in &mut voxels[row..=row + (max.x - min.x) as usize] {
+                    if *voxel == from.id {
+                        *voxel = to.id;
+                    }
+                }
+            }
+        }
+
+        if voxels.iter().all(|&voxel| voxel == to.id) {
+            self.voxels = Voxels::Homogeneous(to.id);
+        }
+    }
+
     #[inline]
     #[must_use]
     pub const fn is_homogenous(&self) -> bool {
         matches!(self.voxels, Voxels::Homogeneous(_))
     }
+
+    /// The block filling the whole chunk, or `None` if it holds more than one block type.
+    #[inline]
+    #[must_use]
+    pub fn homogeneous_block(&self) -> Option<&'static BlockPrototype> {
+        match self.voxels {
+            Voxels::Homogeneous(block_pointer) => access_block_registry(block_pointer),
+            Voxels::Heterogeneous(_) => None,
+        }
+    }
+
+    /// True if the chunk is a single block the mesher emits no faces for, like air.
+    /// Its mesh is empty whatever its neighbours are.
+    #[must_use]
+    pub fn meshes_to_nothing(&self) -> bool {
+        self.homogeneous_block()
+            .is_some_and(|block| block.is_transparent && !block.is_meshable && block.shape == BlockShape::Full)
+    }
+
+    /// A stable hash of the voxel contents. The chunk position is not included.
+    /// Chunks with the same blocks hash equally, whether they are stored homogeneous or not.
+    /// Uses FNV-1a, so the value does not change between runs or compiler versions.
+    /// Computed once and kept until the chunk is edited.
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        *self.content_hash.get_or_init(|| match &self.voxels {
+            Voxels::Homogeneous(block) => {
+                (0..CHUNK_SIZE3).fold(FNV_OFFSET_BASIS, |hash, _| hash_block(hash, *block))
+            }
+            Voxels::Heterogeneous(voxels) => voxels
+                .iter()
+                .fold(FNV_OFFSET_BASIS, |hash, &block| hash_block(hash, block)),
+        })
+    }
+
+    /// The id of the block at `index`, without going through the block registry.
+    #[inline]
+    #[must_use]
+    pub(crate) fn block_id(&self, index: VoxelIndex) -> ThinBlockPointer {
+        match &self.voxels {
+            Voxels::Homogeneous(block_pointer) => *block_pointer,
+            Voxels::Heterogeneous(voxels) => voxels[index.i()],
+        }
+    }
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Feeds a block id into an FNV-1a hash.
+pub(crate) fn hash_block(hash: u64, block: ThinBlockPointer) -> u64 {
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    block
+        .to_le_bytes()
+        .iter()
+        .fold(hash, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
 }
 
 /// The index of a voxel within a chunk.
@@ -120,6 +281,12 @@ impl From<VoxelIndex> for Position {
     }
 }
 
+impl From<RelativePosition> for VoxelIndex {
+    fn from(value: RelativePosition) -> Self {
+        Position::from(value).into()
+    }
+}
+
 impl From<Position> for VoxelIndex {
     fn from(value: Position) -> Self {
         let x: usize = value
@@ -138,8 +305,131 @@ impl From<Position> for VoxelIndex {
     }
 }
 
-static BLOCK_REGISTRY: OnceLock<[Option<&'static BlockPrototype>; u8::MAX as usize]> =
+static BLOCK_REGISTRY: OnceLock<[Option<&'static BlockPrototype>; MAX_BLOCK_PROTOTYPES]> =
     OnceLock::new();
+
+/// The noise terrain voxels of the chunk starting at `world_position`, ordered like [`VoxelIndex`].
+/// Runs on the calling thread, chunks are already generated in parallel by their own tasks.
+fn generate_noise_voxels(
+    world_position: Position,
+    air: ThinBlockPointer,
+    solid_blocks: &SolidBlocks,
+    surface_octaves: Octaves,
+) -> Box<[ThinBlockPointer]> {
+    let noise = TerrainNoise::new(surface_octaves);
+    let mut voxels = Vec::with_capacity(CHUNK_SIZE3);
+    for z in 0..CHUNK_SIZE_I32 {
+        let wz = (z + world_position.z) as f32;
+        for y in 0..CHUNK_SIZE_I32 {
+            let wy = (y + world_position.y - SURFACE_Y) as f32;
+            for x in 0..CHUNK_SIZE_I32 {
+                let wx = (x + world_position.x) as f32;
+                let solid = noise.surface_height(wx, wy, wz) > wy;
+                voxels.push(if solid { solid_blocks.at(x, y + world_position.y, z) } else { air });
+            }
+        }
+    }
+    voxels.into_boxed_slice()
+}
+
+/// The solid blocks of the columns of one chunk.
+enum SolidBlocks {
+    /// Every solid block is the same, when there are no biomes.
+    Uniform(&'static BlockPrototype),
+    /// The biome of each column, indexed by `x + z * CHUNK_SIZE`.
+    Biomes(Box<[&'static BiomePrototype]>),
+}
+
+impl SolidBlocks {
+    fn new(block_prototypes: &BlockPrototypes, biomes: &BiomePrototypes, world_position: Position) -> Self {
+        if biomes.is_empty() {
+            let grass = block_prototypes.get("grass").expect("Without biomes the terrain is made of grass.");
+            return Self::Uniform(grass);
+        }
+        let noise = biome_noise();
+        let column_biomes = (0..CHUNK_SIZE2_I32)
+            .map(|i| {
+                let (x, z) = (i % CHUNK_SIZE_I32, i / CHUNK_SIZE_I32);
+                pick_biome(&noise, biomes, world_position.x + x, world_position.z + z)
+                    .expect("There is at least one biome.")
+            })
+            .collect();
+        Self::Biomes(column_biomes)
+    }
+
+    /// The solid block at chunk column (`x`, `z`) and world height `y`.
+    fn at(&self, x: i32, y: i32, z: i32) -> ThinBlockPointer {
+        match self {
+            Self::Uniform(block) => block.id,
+            Self::Biomes(column_biomes) => column_biomes[(x + z * CHUNK_SIZE_I32) as usize].block_at(y).id,
+        }
+    }
+}
+
+/// Low frequency noise splitting the world into biomes.
+fn biome_noise() -> Noise {
+    Noise::new(1, 0.0015)
+}
+
+fn pick_biome(noise: &Noise, biomes: &BiomePrototypes, x: i32, z: i32) -> Option<&'static BiomePrototype> {
+    // the noise is roughly within -1..1, split evenly between the biomes in name order.
+    let t = (noise.get(x as f32, z as f32) + 1.) / 2.;
+    let index = ((t * biomes.len() as f32) as usize).min(biomes.len().saturating_sub(1));
+    biomes.iter().nth(index).map(|(_, biome)| *biome)
+}
+
+/// The biome of the column at world (`x`, `z`), `None` without biomes.
+#[must_use]
+pub fn biome_at(biomes: &BiomePrototypes, x: i32, z: i32) -> Option<&'static BiomePrototype> {
+    pick_biome(&biome_noise(), biomes, x, z)
+}
+
+/// World y of the highest solid voxel [`ChunkData::generate_with_biomes`] places in the column at world (`x`, `z`),
+/// given the same `surface_octaves` and `extent`.
+/// Evaluates the same noise top-down, so it matches the generated voxels exactly.
+#[must_use]
+pub fn noise_surface_y(x: i32, z: i32, surface_octaves: Octaves, extent: &RangeInclusive<i32>) -> i32 {
+    let bottom = extent.start() * CHUNK_SIZE_I32;
+    let top = (extent.end() + 1) * CHUNK_SIZE_I32 - 1;
+    let noise = TerrainNoise::new(surface_octaves);
+    let (wx, wz) = (x as f32, z as f32);
+    (bottom..=top)
+        .rev()
+        .find(|&y| {
+            let wy = (y - SURFACE_Y) as f32;
+            noise.surface_height(wx, wy, wz) > wy
+        })
+        .unwrap_or(bottom - 1)
+}
+
+/// The noise layers shaping the noise terrain.
+struct TerrainNoise {
+    overhang: Noise,
+    surface: Noise,
+    surface_octaves: Octaves,
+}
+
+/// The default seed of `bracket_noise`, which the noise terrain has always been generated with.
+const TERRAIN_SEED: u64 = 1337;
+
+impl TerrainNoise {
+    fn new(surface_octaves: Octaves) -> Self {
+        Self {
+            overhang: Noise::new(TERRAIN_SEED, 0.0254),
+            surface: Noise::new(TERRAIN_SEED, 0.002591),
+            surface_octaves,
+        }
+    }
+
+    /// The surface height relative to `SURFACE_Y`, as seen from the voxel at `wy` above it.
+    /// The overhang shifts the surface sideways depending on `wy`,
+    /// so this is not a heightmap and can't be computed once per column.
+    fn surface_height(&self, wx: f32, wy: f32, wz: f32) -> f32 {
+        let overhang = self.overhang.get3d(wx, wy, wz) * 55.0;
+        self.surface.fractal(wx + overhang, wz / 3.0, self.surface_octaves) * SURFACE_AMPLITUDE as f32
+    }
+}
+
 type ThinBlockPointer = u16; // Classic rust reimplementing pointers. But &'static BlockPrototype is too fat :(
 
 #[inline]
@@ -165,88 +455,180 @@ pub fn set_block_registry(block_prototypes: &BlockPrototypes) {
         "Block registry has already been constructed."
     );
 
-    BLOCK_REGISTRY.get_or_init(|| {
-        let mut registry = [None; u8::MAX as usize];
-        for (_, &block) in block_prototypes.iter() {
-            registry[block.id as usize] = Some(block);
+    BLOCK_REGISTRY.get_or_init(|| build_block_registry(block_prototypes));
+}
+
+fn build_block_registry(
+    block_prototypes: &BlockPrototypes,
+) -> [Option<&'static BlockPrototype>; MAX_BLOCK_PROTOTYPES] {
+    let mut registry = [None; MAX_BLOCK_PROTOTYPES];
+    for (_, &block) in block_prototypes.iter() {
+        registry[block.id as usize] = Some(block);
+    }
+    registry
+}
+
+/// Builds the block registry from `test_fixture_block_prototypes`, unless another test already did.
+/// Every block a test puts in a chunk has to be in it, so this holds the fixtures as well.
+#[cfg(test)]
+pub(crate) fn init_test_block_registry() -> &'static BlockPrototypes {
+    let block_prototypes = crate::mod_manager::prototypes::test_fixture_block_prototypes();
+    BLOCK_REGISTRY.get_or_init(|| build_block_registry(block_prototypes));
+    block_prototypes
+}
+
+/// Air everywhere, with the surface in the chunk layer at y 0. For tests loading chunks around a scanner.
+#[cfg(test)]
+pub(crate) struct TestSky;
+
+#[cfg(test)]
+impl crate::chunky::terrain::TerrainGenerator for TestSky {
+    fn generate(&self, block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition) -> ChunkData {
+        ChunkData::filled(chunk_position, block_prototypes.get("air").expect("Test blocks include air."))
+    }
+
+    fn vertical_extent(&self, _x: i32, _z: i32) -> RangeInclusive<i32> {
+        0..=0
+    }
+}
+
+/// Stone below y = 16 and air above, with the surface in the chunk layer at y 0,
+/// so every chunk below that layer is solid.
+#[cfg(test)]
+pub(crate) struct TestHalfFilled;
+
+#[cfg(test)]
+impl crate::chunky::terrain::TerrainGenerator for TestHalfFilled {
+    fn generate(&self, block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition) -> ChunkData {
+        let block = |name| block_prototypes.get(name).expect("Test blocks include air and stone.");
+        let (air, stone) = (block("air"), block("stone"));
+        ChunkData::from_fn(chunk_position, |position| {
+            if chunk_position.0.y * CHUNK_SIZE_I32 + position.y < 16 { stone } else { air }
+        })
+    }
+
+    fn vertical_extent(&self, _x: i32, _z: i32) -> RangeInclusive<i32> {
+        0..=0
+    }
+}
+
+/// Updates `app` until its scanners and the chunkloader have nothing left to load or unload,
+/// then returns the loaded chunks.
+#[cfg(test)]
+pub(crate) fn settle_chunk_loading(app: &mut App) -> bevy::platform::collections::HashSet<ChunkPosition> {
+    use crate::{
+        chunky::async_chunkloader::{AsyncChunkloader, Chunks},
+        player::render_distance::Scanner,
+    };
+
+    for _ in 0..1000 {
+        app.update();
+        let world = app.world_mut();
+        let scanned = world
+            .query::<&Scanner>()
+            .iter(world)
+            .all(|scanner| scanner.unresolved_data_load.is_empty() && scanner.unresolved_data_unload.is_empty());
+        let chunkloader = world.resource::<AsyncChunkloader>();
+        if scanned
+            && chunkloader.load_chunk_queue.is_empty()
+            && chunkloader.unload_chunk_queue.is_empty()
+            && chunkloader.worldgen_tasks.is_empty()
+        {
+            break;
         }
-        registry
-    });
+    }
+    app.world().resource::<Chunks>().0.keys().copied().collect()
 }
 
 impl ChunkData {
-    /// use noise shape our voxel data based on the `chunk_pos`
+    /// A chunk where every voxel is `block`.
     #[must_use]
-    pub fn generate(block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition) -> Self {
-        // hardcoded extremity check
-        if chunk_position.y * CHUNK_SIZE_I32 > 285 {
-            return Self {
-                voxels: Voxels::Homogeneous(block_prototypes.get("air").unwrap().id),
-                position: chunk_position,
-            };
+    pub const fn filled(chunk_position: ChunkPosition, block: &BlockPrototype) -> Self {
+        Self {
+            voxels: Voxels::Homogeneous(block.id),
+            position: chunk_position,
+            content_hash: OnceLock::new(),
         }
-        // hardcoded extremity check
-        if chunk_position.y * CHUNK_SIZE_I32 < -160 {
-            return Self {
-                voxels: Voxels::Homogeneous(block_prototypes.get("grass").unwrap().id),
-                position: chunk_position,
-            };
+    }
+
+    /// use noise shape our voxel data based on the `chunk_pos`, with the solid blocks of each column picked by its biome,
+    /// see [`biome_at`]. Without biomes every solid block is grass. `surface_octaves` adds detail to the hills,
+    /// see [`Noise::fractal`]. Chunks above `extent` are air, chunks below it are solid,
+    /// see [`TerrainGenerator::vertical_extent`].
+    ///
+    /// [`TerrainGenerator::vertical_extent`]: crate::chunky::terrain::TerrainGenerator::vertical_extent
+    #[must_use]
+    pub fn generate_with_biomes(
+        block_prototypes: &BlockPrototypes,
+        biomes: &BiomePrototypes,
+        surface_octaves: Octaves,
+        extent: &RangeInclusive<i32>,
+        chunk_position: ChunkPosition,
+    ) -> Self {
+        let air = block_prototypes.get("air").expect("Air is a block prototype.");
+        if chunk_position.y > *extent.end() {
+            return Self::filled(chunk_position, air);
         }
 
         let world_position = Position::from(chunk_position);
-        let mut fast_noise = FastNoise::new();
-        fast_noise.set_frequency(0.0254);
-        let mut x = 0;
-        let mut y = 0;
-        let mut z = 0;
-
-        let voxels: Box<[ThinBlockPointer; CHUNK_SIZE3]> = std::array::from_fn(|_| {
-            let wx = (x + world_position.x) as f32;
-            let wy = (y + world_position.y) as f32 - 200.;
-            let wz = (z + world_position.z) as f32;
-
-            let scale = 1.0;
-            fast_noise.set_frequency(0.0254);
-            let overhang = fast_noise.get_noise3d(wx * scale, wy, wz * scale) * 55.0;
-            fast_noise.set_frequency(0.002591);
-            let noise_2 = fast_noise.get_noise(wx + overhang, wz / 3.0);
-            let h = noise_2 * 30.0;
-            let solid = h > wy;
-
-            let block_type = if !solid {
-                block_prototypes.get("air").unwrap()
-            } else {
-                block_prototypes.get("grass").unwrap()
-            };
-
-            x += 1;
-            if x == CHUNK_SIZE_I32 {
-                y += 1;
-                x = 0;
-                if y == CHUNK_SIZE_I32 {
-                    z += 1;
-                    y = 0;
-                }
+        // every column is solid below the extent, so the chunk is a single block unless the biomes differ at its depth
+        if chunk_position.y < *extent.start() {
+            if let Some(block) = biomes.uniform_block(world_position.y, world_position.y + CHUNK_SIZE_I32 - 1) {
+                return Self::filled(chunk_position, block);
             }
+        }
+        let solid_blocks = SolidBlocks::new(block_prototypes, biomes, world_position);
+        if chunk_position.y < *extent.start() {
+            if let SolidBlocks::Uniform(block) = solid_blocks {
+                return Self::filled(chunk_position, block);
+            }
+            let voxels = (0..CHUNK_SIZE3)
+                .map(|i| {
+                    let position = Position::from(VoxelIndex(i));
+                    solid_blocks.at(position.x, world_position.y + position.y, position.z)
+                })
+                .collect();
+            return Self::from_voxels(chunk_position, voxels);
+        }
 
-            block_type.id
-        })
-        .into();
+        let voxels = generate_noise_voxels(world_position, air.id, &solid_blocks, surface_octaves);
+        Self::from_voxels(chunk_position, voxels)
+    }
+
+    /// Builds a chunk by calling `f` for every voxel. Used to set up chunks in tests.
+    #[cfg(test)]
+    #[must_use]
+    pub(crate) fn from_fn(
+        chunk_position: ChunkPosition,
+        mut f: impl FnMut(RelativePosition) -> &'static BlockPrototype,
+    ) -> Self {
+        let voxels = (0..CHUNK_SIZE3)
+            .map(|i| f(RelativePosition(Position::from(VoxelIndex(i)).0)).id)
+            .collect();
+        Self::from_voxels(chunk_position, voxels)
+    }
 
+    /// Stores `voxels` as homogeneous if every voxel is the same block.
+    pub(crate) fn from_voxels(chunk_position: ChunkPosition, voxels: Box<[ThinBlockPointer]>) -> Self {
         if let Some(&first) = voxels.first() {
             let homogeneous = voxels.iter().all(|&block_type| block_type == first);
             if homogeneous {
                 return Self {
                     voxels: Voxels::Homogeneous(first),
                     position: chunk_position,
+                    content_hash: OnceLock::new(),
                 };
             }
         }
 
-        Self {
+        // hashed here, on the worldgen thread, so meshing finds it ready. see `MeshCache`.
+        let chunk_data = Self {
             voxels: Voxels::Heterogeneous(voxels),
             position: chunk_position,
-        }
+            content_hash: OnceLock::new(),
+        };
+        let _ = chunk_data.content_hash();
+        chunk_data
     }
 }
 
@@ -263,3 +645,140 @@ fn index_functions() {
         }
     }
 }
+
+#[test]
+fn content_hash() {
+    let block_prototypes = crate::mod_manager::prototypes::test_block_prototypes();
+    let air = block_prototypes
+        .get("air")
+        .expect("Test blocks include air.");
+    let stone = block_prototypes
+        .get("stone")
+        .expect("Test blocks include stone.");
+
+    let a = ChunkData::filled(ChunkPosition::new(0, 0, 0), stone);
+    let b = ChunkData::filled(ChunkPosition::new(3, -2, 7), stone);
+    assert_eq!(a.content_hash(), b.content_hash());
+
+    // Writing the same block turns the chunk heterogeneous without changing its contents.
+    let mut heterogeneous = a.clone();
+    heterogeneous.set_block(VoxelIndex::new(1, 2, 3), stone);
+    assert!(!heterogeneous.is_homogenous());
+    assert_eq!(a.content_hash(), heterogeneous.content_hash());
+
+    let mut edited = heterogeneous.clone();
+    edited.set_block(VoxelIndex::new(1, 2, 3), air);
+    assert_ne!(heterogeneous.content_hash(), edited.content_hash());
+    assert_ne!(
+        ChunkData::filled(ChunkPosition::new(0, 0, 0), air).content_hash(),
+        a.content_hash()
+    );
+}
+
+#[test]
+fn fill_and_set_region() {
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes
+        .get("air")
+        .expect("Test blocks include air.");
+    let stone = block_prototypes
+        .get("stone")
+        .expect("Test blocks include stone.");
+
+    let mut chunk = ChunkData::filled(ChunkPosition::new(0, 0, 0), stone);
+    chunk.set_block(VoxelIndex::new(4, 5, 6), air);
+    assert!(!chunk.is_homogenous());
+
+    chunk.fill(air);
+    assert!(chunk.is_homogenous());
+    assert_eq!(chunk.content_hash(), ChunkData::filled(ChunkPosition::new(0, 0, 0), air).content_hash());
+
+    // a region past the chunk edges is clamped, so this covers the whole chunk
+    chunk.set_region(RelativePosition::new(-5, -5, -5), RelativePosition::new(40, 40, 40), stone);
+    assert_eq!(chunk.homogeneous_block().map(|block| block.id), Some(stone.id));
+
+    // writing the block the chunk is filled with changes nothing
+    chunk.set_region(RelativePosition::new(1, 1, 1), RelativePosition::new(2, 2, 2), stone);
+    assert!(chunk.is_homogenous());
+
+    chunk.set_region(RelativePosition::new(2, 3, 4), RelativePosition::new(5, 6, 7), air);
+    assert!(!chunk.is_homogenous());
+    for i in 0..CHUNK_SIZE3 {
+        let position = Position::from(VoxelIndex(i)).0;
+        let inside = position.cmpge(IVec3::new(2, 3, 4)).all() && position.cmple(IVec3::new(5, 6, 7)).all();
+        let expected = if inside { air } else { stone };
+        assert_eq!(chunk.get_block(VoxelIndex(i)).id, expected.id);
+    }
+    let mut same = ChunkData::filled(ChunkPosition::new(0, 0, 0), stone);
+    same.set_block(VoxelIndex::new(9, 9, 9), stone);
+    assert_ne!(chunk.content_hash(), same.content_hash());
+
+    // overwriting every other block collapses back to homogeneous
+    chunk.set_region(RelativePosition::new(0, 0, 0), RelativePosition::new(31, 31, 7), stone);
+    assert_eq!(chunk.homogeneous_block().map(|block| block.id), Some(stone.id));
+}
+
+#[test]
+fn surface_chunks_hold_air_and_ground() {
+    let air = 0;
+    let stone = crate::mod_manager::prototypes::test_block_prototypes()
+        .get("stone")
+        .expect("Test blocks include stone.");
+    let solid_blocks = SolidBlocks::Uniform(stone);
+    let world_position = Position::from(ChunkPosition::new(-3, SURFACE_Y.div_euclid(CHUNK_SIZE_I32), 2));
+    let octaves = Octaves::default();
+    let voxels = generate_noise_voxels(world_position, air, &solid_blocks, octaves);
+    assert_eq!(voxels.len(), CHUNK_SIZE3);
+    // the surface crosses this chunk
+    assert!(voxels.contains(&air) && voxels.contains(&stone.id));
+}
+
+#[test]
+fn surface_height_varies_along_a_column() {
+    let noise = TerrainNoise::new(Octaves::default());
+    let heights: Vec<f32> = (-SURFACE_AMPLITUDE..=SURFACE_AMPLITUDE)
+        .map(|wy| noise.surface_height(5., wy as f32, -12.))
+        .collect();
+    assert!(heights.iter().any(|&height| (height - heights[0]).abs() > f32::EPSILON), "{heights:?}");
+}
+
+#[test]
+fn boundary_columns_use_their_own_biome() {
+    use crate::chunky::terrain::{NoiseTerrain, TerrainGenerator};
+
+    let block_prototypes = init_test_block_registry();
+    let biomes = crate::mod_manager::prototypes::test_biome_prototypes();
+    let biome = |x| biome_at(biomes, x, 0).expect("There are test biomes.");
+
+    // a biome border along x which falls inside a chunk
+    let border = (1..100_000)
+        .find(|&x| x.rem_euclid(CHUNK_SIZE_I32) != 0 && biome(x) != biome(x - 1))
+        .expect("Both test biomes show up along the x axis.");
+    // far below the terrain surface, so every voxel is solid
+    let chunk_position = ChunkPosition::new(border.div_euclid(CHUNK_SIZE_I32), 0, 0);
+    let extent = NoiseTerrain::default().vertical_extent(chunk_position.x, chunk_position.z);
+    let chunk_data =
+        ChunkData::generate_with_biomes(block_prototypes, biomes, Octaves::default(), &extent, chunk_position);
+
+    let x = border.rem_euclid(CHUNK_SIZE_I32);
+    for (x, world_x) in [(x - 1, border - 1), (x, border)] {
+        for y in [0, 15, 16, CHUNK_SIZE_I32 - 1] {
+            let block = chunk_data.get_block(VoxelIndex::new(x as usize, y as usize, 0));
+            assert_eq!(block, biome(world_x).block_at(y), "at x {world_x} y {y}");
+        }
+    }
+}
+
+#[test]
+fn heap_bytes_of_both_voxel_layouts() {
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test blocks include air.");
+    let stone = block_prototypes.get("stone").expect("Test blocks include stone.");
+
+    let mut chunk_data = ChunkData::filled(ChunkPosition::new(0, 0, 0), air);
+    assert_eq!(chunk_data.heap_bytes(), 0);
+    chunk_data.set_block(VoxelIndex::new(1, 2, 3), stone);
+    assert_eq!(chunk_data.heap_bytes(), CHUNK_SIZE3 * size_of::<u16>());
+    chunk_data.fill(stone);
+    assert_eq!(chunk_data.heap_bytes(), 0);
+}
@@ -0,0 +1,375 @@
+/*!
+saving edited chunks to disk without blocking the main thread.
+
+edited chunks are marked dirty. when a dirty chunk unloads, its data moves into [`ChunkSaver::unsaved`]
+and a task on the `AsyncComputeTaskPool` writes it to `<directory>/<x>_<y>_<z>.json` in the
+[debug json](super::debug_json) format. the data is only dropped once the write is confirmed,
+failed writes are retried with a growing delay, and a chunk loaded again before its save finished
+picks its edits back up.
+chunks with a save file are read back from it by their worldgen task instead of being generated.
+
+dirty chunks which are still loaded are not saved yet.
+*/
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on},
+};
+use futures_lite::future;
+
+use crate::{chunky::chunk::ChunkData, mod_manager::prototypes::BlockPrototypes, position::ChunkPosition};
+
+use super::async_chunkloader::ChunkLoaderBudget;
+
+/// A running save, returning the data it wrote.
+pub type SaveTask = Task<(Arc<ChunkData>, io::Result<()>)>;
+
+/// How long a chunk waits after its first failed save before it is written again.
+/// Every further failure doubles the wait, up to [`MAX_SAVE_RETRY_DELAY`].
+pub const SAVE_RETRY_DELAY: Duration = Duration::from_secs(1);
+pub const MAX_SAVE_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Resource)]
+pub struct ChunkSaver {
+    /// Where chunk files are written.
+    pub directory: PathBuf,
+    /// Loaded chunks with edits that are not on disk.
+    pub dirty_chunks: HashSet<ChunkPosition>,
+    /// Unloaded chunks with edits that are not confirmed on disk yet. This is the save queue.
+    pub unsaved: HashMap<ChunkPosition, Arc<ChunkData>>,
+    pub save_tasks: HashMap<ChunkPosition, SaveTask>,
+    /// Chunks with a file in `directory`, found when the saver is created and added as saves finish.
+    pub saved_chunks: HashSet<ChunkPosition>,
+    /// Unsaved chunks whose last save failed, by how many saves failed in a row and the [`Time::elapsed`] to retry at.
+    pub failed_saves: HashMap<ChunkPosition, (u32, Duration)>,
+}
+
+impl Default for ChunkSaver {
+    fn default() -> Self {
+        Self::new("saves/world/chunks")
+    }
+}
+
+impl ChunkSaver {
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        let directory = directory.into();
+        Self {
+            saved_chunks: saved_chunk_positions(&directory),
+            directory,
+            dirty_chunks: HashSet::default(),
+            unsaved: HashMap::default(),
+            save_tasks: HashMap::default(),
+            failed_saves: HashMap::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn chunk_path(&self, chunk_position: ChunkPosition) -> PathBuf {
+        chunk_path(&self.directory, chunk_position)
+    }
+
+    /// Queues the chunk for saving if it was edited. Call this when unloading it.
+    pub fn unload(&mut self, chunk_data: Arc<ChunkData>) {
+        if self.dirty_chunks.remove(&chunk_data.position) {
+            self.unsaved.insert(chunk_data.position, chunk_data);
+        }
+    }
+
+    /// Takes back an unloaded chunk whose edits are not confirmed on disk, to load it again instead of generating it.
+    pub fn reload(&mut self, chunk_position: ChunkPosition) -> Option<ChunkData> {
+        let chunk_data = self.unsaved.remove(&chunk_position)?;
+        self.failed_saves.remove(&chunk_position);
+        self.dirty_chunks.insert(chunk_position);
+        Some(Arc::unwrap_or_clone(chunk_data))
+    }
+
+    /// The file to load the chunk from instead of generating it, if it was saved. Read it with [`read_chunk`].
+    #[must_use]
+    pub fn saved_path(&self, chunk_position: ChunkPosition) -> Option<PathBuf> {
+        self.saved_chunks
+            .contains(&chunk_position)
+            .then(|| self.chunk_path(chunk_position))
+    }
+
+    /// The save queue sorted by chunk position, so chunks are written in the same order on every run.
+    #[must_use]
+    pub fn unsaved_in_order(&self) -> Vec<(ChunkPosition, &Arc<ChunkData>)> {
+        let mut unsaved: Vec<_> = self
+            .unsaved
+            .iter()
+            .map(|(&chunk_position, chunk_data)| (chunk_position, chunk_data))
+            .collect();
+        unsaved.sort_unstable_by_key(|&(chunk_position, _)| chunk_position);
+        unsaved
+    }
+}
+
+fn chunk_path(directory: &Path, chunk_position: ChunkPosition) -> PathBuf {
+    let ChunkPosition(IVec3 { x, y, z }) = chunk_position;
+    directory.join(format!("{x}_{y}_{z}.json"))
+}
+
+/// The positions of the chunk files in `directory`, see [`chunk_path`]. Empty if it does not exist yet.
+fn saved_chunk_positions(directory: &Path) -> HashSet<ChunkPosition> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return HashSet::default();
+    };
+    entries
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name();
+            let mut coordinates = file_name.to_str()?.strip_suffix(".json")?.split('_').map(str::parse);
+            match (coordinates.next(), coordinates.next(), coordinates.next(), coordinates.next()) {
+                (Some(Ok(x)), Some(Ok(y)), Some(Ok(z)), None) => Some(ChunkPosition::new(x, y, z)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reads back a chunk written by a save task.
+/// Logs and returns `None` if the file can't be read, so the chunk is generated instead.
+#[must_use]
+pub fn read_chunk(path: &Path, block_prototypes: &BlockPrototypes) -> Option<ChunkData> {
+    let _span = info_span!("load_chunk").entered();
+    let read = || -> anyhow::Result<ChunkData> {
+        let json = fs::read_to_string(path)?;
+        ChunkData::from_debug_json(&json, block_prototypes)
+    };
+    read()
+        .inspect_err(|err| error!("Failed to load {}, generating the chunk instead: {err:#}", path.display()))
+        .ok()
+}
+
+/// Writes through a temporary file, so a crash mid write keeps the previous save.
+fn write_chunk(directory: &Path, chunk_data: &ChunkData) -> io::Result<()> {
+    let _span = info_span!("save_chunk", chunk = %chunk_data.position).entered();
+    fs::create_dir_all(directory)?;
+    let path = chunk_path(directory, chunk_data.position);
+    let temporary_path = path.with_extension("json.tmp");
+    fs::write(&temporary_path, chunk_data.to_debug_json())?;
+    fs::rename(temporary_path, path)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn start_save_threads(
+    mut chunk_saver: ResMut<ChunkSaver>,
+    budget: Res<ChunkLoaderBudget>,
+    time: Res<Time>,
+) {
+    let task_pool = AsyncComputeTaskPool::get();
+    let chunk_saver = &mut *chunk_saver;
+    let now = time.elapsed();
+
+    // a chunk edited again while its last save runs waits for it, so writes land in order.
+    let tasks_left = budget.max_save_tasks.saturating_sub(chunk_saver.save_tasks.len());
+    let to_save: Vec<Arc<ChunkData>> = chunk_saver
+        .unsaved_in_order()
+        .into_iter()
+        .filter(|(chunk_position, _)| !chunk_saver.save_tasks.contains_key(chunk_position))
+        .filter(|(chunk_position, _)| {
+            chunk_saver
+                .failed_saves
+                .get(chunk_position)
+                .is_none_or(|&(_, retry_at)| now >= retry_at)
+        })
+        .take(tasks_left)
+        .map(|(_, chunk_data)| chunk_data.clone())
+        .collect();
+    for chunk_data in to_save {
+        let directory = chunk_saver.directory.clone();
+        let chunk_position = chunk_data.position;
+        let task = task_pool.spawn(async move {
+            let result = write_chunk(&directory, &chunk_data);
+            (chunk_data, result)
+        });
+        chunk_saver.save_tasks.insert(chunk_position, task);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn join_save_threads(mut chunk_saver: ResMut<ChunkSaver>, time: Res<Time>) {
+    let chunk_saver = &mut *chunk_saver;
+    let now = time.elapsed();
+    chunk_saver.save_tasks.retain(|chunk_position, task| {
+        let Some((saved, result)) = block_on(future::poll_once(task)) else {
+            return true;
+        };
+
+        match result {
+            // the chunk may have been reloaded, or edited and unloaded again, since. only drop the data that was written.
+            Ok(()) => {
+                chunk_saver.saved_chunks.insert(*chunk_position);
+                chunk_saver.failed_saves.remove(chunk_position);
+                if chunk_saver
+                    .unsaved
+                    .get(chunk_position)
+                    .is_some_and(|unsaved| Arc::ptr_eq(unsaved, &saved))
+                {
+                    chunk_saver.unsaved.remove(chunk_position);
+                }
+            }
+            Err(err) => {
+                let failures = chunk_saver
+                    .failed_saves
+                    .get(chunk_position)
+                    .map_or(1, |&(failures, _)| failures + 1);
+                let delay = SAVE_RETRY_DELAY
+                    .saturating_mul(2u32.saturating_pow(failures - 1))
+                    .min(MAX_SAVE_RETRY_DELAY);
+                chunk_saver.failed_saves.insert(*chunk_position, (failures, now + delay));
+                error!("Failed to save chunk {chunk_position}, retrying in {delay:?}: {err}");
+            }
+        }
+        false
+    });
+}
+
+#[test]
+fn edited_chunk_is_saved_after_unloading() {
+    use crate::{
+        chunky::async_chunkloader::{
+            AsyncChunkloader, ChunkEntityIndex, Chunks, apply_chunk_modifications, unload_chunks,
+        },
+        chunky::chunk::init_test_block_registry,
+        mod_manager::prototypes::Prototypes,
+        position::RelativePosition,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test blocks include air.");
+    let stone = block_prototypes.get("stone").expect("Test blocks include stone.");
+
+    let directory = std::env::temp_dir().join(format!("talc_chunk_saver_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    let edited = ChunkPosition::new(1, -2, 3);
+    let untouched = ChunkPosition::new(0, 0, 0);
+    let mut chunks = Chunks::default();
+    for chunk_position in [edited, untouched] {
+        chunks.0.insert(chunk_position, Arc::new(ChunkData::filled(chunk_position, air)));
+    }
+    let mut chunkloader = AsyncChunkloader::default();
+    chunkloader
+        .chunk_modifications
+        .insert(edited, vec![(RelativePosition::new(4, 5, 6), stone)]);
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(chunks)
+        .insert_resource(chunkloader)
+        .insert_resource(ChunkSaver::new(&directory))
+        .init_resource::<ChunkEntityIndex>()
+        .init_resource::<ChunkLoaderBudget>()
+        .add_systems(
+            Update,
+            (apply_chunk_modifications, unload_chunks, start_save_threads, join_save_threads).chain(),
+        );
+    app.update();
+    assert!(app.world().resource::<ChunkSaver>().dirty_chunks.contains(&edited));
+
+    app.world_mut()
+        .resource_mut::<AsyncChunkloader>()
+        .unload_chunk_queue
+        .extend([edited, untouched]);
+    app.update();
+    assert!(app.world().resource::<Chunks>().0.is_empty());
+    // the edits stay in memory until the save is confirmed
+    let chunk_saver = app.world().resource::<ChunkSaver>();
+    assert!(chunk_saver.unsaved.contains_key(&edited) || chunk_saver.chunk_path(edited).exists());
+
+    for _ in 0..1000 {
+        app.update();
+        if app.world().resource::<ChunkSaver>().unsaved.is_empty() {
+            break;
+        }
+    }
+    let chunk_saver = app.world().resource::<ChunkSaver>();
+    assert!(chunk_saver.unsaved.is_empty());
+    assert!(chunk_saver.save_tasks.is_empty());
+
+    let json = fs::read_to_string(chunk_saver.chunk_path(edited)).expect("The edited chunk was saved.");
+    let saved = ChunkData::from_debug_json(&json, block_prototypes).expect("The save is a chunk dump.");
+    assert_eq!(saved.position, edited);
+    assert_eq!(saved.get_block(RelativePosition::new(4, 5, 6).into()).name.as_ref(), "stone");
+    assert_eq!(saved.get_block(RelativePosition::new(0, 0, 0).into()).name.as_ref(), "air");
+    assert!(!chunk_saver.chunk_path(untouched).exists());
+
+    // the next run finds the save, and loads the chunk from it
+    assert_eq!(chunk_saver.saved_chunks.iter().collect::<Vec<_>>(), [&edited]);
+    let next_run = ChunkSaver::new(&directory);
+    assert_eq!(next_run.saved_chunks.iter().collect::<Vec<_>>(), [&edited]);
+    assert!(next_run.saved_path(untouched).is_none());
+    let path = next_run.saved_path(edited).expect("The edited chunk was saved.");
+    let loaded = read_chunk(&path, block_prototypes).expect("The save can be read back.");
+    assert_eq!(loaded.get_block(RelativePosition::new(4, 5, 6).into()).name.as_ref(), "stone");
+
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn save_queue_is_in_chunk_position_order() {
+    use crate::{chunky::chunk::init_test_block_registry, mod_manager::prototypes::Prototypes};
+
+    let air = init_test_block_registry().get("air").expect("Test blocks include air.");
+    let mut chunk_saver = ChunkSaver::new(std::env::temp_dir());
+    for i in 0..64 {
+        let chunk_position = ChunkPosition::new(i % 4 - 2, i / 4 % 4 - 2, i / 16 - 2);
+        chunk_saver.dirty_chunks.insert(chunk_position);
+        chunk_saver.unload(Arc::new(ChunkData::filled(chunk_position, air)));
+    }
+
+    let order = |chunk_saver: &ChunkSaver| -> Vec<ChunkPosition> {
+        chunk_saver.unsaved_in_order().into_iter().map(|(chunk_position, _)| chunk_position).collect()
+    };
+    let first = order(&chunk_saver);
+    assert_eq!(first.len(), 64);
+    assert_eq!(first, order(&chunk_saver));
+    assert!(first.is_sorted());
+}
+
+#[test]
+fn failed_saves_back_off() {
+    use crate::{chunky::chunk::init_test_block_registry, mod_manager::prototypes::Prototypes};
+
+    let air = init_test_block_registry().get("air").expect("Test blocks include air.");
+    // a file where the save directory should be, so every save fails
+    let directory = std::env::temp_dir().join(format!("talc_failed_saves_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::write(&directory, "").expect("The temp dir is writable.");
+
+    let chunk_position = ChunkPosition::new(0, 0, 0);
+    let mut chunk_saver = ChunkSaver::new(&directory);
+    chunk_saver.dirty_chunks.insert(chunk_position);
+    chunk_saver.unload(Arc::new(ChunkData::filled(chunk_position, air)));
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(chunk_saver)
+        .init_resource::<ChunkLoaderBudget>()
+        .add_systems(Update, (start_save_threads, join_save_threads).chain());
+    for _ in 0..1000 {
+        app.update();
+        if !app.world().resource::<ChunkSaver>().failed_saves.is_empty() {
+            break;
+        }
+    }
+
+    // the retry waits for its delay instead of running again right away
+    app.update();
+    let chunk_saver = app.world().resource::<ChunkSaver>();
+    assert_eq!(chunk_saver.failed_saves.get(&chunk_position).map(|&(failures, _)| failures), Some(1));
+    assert!(chunk_saver.save_tasks.is_empty());
+    assert!(chunk_saver.unsaved.contains_key(&chunk_position));
+    assert!(chunk_saver.saved_chunks.is_empty());
+
+    let _ = fs::remove_file(&directory);
+}
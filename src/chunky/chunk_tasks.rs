@@ -0,0 +1,126 @@
+//! Background tasks keyed by chunk position, which hand their results back through a bounded channel.
+//! Joining drains the channel instead of polling every task in flight, so its cost follows the finished tasks only.
+
+use std::future::Future;
+
+use async_channel::{Receiver, Sender};
+use bevy::{
+    platform::collections::HashMap,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+
+use crate::position::ChunkPosition;
+
+/// Results waiting to be joined before finished tasks wait for room in the channel.
+pub const RESULT_CHANNEL_CAPACITY: usize = 64;
+
+/// A finished task's result, tagged with the id of the task that produced it.
+type TaskResult<T> = (ChunkPosition, u64, T);
+
+/// At most one task per chunk position. Dropping a task cancels it.
+pub struct ChunkTasks<T> {
+    tasks: HashMap<ChunkPosition, (u64, Task<()>)>,
+    next_id: u64,
+    sender: Sender<TaskResult<T>>,
+    receiver: Receiver<TaskResult<T>>,
+}
+
+impl<T: Send + 'static> Default for ChunkTasks<T> {
+    fn default() -> Self {
+        Self::with_capacity(RESULT_CHANNEL_CAPACITY)
+    }
+}
+
+impl<T: Send + 'static> ChunkTasks<T> {
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, receiver) = async_channel::bounded(capacity);
+        Self {
+            tasks: HashMap::new(),
+            next_id: 0,
+            sender,
+            receiver,
+        }
+    }
+
+    /// Runs `future` on the [`AsyncComputeTaskPool`], replacing and cancelling any task already running for `chunk_position`.
+    pub fn spawn(&mut self, chunk_position: ChunkPosition, future: impl Future<Output = T> + Send + 'static) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let sender = self.sender.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let result = future.await;
+            // a full channel holds the task back until the next join makes room.
+            // the send only fails once the tasks are dropped, nobody wants the result then.
+            let _ = sender.send((chunk_position, id, result)).await;
+        });
+        self.tasks.insert(chunk_position, (id, task));
+    }
+
+    /// Cancels the task for `chunk_position`. Returns whether there was one.
+    pub fn remove(&mut self, chunk_position: &ChunkPosition) -> bool {
+        self.tasks.remove(chunk_position).is_some()
+    }
+
+    /// Cancels every task whose chunk position `keep` rejects. Returns how many were cancelled.
+    pub fn retain(&mut self, mut keep: impl FnMut(&ChunkPosition) -> bool) -> usize {
+        let before = self.tasks.len();
+        self.tasks.retain(|chunk_position, _| keep(chunk_position));
+        before - self.tasks.len()
+    }
+
+    #[must_use]
+    pub fn contains_key(&self, chunk_position: &ChunkPosition) -> bool {
+        self.tasks.contains_key(chunk_position)
+    }
+
+    /// Tasks spawned and not joined yet, finished or not.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Takes up to `max` finished results out of the channel, without polling the tasks still running.
+    pub fn join(&mut self, max: usize) -> Vec<(ChunkPosition, T)> {
+        let mut finished = Vec::new();
+        while finished.len() < max {
+            let Ok((chunk_position, id, result)) = self.receiver.try_recv() else {
+                break;
+            };
+            // a task can finish right before it is cancelled or replaced, its result is dropped here.
+            if self.tasks.get(&chunk_position).is_some_and(|&(task_id, _)| task_id == id) {
+                self.tasks.remove(&chunk_position);
+                finished.push((chunk_position, result));
+            }
+        }
+        finished
+    }
+}
+
+#[test]
+fn replaced_tasks_are_never_joined() {
+    AsyncComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+
+    let chunk_position = ChunkPosition::new(0, 0, 0);
+    let mut tasks = ChunkTasks::with_capacity(2);
+    tasks.spawn(chunk_position, async { 1 });
+    tasks.spawn(chunk_position, async { 2 });
+    tasks.spawn(ChunkPosition::new(1, 0, 0), async { 3 });
+    assert_eq!(tasks.len(), 2);
+
+    let mut joined = Vec::new();
+    for _ in 0..10_000 {
+        joined.extend(tasks.join(1));
+        if tasks.is_empty() {
+            break;
+        }
+        std::thread::yield_now();
+    }
+    joined.sort_unstable_by_key(|&(_, result)| result);
+    assert_eq!(joined, [(chunk_position, 2), (ChunkPosition::new(1, 0, 0), 3)]);
+}
@@ -1,4 +1,4 @@
-use std::{hash::Hash, sync::Arc};
+use std::{hash::Hash, ops::Deref, sync::Arc};
 
 use bevy::prelude::*;
 
@@ -10,46 +10,106 @@ use crate::{
 
 use super::{
     async_chunkloader::Chunks,
-    chunk::{CHUNK_SIZE, CHUNK_SIZE_I32, ChunkData, VoxelIndex},
-    quad::Direction,
+    chunk::{CHUNK_SIZE_I32, ChunkData, FNV_OFFSET_BASIS, hash_block},
+    face_direction::FaceDir,
 };
 
 // Pointers to chunk data, repersented as the middle one with all their neighbours in 3x3x3 cube.
+// Owns an `Arc` per chunk by default so it can be moved into a mesh task.
 #[derive(Clone)]
-pub struct ChunkRefs {
-    pub adjacent_chunks: [Arc<ChunkData>; 27],
+pub struct ChunkRefs<R: Deref<Target = ChunkData> = Arc<ChunkData>> {
+    pub adjacent_chunks: [R; 27],
     pub center_chunk_position: ChunkPosition,
+    /// The block filling each of `adjacent_chunks`, if it is homogeneous.
+    /// Lets `get_block` skip the voxel lookup for sky and underground chunks.
+    homogeneous_blocks: [Option<&'static BlockPrototype>; 27],
+}
+
+/// Borrows the 27 chunks instead of cloning their `Arc`s.
+/// Use this for meshing that happens on the current thread.
+pub type ChunkRefsBorrowed<'a> = ChunkRefs<&'a ChunkData>;
+
+/// finds the 3x3x3 cube of chunks centered on `center_chunk_position`
+/// returns `None` if any of them is missing from `chunks`
+fn try_get_adjacent_chunks(
+    chunks: &Chunks,
+    center_chunk_position: ChunkPosition,
+) -> Option<[&Arc<ChunkData>; 27]> {
+    let mut adjacent_chunks: [Option<&Arc<ChunkData>>; 27] = [None; 27];
+    for (i, chunk) in adjacent_chunks.iter_mut().enumerate() {
+        let offset = ChunkPosition(index_to_ivec3_bounds(i as i32, 3) + IVec3::NEG_ONE);
+        *chunk = Some(chunks.0.get(&(center_chunk_position + offset))?);
+    }
+    Some(adjacent_chunks.map(|chunk| chunk.expect("All 27 adjacent chunks were found.")))
 }
 
 impl ChunkRefs {
     /// construct a `ChunkRefs` at `middle_chunk` position
-    /// # Panics
-    /// if `ChunkData` doesn't exist in input `world_data`
+    /// returns `None` if any of the 27 chunks is missing from `chunks`
     #[must_use]
     pub fn try_new(chunks: &Chunks, center_chunk_position: ChunkPosition) -> Option<Self> {
-        let get_chunk = |i| {
-            //let offset = ADJACENT_CHUNK_DIRECTIONS[i] + IVec3::NEG_ONE;
-            let offset = ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE);
-            chunks.0.get(&(center_chunk_position + offset))
-        };
-        #[rustfmt::skip]
-        let adjacent_chunks: [Arc<ChunkData>; 27] = [
-          get_chunk(0)?.clone(), get_chunk(1)?.clone(), get_chunk(2)?.clone(),
-          get_chunk(3)?.clone(), get_chunk(4)?.clone(), get_chunk(5)?.clone(),
-          get_chunk(6)?.clone(), get_chunk(7)?.clone(), get_chunk(8)?.clone(),
-
-          get_chunk(9)?.clone(), get_chunk(10)?.clone(), get_chunk(11)?.clone(),
-          get_chunk(12)?.clone(), get_chunk(13)?.clone(), get_chunk(14)?.clone(),
-          get_chunk(15)?.clone(), get_chunk(16)?.clone(), get_chunk(17)?.clone(),
-
-          get_chunk(18)?.clone(), get_chunk(19)?.clone(), get_chunk(20)?.clone(),
-          get_chunk(21)?.clone(), get_chunk(22)?.clone(), get_chunk(23)?.clone(),
-          get_chunk(24)?.clone(), get_chunk(25)?.clone(), get_chunk(26)?.clone(),
-        ];
-        Some(Self {
+        Some(Self::new(
+            try_get_adjacent_chunks(chunks, center_chunk_position)?.map(Arc::clone),
+            center_chunk_position,
+        ))
+    }
+
+    /// Index into the 27 adjacent chunks of the offset `vec`, each component in `0..3` with the center at 1.
+    /// The inverse of [`index_to_ivec3_bounds`] with bounds 3.
+    #[must_use]
+    pub const fn vec3_to_chunk_index(vec: IVec3) -> usize {
+        // out of range components would alias another chunk instead of failing
+        debug_assert!(
+            vec.x >= 0 && vec.x < 3 && vec.y >= 0 && vec.y < 3 && vec.z >= 0 && vec.z < 3,
+            "Adjacent chunk offsets range from 0 to 2."
+        );
+        let x_i = vec.x;
+        let y_i = vec.y * 3;
+        let z_i = vec.z * (3 * 3);
+        (x_i + y_i + z_i) as usize
+    }
+}
+
+#[cfg(test)]
+impl ChunkRefs {
+    /// `center` surrounded by 26 chunks filled with `neighbour`. Used to mesh chunks in tests.
+    pub(crate) fn from_single(center: ChunkData, neighbour: &BlockPrototype) -> Self {
+        let center_chunk_position = center.position;
+        let mut center = Some(center);
+        let adjacent_chunks = std::array::from_fn(|i| {
+            let offset = ChunkPosition(index_to_ivec3_bounds(i as i32, 3) + IVec3::NEG_ONE);
+            if offset.0 == IVec3::ZERO {
+                Arc::new(center.take().expect("There is one center chunk."))
+            } else {
+                Arc::new(ChunkData::filled(center_chunk_position + offset, neighbour))
+            }
+        });
+        Self::new(adjacent_chunks, center_chunk_position)
+    }
+}
+
+impl<'a> ChunkRefsBorrowed<'a> {
+    /// construct a `ChunkRefsBorrowed` at `middle_chunk` position
+    /// returns `None` if any of the 27 chunks is missing from `chunks`
+    #[must_use]
+    pub fn try_borrow(chunks: &'a Chunks, center_chunk_position: ChunkPosition) -> Option<Self> {
+        Some(Self::new(
+            try_get_adjacent_chunks(chunks, center_chunk_position)?.map(|chunk| &**chunk),
+            center_chunk_position,
+        ))
+    }
+}
+
+impl<R: Deref<Target = ChunkData>> ChunkRefs<R> {
+    /// `adjacent_chunks` must be ordered by `vec3_to_chunk_index`, centered on `center_chunk_position`.
+    #[must_use]
+    pub fn new(adjacent_chunks: [R; 27], center_chunk_position: ChunkPosition) -> Self {
+        let homogeneous_blocks = std::array::from_fn(|i| adjacent_chunks[i].homogeneous_block());
+        Self {
             adjacent_chunks,
             center_chunk_position,
-        })
+            homogeneous_blocks,
+        }
     }
 
     #[must_use]
@@ -64,23 +124,70 @@ impl ChunkRefs {
             .all(|chunk| chunk.is_homogenous() && chunk.get_block(0.into()) == block_type)
     }
 
+    /// true if the middle chunk and its 6 face neighbours are homogeneous opaque cubes.
+    /// none of the middle chunk's faces can be visible, so it needs no mesh.
+    /// the chunk gets remeshed, and this rechecked, whenever a face neighbour is edited.
+    #[must_use]
+    pub fn is_occluded(&self) -> bool {
+        let is_opaque =
+            |chunk: &ChunkData| chunk.is_homogenous() && chunk.get_block(0.into()).is_opaque_cube();
+
+        [
+            IVec3::ZERO,
+            IVec3::X,
+            IVec3::NEG_X,
+            IVec3::Y,
+            IVec3::NEG_Y,
+            IVec3::Z,
+            IVec3::NEG_Z,
+        ]
+        .into_iter()
+        .all(|offset| {
+            is_opaque(&self.adjacent_chunks[ChunkRefs::vec3_to_chunk_index(offset + IVec3::ONE)])
+        })
+    }
+
+    /// A stable hash of the one voxel shell around the middle chunk, see `ChunkData::content_hash`.
+    /// Together with the middle chunk's content hash it decides the mesh, the mesher samples no further.
+    #[must_use]
+    pub fn boundary_hash(&self) -> u64 {
+        // the voxels of a neighbour touching the middle chunk, along one axis
+        let touching = |offset: i32| match offset {
+            -1 => CHUNK_SIZE_I32 - 1..=CHUNK_SIZE_I32 - 1,
+            0 => 0..=CHUNK_SIZE_I32 - 1,
+            _ => 0..=0,
+        };
+        let mut hash = FNV_OFFSET_BASIS;
+        for (i, chunk) in self.adjacent_chunks.iter().enumerate() {
+            let offset = index_to_ivec3_bounds(i as i32, 3) - IVec3::ONE;
+            if offset == IVec3::ZERO {
+                continue;
+            }
+            for z in touching(offset.z) {
+                for y in touching(offset.y) {
+                    for x in touching(offset.x) {
+                        hash = hash_block(hash, chunk.block_id(Position::new(x, y, z).into()));
+                    }
+                }
+            }
+        }
+        hash
+    }
+
     /// helper function to get block data that may exceed the bounds of the middle chunk
     /// input position is local pos to middle chunk
     #[must_use]
     #[allow(clippy::missing_const_for_fn)]
     pub fn get_block(&self, pos: Position) -> &'static BlockPrototype {
-        let x = (pos.x + CHUNK_SIZE_I32) as usize;
-        let y = (pos.y + CHUNK_SIZE_I32) as usize;
-        let z = (pos.z + CHUNK_SIZE_I32) as usize;
-        let (x_chunk, x) = ((x / CHUNK_SIZE) as i32, (x % CHUNK_SIZE));
-        let (y_chunk, y) = ((y / CHUNK_SIZE) as i32, (y % CHUNK_SIZE));
-        let (z_chunk, z) = ((z / CHUNK_SIZE) as i32, (z % CHUNK_SIZE));
-
-        let chunk_index = Self::vec3_to_chunk_index(IVec3::new(x_chunk, y_chunk, z_chunk));
+        let (chunk_offset, relative_position) = pos.to_chunk_local();
+
+        let chunk_index = ChunkRefs::vec3_to_chunk_index(chunk_offset.0 + IVec3::ONE);
+        if let Some(block) = self.homogeneous_blocks[chunk_index] {
+            return block;
+        }
         let chunk_data = &self.adjacent_chunks[chunk_index];
-        let i = VoxelIndex::new(x, y, z);
 
-        chunk_data.get_block(i)
+        chunk_data.get_block(relative_position.into())
     }
 
     /// helper function to get voxels
@@ -88,7 +195,10 @@ impl ChunkRefs {
     #[must_use]
     #[allow(clippy::missing_const_for_fn)]
     pub fn get_block_no_neighbour(&self, pos: Position) -> &'static BlockPrototype {
-        let chunk_data: &Arc<ChunkData> = &self.adjacent_chunks[13];
+        if let Some(block) = self.homogeneous_blocks[13] {
+            return block;
+        }
+        let chunk_data: &ChunkData = &self.adjacent_chunks[13];
         chunk_data.get_block(pos.into())
     }
 
@@ -113,38 +223,16 @@ impl ChunkRefs {
 
     /// helper function to sample adjacent voxels, von neuman include all facing planes
     #[must_use]
-    pub fn get_von_neumann(&self, pos: Position) -> [(Direction, &'static BlockPrototype); 6] {
+    pub fn get_von_neumann(&self, pos: Position) -> [(FaceDir, &'static BlockPrototype); 6] {
         [
-            (
-                Direction::Back,
-                self.get_block(pos + Position::new(0, 0, -1)),
-            ),
-            (
-                Direction::Forward,
-                self.get_block(pos + Position::new(0, 0, 1)),
-            ),
-            (
-                Direction::Down,
-                self.get_block(pos + Position::new(0, -1, 0)),
-            ),
-            (Direction::Up, self.get_block(pos + Position::new(0, 1, 0))),
-            (
-                Direction::Left,
-                self.get_block(pos + Position::new(-1, 0, 0)),
-            ),
-            (
-                Direction::Right,
-                self.get_block(pos + Position::new(1, 0, 0)),
-            ),
+            FaceDir::Forward,
+            FaceDir::Back,
+            FaceDir::Down,
+            FaceDir::Up,
+            FaceDir::Left,
+            FaceDir::Right,
         ]
-    }
-
-    #[must_use]
-    pub const fn vec3_to_chunk_index(vec: IVec3) -> usize {
-        let x_i = vec.x % 3;
-        let y_i = vec.y * 3;
-        let z_i = vec.z * (3 * 3);
-        (x_i + y_i + z_i) as usize
+        .map(|face_dir| (face_dir, self.get_block(pos + Position(face_dir.air_sample_dir()))))
     }
 
     #[must_use]
@@ -159,14 +247,37 @@ impl ChunkRefs {
     }
 }
 
-impl Hash for ChunkRefs {
+impl<R: Deref<Target = ChunkData>> Hash for ChunkRefs<R> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.center_chunk_position.hash(state);
     }
 }
 
-impl PartialEq<ChunkPosition> for ChunkRefs {
+impl<R: Deref<Target = ChunkData>> PartialEq<ChunkPosition> for ChunkRefs<R> {
     fn eq(&self, other: &ChunkPosition) -> bool {
         *other == self.center_chunk_position
     }
 }
+
+#[test]
+fn chunk_indices_are_unique() {
+    let mut seen = [false; 27];
+    for z in 0..3 {
+        for y in 0..3 {
+            for x in 0..3 {
+                let vec = IVec3::new(x, y, z);
+                let index = ChunkRefs::vec3_to_chunk_index(vec);
+                assert!(!std::mem::replace(&mut seen[index], true), "{vec} shares index {index}");
+                assert_eq!(index_to_ivec3_bounds(index as i32, 3), vec);
+            }
+        }
+    }
+    assert!(seen.iter().all(|&seen| seen));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic = "Adjacent chunk offsets range from 0 to 2."]
+fn out_of_range_chunk_offset_panics() {
+    let _ = ChunkRefs::vec3_to_chunk_index(IVec3::new(0, 3, 0));
+}
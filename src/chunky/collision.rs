@@ -0,0 +1,213 @@
+/*!
+collision queries against the loaded chunks.
+a block is solid if it is not transparent and not a fluid, see `BlockPrototype::is_solid`.
+chunks which are not loaded are treated as empty, so queries near the edge of the
+render distance can pass through terrain which has not been generated yet.
+*/
+
+use bevy::{prelude::*, render::primitives::Aabb};
+
+use crate::{
+    chunky::async_chunkloader::Chunks, mod_manager::prototypes::BlockPrototype,
+    position::Position,
+};
+
+/// The first solid voxel along a ray.
+#[derive(Debug, Clone, Copy)]
+pub struct VoxelHit {
+    pub position: Position,
+    /// The normal of the face the ray entered through. Zero if the ray started inside the voxel.
+    pub normal: IVec3,
+    /// Distance from the ray origin to the entry point, in blocks.
+    pub distance: f32,
+    pub block: &'static BlockPrototype,
+}
+
+impl Chunks {
+    /// The block at a world position, `None` if its chunk is not loaded.
+    #[must_use]
+    pub fn get_block(&self, position: Position) -> Option<&'static BlockPrototype> {
+        let (chunk_position, relative_position) = position.to_chunk_local();
+        let chunk_data = self.0.get(&chunk_position)?;
+        Some(chunk_data.get_block(relative_position.into()))
+    }
+
+    /// The solid block at a world position. Unloaded chunks are empty.
+    fn solid_block(&self, position: Position) -> Option<&'static BlockPrototype> {
+        self.get_block(position).filter(|block| block.is_solid())
+    }
+
+    /// Walks the voxels along the ray (Amanatides & Woo) and returns the first solid one within `max_dist`.
+    /// `dir` does not need to be normalized. `None` for a `max_dist` or `origin` that is not finite,
+    /// the walk could go on forever.
+    #[must_use]
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, max_dist: f32) -> Option<VoxelHit> {
+        self.raycast_blocks(origin, dir, max_dist, BlockPrototype::is_solid)
+    }
+
+    /// [`Chunks::raycast`], stopping at the first block `hits` accepts instead of the first solid one.
+    #[must_use]
+    pub fn raycast_blocks(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_dist: f32,
+        hits: impl Fn(&BlockPrototype) -> bool,
+    ) -> Option<VoxelHit> {
+        if !max_dist.is_finite() || !origin.is_finite() {
+            return None;
+        }
+        let dir = dir.try_normalize()?;
+        let mut voxel = origin.floor().as_ivec3();
+        let step = dir.signum().as_ivec3();
+        // distance along the ray to cross one voxel on each axis
+        let t_delta = dir.recip().abs();
+        // distance along the ray to the first voxel boundary on each axis
+        let next_boundary =
+            voxel.as_vec3() + Vec3::select(dir.cmpgt(Vec3::ZERO), Vec3::ONE, Vec3::ZERO);
+        let mut t_max = Vec3::select(
+            dir.cmpeq(Vec3::ZERO),
+            Vec3::INFINITY,
+            (next_boundary - origin) / dir,
+        );
+        let mut normal = IVec3::ZERO;
+        let mut distance = 0.;
+
+        loop {
+            if let Some(block) = self.get_block(Position(voxel)).filter(|block| hits(block)) {
+                return Some(VoxelHit {
+                    position: Position(voxel),
+                    normal,
+                    distance,
+                    block,
+                });
+            }
+
+            // cross the nearest voxel boundary
+            let axis = if t_max.x <= t_max.y && t_max.x <= t_max.z {
+                0
+            } else if t_max.y <= t_max.z {
+                1
+            } else {
+                2
+            };
+            distance = t_max[axis];
+            t_max[axis] += t_delta[axis];
+            voxel[axis] += step[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+            if distance > max_dist {
+                return None;
+            }
+        }
+    }
+
+    /// Whether any solid voxel overlaps the box. Touching a voxel's face does not count.
+    #[must_use]
+    pub fn overlaps_solid(&self, aabb: Aabb) -> bool {
+        let min = aabb.min().floor().as_ivec3();
+        // the max corner belongs to the previous voxel when it lies exactly on a boundary
+        let max = aabb.max().ceil().as_ivec3() - IVec3::ONE;
+        (min.z..=max.z).any(|z| {
+            (min.y..=max.y).any(|y| {
+                (min.x..=max.x).any(|x| self.solid_block(Position::new(x, y, z)).is_some())
+            })
+        })
+    }
+}
+
+/// A single loaded chunk at the origin, air with stone at (5, 5, 5) and water at (5, 5, 2).
+#[cfg(test)]
+fn test_world() -> Chunks {
+    use std::sync::Arc;
+
+    use crate::{
+        chunky::chunk::{ChunkData, init_test_block_registry},
+        mod_manager::prototypes::Prototypes,
+        position::ChunkPosition,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test registry has air.");
+    let stone = block_prototypes.get("stone").expect("Test registry has stone.");
+    let water = block_prototypes.get("water").expect("Test registry has water.");
+    let chunk_position = ChunkPosition::new(0, 0, 0);
+    let chunk_data = ChunkData::from_fn(chunk_position, |position| match position.to_array() {
+        [5, 5, 5] => stone,
+        [5, 5, 2] => water,
+        _ => air,
+    });
+
+    let mut chunks = Chunks::default();
+    chunks.0.insert(chunk_position, Arc::new(chunk_data));
+    chunks
+}
+
+#[test]
+fn raycast_hits_the_solid_block() {
+    let chunks = test_world();
+
+    let hit = chunks
+        .raycast(Vec3::new(5.5, 5.5, 0.5), Vec3::Z, 10.)
+        .expect("The ray passes through the stone.");
+    assert_eq!(hit.position, Position::new(5, 5, 5));
+    assert_eq!(hit.normal, IVec3::NEG_Z);
+    assert!((hit.distance - 4.5).abs() < 1e-5);
+    assert_eq!(hit.block.name.as_ref(), "stone");
+
+    // a diagonal ray enters through the face it crosses last
+    let hit = chunks
+        .raycast(Vec3::new(3.2, 5.5, 3.5), Vec3::new(1., 0., 1.), 10.)
+        .expect("The ray passes through the stone.");
+    assert_eq!(hit.position, Position::new(5, 5, 5));
+    assert_eq!(hit.normal, IVec3::NEG_X);
+
+    // too short, pointing away, and through an unloaded chunk
+    assert!(chunks.raycast(Vec3::new(5.5, 5.5, 0.5), Vec3::Z, 4.).is_none());
+    assert!(chunks.raycast(Vec3::new(5.5, 5.5, 0.5), Vec3::NEG_Z, 100.).is_none());
+    assert!(chunks.raycast(Vec3::new(5.5, 5.5, -0.5), Vec3::NEG_Z, 100.).is_none());
+    assert!(chunks.raycast(Vec3::new(5.5, 5.5, 0.5), Vec3::ZERO, 100.).is_none());
+    // an endless ray would never stop walking through unloaded chunks
+    assert!(chunks.raycast(Vec3::new(5.5, 5.5, -0.5), Vec3::NEG_Z, f32::INFINITY).is_none());
+    assert!(chunks.raycast(Vec3::new(5.5, 5.5, -0.5), Vec3::NEG_Z, f32::NAN).is_none());
+    assert!(chunks.raycast(Vec3::new(f32::NAN, 5.5, 0.5), Vec3::Z, 10.).is_none());
+
+    let inside = chunks
+        .raycast(Vec3::splat(5.5), Vec3::X, 1.)
+        .expect("The ray starts inside the stone.");
+    assert_eq!(inside.normal, IVec3::ZERO);
+    assert!(inside.distance.abs() < f32::EPSILON);
+}
+
+#[test]
+fn overlaps_solid_block() {
+    let chunks = test_world();
+
+    assert!(chunks.overlaps_solid(Aabb::from_min_max(Vec3::splat(4.5), Vec3::splat(5.5))));
+    assert!(chunks.overlaps_solid(Aabb::from_min_max(Vec3::ZERO, Vec3::splat(32.))));
+    // touching faces
+    assert!(!chunks.overlaps_solid(Aabb::from_min_max(Vec3::splat(4.), Vec3::splat(5.))));
+    assert!(!chunks.overlaps_solid(Aabb::from_min_max(
+        Vec3::new(6., 5., 5.),
+        Vec3::new(7., 6., 6.)
+    )));
+    // unloaded chunks are empty
+    assert!(!chunks.overlaps_solid(Aabb::from_min_max(Vec3::splat(-10.), Vec3::splat(-1.))));
+}
+
+#[test]
+fn water_neither_collides_nor_stops_raycasts() {
+    let chunks = test_world();
+    let water = Aabb::from_min_max(Vec3::new(5., 5., 2.), Vec3::new(6., 6., 3.));
+
+    assert_eq!(
+        chunks.get_block(Position::new(5, 5, 2)).map(|block| block.name.as_ref()),
+        Some("water")
+    );
+    assert!(!chunks.overlaps_solid(water));
+    // the ray from z 0.5 passes the water on its way to the stone
+    let hit = chunks
+        .raycast(Vec3::new(5.5, 5.5, 0.5), Vec3::Z, 10.)
+        .expect("The ray passes through the water into the stone.");
+    assert_eq!(hit.block.name.as_ref(), "stone");
+}
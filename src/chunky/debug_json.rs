@@ -0,0 +1,231 @@
+/*!
+human readable chunk dumps, for attaching the exact chunk that reproduces a meshing bug to a report.
+
+homogeneous chunks are written as a single block:
+```json
+{ "position": [0, 6, 0], "block": "air" }
+```
+other chunks as a palette of block names and a `voxels[z][y][x]` array of palette indices.
+dumps can be read back with [`ChunkData::from_debug_json`], so a reported chunk can be committed as a test fixture.
+*/
+
+use std::fmt::Write;
+
+use anyhow::{Context, bail, ensure};
+use serde_json::Value;
+
+use crate::{
+    chunky::chunk::{CHUNK_SIZE, CHUNK_SIZE3, ChunkData, VoxelIndex},
+    mod_manager::prototypes::{BlockPrototype, BlockPrototypes, Prototypes},
+    position::ChunkPosition,
+};
+
+impl ChunkData {
+    /// Dumps the chunk as json, see the [module docs](self) for the format.
+    #[must_use]
+    pub fn to_debug_json(&self) -> String {
+        let position = self.position;
+        let mut json = format!(
+            "{{\n  \"position\": [{}, {}, {}],\n",
+            position.x, position.y, position.z
+        );
+
+        if let Some(block) = self.homogeneous_block() {
+            let _ = writeln!(json, "  \"block\": {}\n}}", quoted(&block.name));
+            return json;
+        }
+
+        let mut palette: Vec<&str> = vec![];
+        let indices: Vec<usize> = (0..CHUNK_SIZE3)
+            .map(|i| {
+                let name = &*self.get_block(VoxelIndex(i)).name;
+                palette.iter().position(|&entry| entry == name).unwrap_or_else(|| {
+                    palette.push(name);
+                    palette.len() - 1
+                })
+            })
+            .collect();
+
+        let palette: Vec<String> = palette.into_iter().map(quoted).collect();
+        let _ = writeln!(json, "  \"palette\": [{}],", palette.join(", "));
+        json.push_str("  \"voxels\": [\n");
+        for (z, layer) in indices.chunks(CHUNK_SIZE * CHUNK_SIZE).enumerate() {
+            json.push_str("    [\n");
+            for (y, row) in layer.chunks(CHUNK_SIZE).enumerate() {
+                let row: Vec<String> = row.iter().map(ToString::to_string).collect();
+                let comma = if y + 1 < CHUNK_SIZE { "," } else { "" };
+                let _ = writeln!(json, "      [{}]{comma}", row.join(", "));
+            }
+            let comma = if z + 1 < CHUNK_SIZE { "," } else { "" };
+            let _ = writeln!(json, "    ]{comma}");
+        }
+        json.push_str("  ]\n}\n");
+        json
+    }
+
+    /// Reads a chunk written by [`ChunkData::to_debug_json`].
+    ///
+    /// # Errors
+    /// If the json is malformed, names a block missing from `block_prototypes`,
+    /// or the voxels are not a `CHUNK_SIZE` cube of palette indices.
+    pub fn from_debug_json(json: &str, block_prototypes: &BlockPrototypes) -> anyhow::Result<Self> {
+        let json: Value = serde_json::from_str(json).context("Chunk debug json is not valid json.")?;
+
+        let position = json["position"]
+            .as_array()
+            .filter(|position| position.len() == 3)
+            .and_then(|position| {
+                let axis = |i: usize| position[i].as_i64().and_then(|axis| i32::try_from(axis).ok());
+                Some(ChunkPosition::new(axis(0)?, axis(1)?, axis(2)?))
+            })
+            .context("Chunk debug json needs a \"position\" of three integers.")?;
+
+        let block = |name: &Value| -> anyhow::Result<&'static BlockPrototype> {
+            let name = name.as_str().context("Block names must be strings.")?;
+            block_prototypes
+                .get(name)
+                .with_context(|| format!("Chunk debug json uses block \"{name}\", which is not a registered block."))
+        };
+
+        if !json["block"].is_null() {
+            return Ok(Self::filled(position, block(&json["block"])?));
+        }
+
+        let palette = json["palette"]
+            .as_array()
+            .context("Chunk debug json needs either a \"block\" or a \"palette\".")?
+            .iter()
+            .map(block)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut voxels = Vec::with_capacity(CHUNK_SIZE3);
+        let layers = as_rows(&json["voxels"], "voxels")?;
+        for (z, layer) in layers.iter().enumerate() {
+            for (y, row) in as_rows(layer, &format!("voxels[{z}]"))?.iter().enumerate() {
+                for (x, index) in as_rows(row, &format!("voxels[{z}][{y}]"))?.iter().enumerate() {
+                    let Some(&block) = index
+                        .as_u64()
+                        .and_then(|index| palette.get(usize::try_from(index).ok()?))
+                    else {
+                        bail!("voxels[{z}][{y}][{x}] is {index}, which is not an index into the palette.");
+                    };
+                    voxels.push(block.id);
+                }
+            }
+        }
+
+        Ok(Self::from_voxels(position, voxels.into_boxed_slice()))
+    }
+}
+
+/// `value` as an array of exactly `CHUNK_SIZE` entries.
+fn as_rows<'a>(value: &'a Value, path: &str) -> anyhow::Result<&'a Vec<Value>> {
+    let rows = value
+        .as_array()
+        .with_context(|| format!("Chunk debug json needs {path} to be an array."))?;
+    ensure!(
+        rows.len() == CHUNK_SIZE,
+        "{path} has {} entries, chunks are {CHUNK_SIZE} voxels across.",
+        rows.len()
+    );
+    Ok(rows)
+}
+
+fn quoted(name: &str) -> String {
+    serde_json::Value::from(name).to_string()
+}
+
+#[test]
+fn debug_json_lists_every_voxel() {
+    use crate::{
+        chunky::chunk::init_test_block_registry, mod_manager::prototypes::Prototypes,
+        position::ChunkPosition,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test registry has air.");
+    let stone = block_prototypes.get("stone").expect("Test registry has stone.");
+    let slab = block_prototypes.get("slab").expect("Test registry has slab.");
+
+    let filled = ChunkData::filled(ChunkPosition::new(1, -2, 3), air);
+    let json: serde_json::Value =
+        serde_json::from_str(&filled.to_debug_json()).expect("The export is valid json.");
+    assert_eq!(json, serde_json::json!({ "position": [1, -2, 3], "block": "air" }));
+
+    let chunk = ChunkData::from_fn(ChunkPosition::new(0, 0, 0), |position| {
+        match (position.x, position.y, position.z) {
+            (_, 0, _) => stone,
+            (1, 2, 3) => slab,
+            _ => air,
+        }
+    });
+    let json: serde_json::Value =
+        serde_json::from_str(&chunk.to_debug_json()).expect("The export is valid json.");
+    assert_eq!(json["position"], serde_json::json!([0, 0, 0]));
+    let palette = json["palette"].as_array().expect("Heterogeneous chunks have a palette.");
+    let voxels = &json["voxels"];
+    assert_eq!(voxels.as_array().map(Vec::len), Some(CHUNK_SIZE));
+
+    // reading the blocks back through the palette gives the original chunk
+    for i in 0..CHUNK_SIZE3 {
+        let position = crate::position::Position::from(VoxelIndex(i));
+        let index = voxels[position.z as usize][position.y as usize][position.x as usize]
+            .as_u64()
+            .expect("Voxels are palette indices.");
+        let name = palette[index as usize].as_str().expect("The palette holds block names.");
+        assert_eq!(name, &*chunk.get_block(VoxelIndex(i)).name, "at {position}");
+    }
+}
+
+#[test]
+fn debug_json_round_trip() {
+    use crate::chunky::chunk::init_test_block_registry;
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test registry has air.");
+    let stone = block_prototypes.get("stone").expect("Test registry has stone.");
+    let slab = block_prototypes.get("slab").expect("Test registry has slab.");
+
+    let chunk = ChunkData::from_fn(ChunkPosition::new(-1, 4, 2), |position| {
+        if position.y < position.x / 4 {
+            stone
+        } else if position.z == 7 {
+            slab
+        } else {
+            air
+        }
+    });
+    for chunk in [chunk, ChunkData::filled(ChunkPosition::new(0, 9, 0), air)] {
+        let json = chunk.to_debug_json();
+        let imported = ChunkData::from_debug_json(&json, block_prototypes).expect("The export imports.");
+        assert_eq!(imported.position, chunk.position);
+        assert_eq!(imported.is_homogenous(), chunk.is_homogenous());
+        assert_eq!(imported.content_hash(), chunk.content_hash());
+        assert_eq!(imported.to_debug_json(), json);
+    }
+
+    let error = |json: &str| {
+        ChunkData::from_debug_json(json, block_prototypes)
+            .err()
+            .map(|error| error.to_string())
+            .expect("The json is rejected.")
+    };
+    assert!(error(r#"{ "position": [0, 0, 0], "block": "lava" }"#).contains("\"lava\""));
+    assert!(error(r#"{ "position": [0, 0], "block": "air" }"#).contains("position"));
+    assert!(
+        error(r#"{ "position": [0, 0, 0], "palette": ["air"], "voxels": [[]] }"#)
+            .contains("voxels has 1 entries")
+    );
+    let out_of_palette = chunk_json_with_voxel(1);
+    assert!(error(&out_of_palette).contains("voxels[0][0][0] is 1"));
+}
+
+/// A heterogeneous chunk json with a palette of just air, and `index` as the first voxel.
+#[cfg(test)]
+fn chunk_json_with_voxel(index: usize) -> String {
+    let row = format!("[{}]", vec!["0"; CHUNK_SIZE].join(", "));
+    let layer = format!("[{}]", vec![row.as_str(); CHUNK_SIZE].join(", "));
+    let mut voxels = format!("[{}]", vec![layer.as_str(); CHUNK_SIZE].join(", "));
+    voxels.replace_range(3..4, &index.to_string());
+    format!(r#"{{ "position": [0, 0, 0], "palette": ["air"], "voxels": {voxels} }}"#)
+}
@@ -15,7 +15,10 @@ pub enum FaceDir {
 }
 
 impl FaceDir {
-    /// normal data is packed in the shader
+    /// Every face direction, in `normal_index` order.
+    pub const ALL: [Self; 6] = [Self::Left, Self::Right, Self::Down, Self::Up, Self::Forward, Self::Back];
+
+    /// normal data is packed in the shader, indexing the `normals` of `chunk.wgsl`
     #[must_use]
     pub const fn normal_index(self) -> u32 {
         match self {
@@ -28,6 +31,25 @@ impl FaceDir {
         }
     }
 
+    /// The face direction pointing the other way.
+    #[must_use]
+    pub const fn opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Forward => Self::Back,
+            Self::Back => Self::Forward,
+        }
+    }
+
+    /// The face direction packed as `index`, see [`FaceDir::normal_index`]. `None` past the 6 directions.
+    #[must_use]
+    pub const fn from_normal_index(index: u32) -> Option<Self> {
+        if index < 6 { Some(Self::ALL[index as usize]) } else { None }
+    }
+
     /// direction to sample face culling
     #[must_use]
     pub const fn air_sample_dir(self) -> IVec3 {
@@ -81,3 +103,50 @@ impl FaceDir {
         }
     }
 }
+
+/// The `normals` array of the chunk shader, which the packed normal indexes into.
+#[cfg(test)]
+fn shader_normals() -> Vec<IVec3> {
+    let shader = include_str!("../../assets/shaders/chunk.wgsl");
+    let (_, normals) = shader
+        .split_once("var<private> normals")
+        .expect("The chunk shader declares its normals.");
+    let (normals, _) = normals.split_once(");").expect("The normals array is closed.");
+    normals
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("vec3<f32>("))
+        .map(|vector| {
+            let (vector, _) = vector.split_once(')').expect("Each normal is a vec3.");
+            let axes: Vec<i32> = vector
+                .split(',')
+                .map(|axis| axis.trim().parse::<f32>().expect("Normals are float literals.") as i32)
+                .collect();
+            IVec3::from_slice(&axes)
+        })
+        .collect()
+}
+
+#[test]
+fn normal_indices_match_the_shader() {
+    let normals = shader_normals();
+    assert_eq!(normals.len(), FaceDir::ALL.len());
+    for face_dir in FaceDir::ALL {
+        let index = face_dir.normal_index();
+        assert_eq!(normals[index as usize], face_dir.air_sample_dir(), "{face_dir:?}");
+        assert_eq!(FaceDir::from_normal_index(index), Some(face_dir));
+    }
+    assert_eq!(FaceDir::from_normal_index(6), None);
+}
+
+#[test]
+fn opposite_face_dirs_pair_up() {
+    let mut indices: Vec<u32> = FaceDir::ALL.map(FaceDir::normal_index).to_vec();
+    indices.sort_unstable();
+    assert_eq!(indices, (0..6).collect::<Vec<_>>());
+
+    for face_dir in FaceDir::ALL {
+        assert_eq!(face_dir.opposite().opposite(), face_dir);
+        assert_ne!(face_dir.opposite(), face_dir);
+        assert_eq!(face_dir.opposite().air_sample_dir(), -face_dir.air_sample_dir(), "{face_dir:?}");
+    }
+}
@@ -1,5 +1,9 @@
+use bevy::prelude::*;
+
+use crate::position::ChunkPosition;
+
 /// level of detail
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
 pub enum Lod {
     #[default]
     L32,
@@ -35,3 +39,61 @@ impl Lod {
         }
     }
 }
+
+/// Picks the [`Lod`] a chunk is meshed at from its distance to the scanner, in chunks.
+///
+/// The greedy mesher does not downsample yet, a lower lod only meshes part of the chunk.
+/// So the default keeps every chunk at full detail, see [`LodPolicy::tiered`] for distance based levels.
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct LodPolicy {
+    /// `(distance, lod)` pairs by ascending distance. Chunks closer than `distance` get `lod`.
+    pub levels: Vec<(u32, Lod)>,
+    /// The lod of chunks past every level.
+    pub beyond: Lod,
+}
+
+impl Default for LodPolicy {
+    fn default() -> Self {
+        Self {
+            levels: vec![],
+            beyond: Lod::L32,
+        }
+    }
+}
+
+impl LodPolicy {
+    /// Full detail up to 8 chunks away, half up to 16, a quarter beyond.
+    #[must_use]
+    pub fn tiered() -> Self {
+        Self {
+            levels: vec![(8, Lod::L32), (16, Lod::L16)],
+            beyond: Lod::L8,
+        }
+    }
+
+    /// The lod of a chunk `distance` chunks away from the scanner.
+    #[must_use]
+    pub fn lod_at(&self, distance: u32) -> Lod {
+        self.levels
+            .iter()
+            .find(|&&(max_distance, _)| distance < max_distance)
+            .map_or(self.beyond, |&(_, lod)| lod)
+    }
+
+    /// The lod of the chunk at `chunk_position`, for a scanner centered on `scanner_chunk_position`.
+    #[must_use]
+    pub fn lod_of(&self, scanner_chunk_position: ChunkPosition, chunk_position: ChunkPosition) -> Lod {
+        let distance = (chunk_position.0.distance_squared(scanner_chunk_position.0) as f32).sqrt();
+        self.lod_at(distance as u32)
+    }
+}
+
+#[test]
+fn tiered_lods_by_distance() {
+    let policy = LodPolicy::tiered();
+    for (distance, lod) in [(0, Lod::L32), (7, Lod::L32), (8, Lod::L16), (15, Lod::L16), (16, Lod::L8), (100, Lod::L8)] {
+        assert_eq!(policy.lod_at(distance), lod, "{distance} chunks away");
+    }
+    assert_eq!(policy.lod_of(ChunkPosition::new(1, 0, 0), ChunkPosition::new(1, 3, 9)), Lod::L16);
+    assert_eq!(LodPolicy::default().lod_at(100), Lod::L32);
+}
@@ -1,8 +1,14 @@
 pub mod async_chunkloader;
 pub mod chunk;
+pub mod chunk_saver;
+pub mod chunk_tasks;
 pub mod chunks_refs;
+pub mod collision;
 pub mod constants;
+pub mod debug_json;
 pub mod face_direction;
 pub mod greedy_mesher_optimized;
 pub mod lod;
+pub mod noise;
 pub mod quad;
+pub mod terrain;
@@ -0,0 +1,144 @@
+//! Fractal noise for terrain generation, layered out of `bracket_noise` octaves.
+
+use bracket_noise::prelude::FastNoise;
+
+/// How many octaves fractal noise sums up, and how they scale. See [`Noise::fractal`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Octaves {
+    /// 1 is plain noise, every further octave adds finer detail.
+    pub count: u32,
+    /// Frequency multiplier from one octave to the next.
+    pub lacunarity: f32,
+    /// Amplitude multiplier from one octave to the next.
+    pub gain: f32,
+    /// Sharp ridges where the noise crosses zero instead of rolling hills, see [`Noise::ridged`].
+    pub ridged: bool,
+}
+
+impl Default for Octaves {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            lacunarity: 2.,
+            gain: 0.5,
+            ridged: false,
+        }
+    }
+}
+
+/// Seeded 2d and 3d noise at a base frequency.
+pub struct Noise(FastNoise);
+
+impl Noise {
+    #[must_use]
+    pub fn new(seed: u64, frequency: f32) -> Self {
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_frequency(frequency);
+        Self(noise)
+    }
+
+    /// A single octave, roughly within -1..1.
+    #[must_use]
+    pub fn get(&self, x: f32, z: f32) -> f32 {
+        self.0.get_noise(x, z)
+    }
+
+    /// A single octave, roughly within -1..1.
+    #[must_use]
+    pub fn get3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.0.get_noise3d(x, y, z)
+    }
+
+    /// Fractal brownian motion, `octaves` layers of noise each `lacunarity` times finer and `gain` times weaker.
+    /// Normalized by the total amplitude, so it stays roughly within -1..1 whatever the octaves.
+    /// One octave is exactly [`Noise::get`].
+    #[must_use]
+    pub fn fbm(&self, x: f32, z: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        self.sum_octaves(x, z, octaves, lacunarity, gain, |noise| noise)
+    }
+
+    /// Like [`Noise::fbm`], with every octave folded to `1 - |noise|`, so zero crossings become sharp ridges.
+    /// Roughly within 0..1, peaking on the ridges.
+    #[must_use]
+    pub fn ridged(&self, x: f32, z: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        self.sum_octaves(x, z, octaves, lacunarity, gain, |noise| 1. - noise.abs())
+    }
+
+    /// [`Noise::fbm`] or [`Noise::ridged`] as `octaves` says, both roughly within -1..1.
+    #[must_use]
+    pub fn fractal(&self, x: f32, z: f32, octaves: Octaves) -> f32 {
+        let Octaves {
+            count,
+            lacunarity,
+            gain,
+            ridged,
+        } = octaves;
+        if ridged {
+            self.ridged(x, z, count, lacunarity, gain).mul_add(2., -1.)
+        } else {
+            self.fbm(x, z, count, lacunarity, gain)
+        }
+    }
+
+    fn sum_octaves(
+        &self,
+        x: f32,
+        z: f32,
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+        shape: impl Fn(f32) -> f32,
+    ) -> f32 {
+        let mut sum = 0.;
+        let mut total_amplitude = 0.;
+        let mut amplitude = 1.;
+        let mut scale = 1.;
+        for octave in 0..octaves.max(1) {
+            // shifted apart, so the octaves don't all line up on the origin
+            let offset = octave as f32 * 131.7;
+            sum = shape(self.get((x + offset) * scale, (z - offset) * scale)).mul_add(amplitude, sum);
+            total_amplitude += amplitude;
+            amplitude *= gain;
+            scale *= lacunarity;
+        }
+        sum / total_amplitude
+    }
+}
+
+#[test]
+fn fbm_is_deterministic_for_a_seed() {
+    let samples = |seed| {
+        let noise = Noise::new(seed, 0.01);
+        (0..100)
+            .map(|i| noise.fbm(i as f32 * 7.3, i as f32 * -3.1, 4, 2., 0.5))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(samples(7), samples(7));
+    assert_ne!(samples(7), samples(8));
+}
+
+#[test]
+fn one_octave_is_plain_noise() {
+    let noise = Noise::new(3, 0.02);
+    for (x, z) in [(0., 0.), (12.5, -40.), (-731., 88.)] {
+        assert!((noise.fbm(x, z, 1, 2., 0.5) - noise.get(x, z)).abs() < f32::EPSILON);
+        assert!((noise.fractal(x, z, Octaves::default()) - noise.get(x, z)).abs() < f32::EPSILON);
+    }
+}
+
+#[test]
+fn more_octaves_add_detail() {
+    let noise = Noise::new(1, 0.005);
+    // fine detail shows up as differences between samples a block apart
+    let roughness = |octaves| {
+        let samples: Vec<f32> = (0..2000).map(|x| noise.fbm(x as f32, 17., octaves, 2., 0.5)).collect();
+        samples.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum::<f32>() / samples.len() as f32
+    };
+    let (one, four) = (roughness(1), roughness(4));
+    assert!(four > one * 2., "{one} with one octave, {four} with four");
+
+    for x in 0..200 {
+        let ridged = noise.ridged(x as f32 * 3., 5., 4, 2., 0.5);
+        assert!((0. ..=1.).contains(&ridged), "{ridged}");
+    }
+}
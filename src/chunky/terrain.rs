@@ -0,0 +1,498 @@
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::{
+    chunky::{
+        chunk::{CHUNK_SIZE_I32, CHUNK_SIZE3, ChunkData, VoxelIndex, noise_surface_y},
+        noise::Octaves,
+    },
+    mod_manager::prototypes::{BiomePrototypes, BlockPrototypes, Prototypes},
+    position::{ChunkPosition, Position, RelativePosition},
+};
+
+/// The world y where the default terrain surface sits on average.
+pub const SURFACE_Y: i32 = 200;
+/// How far the default terrain surface strays from `SURFACE_Y`, in blocks.
+pub const SURFACE_AMPLITUDE: i32 = 30;
+/// The default sea level, low enough to only flood the valleys of the default terrain.
+pub const SEA_LEVEL: i32 = SURFACE_Y - 10;
+
+/// Produces the voxels of a chunk.
+pub trait TerrainGenerator: Send + Sync + 'static {
+    /// # Panics
+    /// If `block_prototypes` is missing a block this generator places.
+    fn generate(&self, block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition)
+    -> ChunkData;
+
+    /// The chunk y range of the chunk column at (x, z) which can contain both air and solid blocks.
+    /// Chunks above are entirely air, chunks below are entirely solid.
+    /// `generate` must be cheap outside this range wherever [`TerrainGenerator::is_surface_chunk`] is false,
+    /// the loader calls it there without spawning a task.
+    fn vertical_extent(&self, x: i32, z: i32) -> RangeInclusive<i32>;
+
+    /// World y of the highest meshable block `generate` places in the column at world (`x`, `z`),
+    /// `i32::MIN` for a column without any. Computed without generating chunks, see [`Chunks::surface_height`]
+    /// for the height of the loaded, possibly edited, voxels.
+    /// Generators which cannot predict it keep the default, `i32::MIN` everywhere.
+    ///
+    /// [`Chunks::surface_height`]: crate::chunky::async_chunkloader::Chunks::surface_height
+    fn surface_height(&self, _x: i32, _z: i32) -> i32 {
+        i32::MIN
+    }
+
+    /// A copy of this generator using the biomes loaded from mods, `None` for generators without biomes.
+    fn with_biomes(&self, _biomes: &BiomePrototypes) -> Option<Arc<dyn TerrainGenerator>> {
+        None
+    }
+
+    /// Whether generating this chunk is worth a worldgen task.
+    fn is_surface_chunk(&self, chunk_position: ChunkPosition) -> bool {
+        self.vertical_extent(chunk_position.x, chunk_position.z)
+            .contains(&chunk_position.y)
+    }
+
+    /// Whether this chunk can have visible faces before it is edited.
+    /// The chunk right below the extent counts, its top faces can touch air above.
+    fn may_have_faces(&self, chunk_position: ChunkPosition) -> bool {
+        let extent = self.vertical_extent(chunk_position.x, chunk_position.z);
+        (extent.start() - 1..=*extent.end()).contains(&chunk_position.y)
+    }
+}
+
+/// The generator used by the chunkloader.
+#[derive(Resource, Clone)]
+pub struct Terrain(pub Arc<dyn TerrainGenerator>);
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Self(Arc::new(NoiseTerrain::default()))
+    }
+}
+
+/// The bottom of the world and the sea. Everything below `void_y` is air,
+/// with an optional band of `bedrock` blocks right above it.
+/// Between that floor and `sea_level`, air is replaced by an optional `water` block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorldBounds {
+    /// World y of the lowest block that is not void.
+    pub void_y: i32,
+    /// Name of the block lining the bottom of the world, `None` to leave the ground bare above the void.
+    pub bedrock: Option<&'static str>,
+    /// How many blocks thick the bedrock band is.
+    pub bedrock_thickness: i32,
+    /// Name of the block filling the air below `sea_level`, `None` for a dry world.
+    pub water: Option<&'static str>,
+    /// World y of the lowest block that stays dry.
+    pub sea_level: i32,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            void_y: 0,
+            bedrock: Some("bedrock"),
+            bedrock_thickness: 3,
+            water: Some("water"),
+            sea_level: SEA_LEVEL,
+        }
+    }
+}
+
+impl WorldBounds {
+    /// World y right above the bedrock band, everything from here up is left to the generator.
+    #[must_use]
+    pub const fn floor_y(&self) -> i32 {
+        match self.bedrock {
+            Some(_) => self.void_y + self.bedrock_thickness,
+            None => self.void_y,
+        }
+    }
+
+    /// Whether every voxel of the chunk is void or bedrock, so the generator has nothing to add.
+    #[must_use]
+    pub const fn is_below_floor(&self, chunk_position: ChunkPosition) -> bool {
+        (chunk_position.0.y + 1) * CHUNK_SIZE_I32 <= self.floor_y()
+    }
+
+    /// Chunk y of the topmost water block, whose top faces are the sea surface. `None` without a sea.
+    #[must_use]
+    pub const fn sea_surface_chunk(&self) -> Option<i32> {
+        match self.water {
+            Some(_) if self.sea_level > self.floor_y() => Some((self.sea_level - 1).div_euclid(CHUNK_SIZE_I32)),
+            _ => None,
+        }
+    }
+
+    /// Fills the air of `chunk_data` between the floor and `sea_level` with water.
+    /// The sea is flat and the mesher culls water against water, so its surface lines up across chunks.
+    ///
+    /// # Panics
+    /// If `block_prototypes` is missing air or the water block.
+    pub fn flood(&self, block_prototypes: &BlockPrototypes, chunk_data: &mut ChunkData) {
+        let Some(water) = self.water else {
+            return;
+        };
+        let chunk_y = Position::from(chunk_data.position).y;
+        let bottom = self.floor_y().max(chunk_y) - chunk_y;
+        let top = (self.sea_level - 1).min(chunk_y + CHUNK_SIZE_I32 - 1) - chunk_y;
+        if bottom > top {
+            return;
+        }
+        let edge = CHUNK_SIZE_I32 - 1;
+
+        let air = block_prototypes.get("air").expect("The sea floods air.");
+        let water = block_prototypes
+            .get(water)
+            .unwrap_or_else(|| panic!("Water block {water} does not exist."));
+        chunk_data.replace_region(
+            RelativePosition::new(0, bottom, 0),
+            RelativePosition::new(edge, top, edge),
+            air,
+            water,
+        );
+    }
+
+    /// World y of the highest block left in a column whose generated ground tops out at `ground_y`,
+    /// once the void is carved out and the sea flooded in. `i32::MIN` if nothing is left.
+    #[must_use]
+    pub fn surface_height(&self, ground_y: i32) -> i32 {
+        let ground = Some(ground_y)
+            .filter(|&y| y >= self.floor_y())
+            .or(self.bedrock.map(|_| self.floor_y() - 1));
+        let sea = self.sea_surface_chunk().map(|_| self.sea_level - 1);
+        ground.max(sea).unwrap_or(i32::MIN)
+    }
+
+    /// Overwrites the part of `chunk_data` below the floor with air and bedrock.
+    ///
+    /// # Panics
+    /// If `block_prototypes` is missing air or the bedrock block.
+    pub fn carve(&self, block_prototypes: &BlockPrototypes, chunk_data: &mut ChunkData) {
+        let chunk_y = Position::from(chunk_data.position).y;
+        if chunk_y >= self.floor_y() {
+            return;
+        }
+        let top = CHUNK_SIZE_I32 - 1;
+
+        let air = block_prototypes.get("air").expect("The void is made of air.");
+        chunk_data.set_region(
+            RelativePosition::new(0, 0, 0),
+            RelativePosition::new(top, self.void_y - chunk_y - 1, top),
+            air,
+        );
+        if let Some(bedrock) = self.bedrock {
+            let bedrock = block_prototypes
+                .get(bedrock)
+                .unwrap_or_else(|| panic!("Bedrock block {bedrock} does not exist."));
+            chunk_data.set_region(
+                RelativePosition::new(0, self.void_y - chunk_y, 0),
+                RelativePosition::new(top, self.floor_y() - chunk_y - 1, top),
+                bedrock,
+            );
+        }
+    }
+}
+
+/// Hands the biomes loaded from mods to the terrain generator.
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn apply_biomes(biomes: Res<BiomePrototypes>, mut terrain: ResMut<Terrain>) {
+    if let Some(generator) = terrain.0.with_biomes(&biomes) {
+        terrain.0 = generator;
+    }
+}
+
+/// Rolling hills around `SURFACE_Y`, see [`ChunkData::generate_with_biomes`], down to the `bounds` of the world
+/// and flooded up to its sea level.
+#[derive(Default)]
+pub struct NoiseTerrain {
+    pub bounds: WorldBounds,
+    /// Without biomes the hills are grass all the way through.
+    pub biomes: BiomePrototypes,
+    /// One octave by default, more roughen the hills without raising them.
+    pub surface_octaves: Octaves,
+}
+
+impl TerrainGenerator for NoiseTerrain {
+    fn generate(
+        &self,
+        block_prototypes: &BlockPrototypes,
+        chunk_position: ChunkPosition,
+    ) -> ChunkData {
+        let mut chunk_data = if self.bounds.is_below_floor(chunk_position) {
+            ChunkData::filled(chunk_position, block_prototypes.get("air").expect("The void is made of air."))
+        } else {
+            ChunkData::generate_with_biomes(
+                block_prototypes,
+                &self.biomes,
+                self.surface_octaves,
+                &self.vertical_extent(chunk_position.x, chunk_position.z),
+                chunk_position,
+            )
+        };
+        self.bounds.carve(block_prototypes, &mut chunk_data);
+        self.bounds.flood(block_prototypes, &mut chunk_data);
+        chunk_data
+    }
+
+    fn with_biomes(&self, biomes: &BiomePrototypes) -> Option<Arc<dyn TerrainGenerator>> {
+        Some(Arc::new(Self {
+            bounds: self.bounds.clone(),
+            biomes: biomes.clone(),
+            surface_octaves: self.surface_octaves,
+        }))
+    }
+
+    /// The overhang noise moves the surface sideways, so every column shares the same bounds.
+    /// A sea above the hills raises the top to the sea surface, the chunks below it are water instead of air.
+    fn vertical_extent(&self, _x: i32, _z: i32) -> RangeInclusive<i32> {
+        let top = (SURFACE_Y + SURFACE_AMPLITUDE).div_euclid(CHUNK_SIZE_I32);
+        (SURFACE_Y - SURFACE_AMPLITUDE).div_euclid(CHUNK_SIZE_I32)
+            ..=self.bounds.sea_surface_chunk().map_or(top, |sea_surface| sea_surface.max(top))
+    }
+
+    fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let (column, _) = Position::new(x, 0, z).to_chunk_local();
+        let extent = self.vertical_extent(column.x, column.z);
+        self.bounds.surface_height(noise_surface_y(x, z, self.surface_octaves, &extent))
+    }
+
+    /// Chunks below the extent are a single block, unless the biomes of their columns differ at that depth.
+    /// Sampling every column is too slow for the main thread, so those go to a task too.
+    fn is_surface_chunk(&self, chunk_position: ChunkPosition) -> bool {
+        let extent = self.vertical_extent(chunk_position.x, chunk_position.z);
+        let bottom = Position::from(chunk_position).y;
+        extent.contains(&chunk_position.y)
+            || (chunk_position.y < *extent.start()
+                && !self.biomes.is_empty()
+                && !self.bounds.is_below_floor(chunk_position)
+                && self.biomes.uniform_block(bottom, bottom + CHUNK_SIZE_I32 - 1).is_none())
+    }
+
+    /// The underside of the world faces the void.
+    fn may_have_faces(&self, chunk_position: ChunkPosition) -> bool {
+        let extent = self.vertical_extent(chunk_position.x, chunk_position.z);
+        (extent.start() - 1..=*extent.end()).contains(&chunk_position.y)
+            || chunk_position.y == self.bounds.void_y.div_euclid(CHUNK_SIZE_I32)
+    }
+}
+
+/// Grass at `surface_y` on top of dirt, air above.
+/// Every surface chunk is the same, which makes it a baseline for meshing and memory use.
+pub struct FlatTerrain {
+    pub surface_y: i32,
+}
+
+impl TerrainGenerator for FlatTerrain {
+    fn generate(
+        &self,
+        block_prototypes: &BlockPrototypes,
+        chunk_position: ChunkPosition,
+    ) -> ChunkData {
+        let block = |name| block_prototypes.get(name).expect("Flat terrain is made of air, grass and dirt.");
+        let (air, grass, dirt) = (block("air"), block("grass"), block("dirt"));
+
+        let chunk_y = Position::from(chunk_position).y;
+        let voxels = (0..CHUNK_SIZE3)
+            .map(|i| {
+                let y = chunk_y + Position::from(VoxelIndex::from(i)).y;
+                match y.cmp(&self.surface_y) {
+                    Ordering::Less => dirt.id,
+                    Ordering::Equal => grass.id,
+                    Ordering::Greater => air.id,
+                }
+            })
+            .collect();
+        ChunkData::from_voxels(chunk_position, voxels)
+    }
+
+    fn vertical_extent(&self, _x: i32, _z: i32) -> RangeInclusive<i32> {
+        let surface_chunk = self.surface_y.div_euclid(CHUNK_SIZE_I32);
+        surface_chunk..=surface_chunk
+    }
+
+    fn surface_height(&self, _x: i32, _z: i32) -> i32 {
+        self.surface_y
+    }
+}
+
+#[test]
+fn vertical_extent_skips_most_worldgen_tasks() {
+    use crate::player::render_distance::make_offset_vec;
+
+    // the debug camera spawns at y 200
+    let center = ChunkPosition::new(0, (SURFACE_Y - CHUNK_SIZE_I32 / 2) / CHUNK_SIZE_I32, 0);
+    for diameter in [8, 16, 32] {
+        let ball = make_offset_vec(diameter + 1);
+        let tasks = ball
+            .iter()
+            .filter(|offset| NoiseTerrain::default().is_surface_chunk(center + **offset))
+            .count();
+        assert!(tasks * 2 < ball.len(), "{tasks} of {} chunks need a task", ball.len());
+    }
+}
+
+#[test]
+fn deep_chunks_need_a_task_only_where_biomes_differ() {
+    use crate::mod_manager::prototypes::test_biome_prototypes;
+
+    let terrain = NoiseTerrain {
+        bounds: WorldBounds {
+            void_y: -1000,
+            bedrock: None,
+            water: None,
+            ..default()
+        },
+        biomes: test_biome_prototypes().clone(),
+        ..default()
+    };
+    let extent = terrain.vertical_extent(0, 0);
+    // the plains' dirt ends at y 15, below it the biomes disagree
+    assert!(terrain.is_surface_chunk(ChunkPosition::new(0, 0, 0)));
+    assert!(!terrain.is_surface_chunk(ChunkPosition::new(0, 1, 0)));
+    assert!(!terrain.is_surface_chunk(ChunkPosition::new(0, extent.start() - 1, 0)));
+    assert!(!NoiseTerrain::default().is_surface_chunk(ChunkPosition::new(0, 0, 0)));
+}
+
+#[test]
+fn deep_chunk_is_void_below_bedrock() {
+    use crate::chunky::chunk::init_test_block_registry;
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test blocks include air.");
+    let stone = block_prototypes.get("stone").expect("Test blocks include stone.");
+
+    // the bedrock band reaches the top of the chunk at y -1
+    let terrain = NoiseTerrain {
+        bounds: WorldBounds {
+            void_y: -CHUNK_SIZE_I32 + 20,
+            bedrock: Some("stone"),
+            bedrock_thickness: 12,
+            ..default()
+        },
+        ..default()
+    };
+    let chunk_position = ChunkPosition::new(3, -1, -7);
+    let chunk_data = terrain.generate(block_prototypes, chunk_position);
+    for y in 0..CHUNK_SIZE_I32 {
+        let expected = if y < 20 { air } else { stone };
+        for (x, z) in [(0, 0), (5, 31), (31, 17)] {
+            let block = chunk_data.get_block(VoxelIndex::from(RelativePosition::new(x, y, z)));
+            assert_eq!(block.name, expected.name, "at y {y}");
+        }
+    }
+
+    let deeper = terrain.generate(block_prototypes, ChunkPosition::new(3, -2, -7));
+    assert_eq!(deeper.homogeneous_block().map(|block| &block.name), Some(&air.name));
+}
+
+#[test]
+fn sea_fills_the_air_up_to_sea_level() {
+    use crate::{
+        chunky::{
+            chunk::init_test_block_registry, chunks_refs::ChunkRefs, face_direction::FaceDir,
+            greedy_mesher_optimized::build_chunk_instance_data, lod::Lod,
+        },
+        render::chunk_material::ChunkLayer,
+        utils::index_to_ivec3_bounds,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let block = |name| block_prototypes.get(name).expect("Test blocks include air, stone and water.");
+    let (air, stone, water) = (block("air"), block("stone"), block("water"));
+
+    // the sea surface sits on the top of chunk y 0, so the waterline is also a chunk border
+    let bounds = WorldBounds {
+        void_y: -1000,
+        bedrock: None,
+        water: Some("water"),
+        sea_level: CHUNK_SIZE_I32,
+        ..default()
+    };
+    let seabed = |chunk_position: ChunkPosition| {
+        let chunk_y = Position::from(chunk_position).y;
+        let mut chunk_data = ChunkData::from_fn(chunk_position, |position| {
+            if chunk_y + position.y < 10 { stone } else { air }
+        });
+        bounds.flood(block_prototypes, &mut chunk_data);
+        chunk_data
+    };
+
+    let center = seabed(ChunkPosition::new(0, 0, 0));
+    for y in 0..CHUNK_SIZE_I32 {
+        let expected = if y < 10 { stone } else { water };
+        for (x, z) in [(0, 0), (7, 31), (31, 12)] {
+            let block = center.get_block(VoxelIndex::from(RelativePosition::new(x, y, z)));
+            assert_eq!(block.name, expected.name, "at y {y}");
+        }
+    }
+    let above = seabed(ChunkPosition::new(0, 1, 0));
+    assert_eq!(above.homogeneous_block().map(|block| &block.name), Some(&air.name));
+
+    // the neighbours are flooded the same way, so the only faces are the sea surface and the seabed seen through it
+    let adjacent_chunks =
+        std::array::from_fn(|i| Arc::new(seabed(ChunkPosition(index_to_ivec3_bounds(i as i32, 3) - IVec3::ONE))));
+    let mut layers = build_chunk_instance_data(&ChunkRefs::new(adjacent_chunks, center.position), Lod::default());
+    let surface = layers.remove(&ChunkLayer::Alpha).expect("The sea surface is drawn.");
+    let floor = layers.remove(&ChunkLayer::Opaque).expect("The seabed is drawn under the water.");
+    assert!(layers.is_empty());
+    for (renderable_chunk, top_y) in [(surface, CHUNK_SIZE_I32 - 1), (floor, 9)] {
+        assert!(!renderable_chunk.quads().is_empty());
+        for quad in renderable_chunk.quads() {
+            assert_eq!(quad.normal(), FaceDir::Up.normal_index());
+            assert_eq!(quad.position().y, top_y);
+        }
+    }
+
+    // a sea above the hills is generated up to its surface
+    let terrain = NoiseTerrain {
+        bounds: WorldBounds {
+            sea_level: 400,
+            ..default()
+        },
+        ..default()
+    };
+    assert_eq!(*terrain.vertical_extent(0, 0).end(), 399 / CHUNK_SIZE_I32);
+}
+
+#[test]
+fn predicted_surface_height_matches_the_generated_column() {
+    use crate::{
+        chunky::{async_chunkloader::Chunks, chunk::init_test_block_registry},
+        mod_manager::prototypes::test_biome_prototypes,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    // the test blocks have no grass or bedrock, the biomes and a stone floor stand in for them
+    let terrain = NoiseTerrain {
+        bounds: WorldBounds {
+            bedrock: Some("stone"),
+            ..default()
+        },
+        biomes: test_biome_prototypes().clone(),
+        // extra octaves must not push the surface out of the vertical extent
+        surface_octaves: Octaves {
+            count: 4,
+            ..default()
+        },
+    };
+
+    for (x, z) in [(0, 0), (17, -3), (-250, 611), (1234, -987), (-4000, 40)] {
+        let (column, _) = Position::new(x, 0, z).to_chunk_local();
+        let extent = terrain.vertical_extent(column.x, column.z);
+        let mut chunks = Chunks::default();
+        for y in extent.start() - 1..=extent.end() + 1 {
+            let chunk_position = ChunkPosition::new(column.x, y, column.z);
+            chunks.0.insert(chunk_position, Arc::new(terrain.generate(block_prototypes, chunk_position)));
+        }
+
+        let predicted = terrain.surface_height(x, z);
+        assert!(extent.contains(&predicted.div_euclid(CHUNK_SIZE_I32)), "{predicted} at ({x}, {z})");
+        let top = (extent.end() + 2) * CHUNK_SIZE_I32 - 1;
+        assert_eq!(chunks.surface_height(x, z, top), Some(predicted), "at ({x}, {z})");
+        // the chunk above the loaded ones is not loaded, so the walk down never starts
+        assert_eq!(chunks.surface_height(x, z, top + 1), None, "at ({x}, {z})");
+    }
+}
@@ -1,14 +1,15 @@
 //! FPS counter for Bevy game engine
 
 use bevy::{
-    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    diagnostic::{Diagnostic, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
     prelude::*,
     window::{PresentMode, Window, PrimaryWindow},
 };
 
     use std::time::Duration;
 
-    use crate::{chunky::{async_chunkloader::Chunks, chunk::Chunk}, render::chunk_material::RenderableChunk};
+    use crate::player::key_bindings::{Action, KeyBindings};
+    use crate::{chunky::{async_chunkloader::{ChunkLoadingPaused, Chunks, MESH_EARLY_EXITS, MESH_QUADS, STALE_MESHES_DISCARDED}, chunk::Chunk}, player::render_distance::Scanner, render::chunk_material::RenderableChunk};
 
 pub const FONT_SIZE: f32 = 32.;
 pub const FONT_COLOR: Color = Color::WHITE;
@@ -24,26 +25,76 @@ impl Plugin for FpsCounterPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(FrameTimeDiagnosticsPlugin::default())
             .add_systems(Startup, spawn_text)
+            .add_systems(Update, fps_counter_toggle_keybind.before(update))
             .add_systems(Update, update)
-            .add_systems(Update, vsync_toggle_keybind)
-            .init_resource::<FpsCounter>();
+            .add_systems(
+                Update,
+                (
+                    present_mode_cycle_keybind,
+                    apply_present_mode.run_if(resource_changed::<RequestedPresentMode>),
+                )
+                    .chain(),
+            )
+            .init_resource::<RequestedPresentMode>()
+            .init_resource::<FpsCounter>()
+            .init_resource::<KeyBindings>();
     }
 }
 
-fn vsync_toggle_keybind(
-    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+/// The present modes V cycles through, to compare vsync against uncapped frame rates.
+/// Only the automatic modes, which wgpu resolves against what the surface supports:
+/// `AutoNoVsync` tries Immediate, then Mailbox, then Fifo. Explicit modes panic where unsupported.
+/// Switching only reconfigures the window surface, the render device is kept.
+pub const PRESENT_MODES: [PresentMode; 2] = [PresentMode::AutoVsync, PresentMode::AutoNoVsync];
+
+/// The present mode picked with V, one of [`PRESENT_MODES`].
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestedPresentMode(pub PresentMode);
+
+impl Default for RequestedPresentMode {
+    fn default() -> Self {
+        Self(PresentMode::AutoVsync)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn present_mode_cycle_keybind(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut requested: ResMut<RequestedPresentMode>,
+) {
+    if key_bindings.just_pressed(&keyboard_input, Action::CyclePresentMode) {
+        let index = PRESENT_MODES.iter().position(|&mode| mode == requested.0);
+        requested.0 = PRESENT_MODES[index.map_or(0, |index| (index + 1) % PRESENT_MODES.len())];
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn apply_present_mode(
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    requested: Res<RequestedPresentMode>,
 ) {
     let Ok(mut window) = primary_window.single_mut() else {
         return;
     };
+    if window.present_mode != requested.0 {
+        info!("Switching present mode to {:?}.", requested.0);
+        window.present_mode = requested.0;
+    }
+}
 
-    if keyboard_input.just_pressed(KeyCode::KeyV) {
-        window.present_mode = if window.present_mode == PresentMode::AutoVsync {
-            PresentMode::AutoNoVsync
-        } else {
-            PresentMode::AutoVsync
-        };
+/// Shows and hides the FPS counter with F3.
+#[allow(clippy::needless_pass_by_value)]
+fn fps_counter_toggle_keybind(
+    fps_counter: Option<ResMut<FpsCounter>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+) {
+    let Some(mut fps_counter) = fps_counter else {
+        return;
+    };
+    if key_bindings.just_pressed(&keyboard_input, Action::ToggleDebug) {
+        fps_counter.toggle();
     }
 }
 
@@ -79,12 +130,22 @@ impl FpsCounter {
     pub fn is_enabled(&self) -> bool {
         !self.timer.paused()
     }
+
+    /// Enable the FPS counter if it is disabled, disable it otherwise
+    pub fn toggle(&mut self) {
+        if self.is_enabled() {
+            self.disable();
+        } else {
+            self.enable();
+        }
+    }
 }
 
 /// The marker on the text to be updated
 #[derive(Component)]
 pub struct FpsCounterText;
 
+#[allow(clippy::too_many_arguments)]
 fn update(
     time: Res<Time>,
     diagnostics: Res<DiagnosticsStore>,
@@ -92,7 +153,9 @@ fn update(
     mut query: Query<Entity, With<FpsCounterText>>,
     mut writer: TextUiWriter,
     chunk_entities: Res<Chunks>,
-    renderable_chunks: Query<(&Chunk, &RenderableChunk)>
+    chunk_loading_paused: Res<ChunkLoadingPaused>,
+    renderable_chunks: Query<(&Chunk, &RenderableChunk)>,
+    scanners: Query<&Scanner>,
 ) {
     let Some(mut state) = state_resources else {
         return;
@@ -100,6 +163,7 @@ fn update(
     if !(state.update_now || state.timer.tick(time.delta()).just_finished()) {
         return;
     }
+    state.update_now = false;
     if state.timer.paused() {
         for entity in query.iter_mut() {
             writer.text(entity, 0).clear();
@@ -109,7 +173,21 @@ fn update(
 
         for entity in query.iter_mut() {
             if let Some((fps, frame_time)) = fps_dialog {
-                *writer.text(entity, 0) = format!("{}{:.0}\n{:.1} ms\nloaded chunks: {}\nmeshed chunks: {}", STRING_FORMAT, fps, frame_time, chunk_entities.0.len(), renderable_chunks.iter().len());
+                let render_distance = scanners.iter().next().map_or(0, |scanner| scanner.distance);
+                let stale_meshes = diagnostics
+                    .get(&STALE_MESHES_DISCARDED)
+                    .and_then(Diagnostic::value)
+                    .unwrap_or_default();
+                let mesh_quads = diagnostics
+                    .get(&MESH_QUADS)
+                    .and_then(Diagnostic::average)
+                    .unwrap_or_default();
+                let mesh_early_exits = diagnostics
+                    .get(&MESH_EARLY_EXITS)
+                    .and_then(Diagnostic::average)
+                    .unwrap_or_default();
+                let voxel_memory = chunk_entities.total_bytes() as f64 / (1024. * 1024.);
+                *writer.text(entity, 0) = format!("{}{:.0}\n{:.1} ms\nloaded chunks: {}{}\nvoxel memory: {:.1} MiB\nmeshed chunks: {}\nrender distance: {} (+/-)\nstale meshes discarded: {}\nquads per mesh: {:.0}\nmesh early exits: {:.0}%", STRING_FORMAT, fps, frame_time, chunk_entities.0.len(), if chunk_loading_paused.0 { " (paused)" } else { "" }, voxel_memory, renderable_chunks.iter().len(), render_distance, stale_meshes, mesh_quads, mesh_early_exits * 100.);
             } else {
                 *writer.text(entity, 0) = STRING_MISSING.to_string();
             }
@@ -142,4 +220,66 @@ fn spawn_text(mut commands: Commands) {
             TextColor(FONT_COLOR),
         ))
         .insert(FpsCounterText);
-}
\ No newline at end of file
+}
+
+#[test]
+fn f3_toggles_the_fps_counter() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), bevy::text::TextPlugin))
+        .init_asset::<Image>()
+        .init_asset::<TextureAtlasLayout>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<KeyBindings>()
+        .init_resource::<DiagnosticsStore>()
+        .init_resource::<Chunks>()
+        .init_resource::<ChunkLoadingPaused>()
+        .init_resource::<FpsCounter>()
+        .add_systems(Update, (fps_counter_toggle_keybind, update).chain());
+    let text = app.world_mut().spawn((Text::new(STRING_INITIAL), FpsCounterText)).id();
+
+    let press_f3 = |app: &mut App| {
+        let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keyboard_input.release(KeyCode::F3);
+        keyboard_input.clear();
+        keyboard_input.press(KeyCode::F3);
+        app.update();
+        let text = app.world().get::<Text>(text).expect("The counter has text.");
+        (app.world().resource::<FpsCounter>().is_enabled(), text.0.clone())
+    };
+
+    for _ in 0..2 {
+        assert_eq!(press_f3(&mut app), (false, String::new()));
+        // no frame time diagnostics in this app
+        assert_eq!(press_f3(&mut app), (true, STRING_MISSING.to_string()));
+    }
+}
+
+#[test]
+fn v_cycles_present_modes() {
+    let mut app = App::new();
+    app.init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<KeyBindings>()
+        .init_resource::<RequestedPresentMode>()
+        .add_systems(
+            Update,
+            (
+                present_mode_cycle_keybind,
+                apply_present_mode.run_if(resource_changed::<RequestedPresentMode>),
+            )
+                .chain(),
+        );
+    let window = app.world_mut().spawn((Window::default(), PrimaryWindow)).id();
+
+    let press_v = |app: &mut App| {
+        let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keyboard_input.release(KeyCode::KeyV);
+        keyboard_input.clear();
+        keyboard_input.press(KeyCode::KeyV);
+        app.update();
+        let window = app.world().get::<Window>(window).expect("The window was spawned.");
+        (app.world().resource::<RequestedPresentMode>().0, window.present_mode)
+    };
+
+    assert_eq!(press_v(&mut app), (PresentMode::AutoNoVsync, PresentMode::AutoNoVsync));
+    assert_eq!(press_v(&mut app), (PresentMode::AutoVsync, PresentMode::AutoVsync));
+}
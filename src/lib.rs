@@ -1,11 +1,14 @@
 #![feature(stmt_expr_attributes)]
 #![feature(lock_value_accessors)]
 
+pub mod atmosphere;
 pub mod chunky;
 pub mod mod_manager;
+pub mod origin_gizmo;
 pub mod player;
 pub mod position;
 pub mod render;
+pub mod screenshot;
 pub mod smooth_transform;
 pub mod sun;
 pub mod utils;
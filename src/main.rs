@@ -1,30 +1,50 @@
 use std::f32::consts::PI;
+use std::time::Duration;
 
 use bevy::prelude::*;
 use bevy::{
     app::TaskPoolThreadAssignmentPolicy,
     core_pipeline::bloom::Bloom,
-    pbr::{Atmosphere, AtmosphereSettings},
+    pbr::AtmosphereSettings,
     render::{
         RenderPlugin,
         settings::{RenderCreation, WgpuFeatures, WgpuSettings},
     },
 };
 
+use talc::atmosphere::{AtmospherePreset, AtmospherePresetPlugin};
 use talc::debug_menu::FpsCounterPlugin;
 use talc::mod_manager::mod_loader::ModLoaderPlugin;
+use talc::origin_gizmo::OriginGizmoPlugin;
 use talc::player::{
+    block_info::BlockInfoPlugin,
+    block_picker::BlockPickerPlugin,
+    camera_settings::CameraSettingsPlugin,
     debug_camera::{FlyCam, NoCameraPlayerPlugin},
+    key_bindings::KeyBindingsPlugin,
+    minimap::MinimapPlugin,
     render_distance::Scanner,
     render_distance::ScannerPlugin,
+    spawn::{SpawnConfig, SpawnConfigPlugin},
+    teleport::TeleportPlugin,
 };
 use talc::render::chunk_render_pipeline::ChunkRenderPipelinePlugin;
+use talc::screenshot::{ScreenshotAfterFrames, ScreenshotPlugin};
 use talc::smooth_transform::smooth_transform;
-use talc::{chunky::async_chunkloader::AsyncChunkloaderPlugin, sun::SunPlugin};
+use talc::{
+    chunky::{
+        async_chunkloader::{AsyncChunkloaderPlugin, ChunkUnloadGrace, WarmStart},
+        terrain::Terrain,
+    },
+    sun::SunPlugin,
+};
 
 fn main() {
-    App::new()
-        .add_plugins((DefaultPlugins
+    let mut app = App::new();
+    if let Some(screenshot) = ScreenshotAfterFrames::from_args(std::env::args()) {
+        app.insert_resource(screenshot);
+    }
+    app.add_plugins((DefaultPlugins
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     present_mode: bevy::window::PresentMode::AutoVsync,
@@ -52,20 +72,35 @@ fn main() {
                     ..default()
                 },
             }),))
+        .add_plugins(KeyBindingsPlugin::default())
+        .add_plugins(SpawnConfigPlugin::default())
+        .insert_resource(ChunkUnloadGrace(Duration::from_secs(3)))
+        .insert_resource(WarmStart { radius: 2 })
         .add_plugins(AsyncChunkloaderPlugin)
         .add_plugins(SunPlugin)
+        .add_plugins(AtmospherePresetPlugin)
         .add_plugins(ScannerPlugin)
+        .add_plugins(TeleportPlugin)
         .add_systems(Startup, setup)
         .add_plugins(ModLoaderPlugin)
         .add_plugins(NoCameraPlayerPlugin)
+        .add_plugins(CameraSettingsPlugin::default())
         .add_systems(Update, smooth_transform)
         .add_plugins(ChunkRenderPipelinePlugin)
         .add_plugins(FpsCounterPlugin)
+        .add_plugins(OriginGizmoPlugin)
+        .add_plugins(BlockPickerPlugin)
+        .add_plugins(BlockInfoPlugin)
+        .add_plugins(MinimapPlugin)
+        .add_plugins(ScreenshotPlugin)
         .run();
 }
 
+#[allow(clippy::needless_pass_by_value)]
 pub fn setup(
     mut commands: Commands,
+    spawn: Res<SpawnConfig>,
+    terrain: Res<Terrain>,
     #[allow(unused)] mut materials: ResMut<Assets<StandardMaterial>>,
     #[allow(unused)] mut meshes: ResMut<Assets<Mesh>>,
 ) {
@@ -82,27 +117,14 @@ pub fn setup(
     commands
         .spawn((
             Scanner::new(12),
-            Transform::from_xyz(0.0, 200.0, 0.5),
+            Transform::from_translation(spawn.translation(terrain.0.as_ref())),
             Camera3d::default(),
             FlyCam,
             Camera {
                 hdr: true,
                 ..default()
             },
-            Atmosphere {
-                bottom_radius: 5_000.0,
-                top_radius: 64_600.0 * 3.,
-                ground_albedo: Vec3::splat(0.3),
-                rayleigh_density_exp_scale: 1.0 / 8_000.0,
-                rayleigh_scattering: Vec3::new(5.802e-5, 13.558e-5, 33.100e-5),
-                mie_density_exp_scale: 1.0 / 1_200.0,
-                mie_scattering: 3.996e-6,
-                mie_absorption: 0.444e-6,
-                mie_asymmetry: 0.8,
-                ozone_layer_altitude: 25_000.0,
-                ozone_layer_width: 30_000.0,
-                ozone_absorption: Vec3::new(0.650e-6, 1.881e-6, 0.085e-6),
-            },
+            AtmospherePreset::default().atmosphere(),
             AtmosphereSettings {
                 aerial_view_lut_max_distance: 3.2,
                 scene_units_to_m: 1.,
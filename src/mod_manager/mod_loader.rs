@@ -1,19 +1,23 @@
-#![allow(clippy::unwrap_used)]
-
 use std::{
     collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
     fs,
     path::{Path, PathBuf},
 };
 
-use anyhow::Result;
 use bevy::prelude::*;
 use mlua::{FromLua, Lua, Table, Value};
 use serde::Deserialize;
 
 use crate::chunky::chunk::set_block_registry;
+use crate::render::chunk_render_pipeline::ChunkShaderPath;
 
-use super::prototypes::{BlockPrototypesBuilder, PrototypesBuilder, RawBlockPrototype};
+use super::prototypes::{
+    BiomePrototypes, BiomePrototypesBuilder, BlockPrototypes, BlockPrototypesBuilder,
+    PrototypesBuilder, RawBiomePrototype, RawBlockPrototype, RawRecipePrototype,
+    RecipePrototypes, RecipePrototypesBuilder,
+};
 
 pub struct ModLoaderPlugin;
 
@@ -27,12 +31,14 @@ impl Plugin for ModLoaderPlugin {
 struct Mod {
     name: String,
     path: PathBuf,
+    /// Replaces the chunk shader, relative to the mod folder. See [`chunk_shader_override`].
+    shader: Option<String>,
     //dependancies: Vec<Box<Mod>>,
     //dependants: Vec<Box<Mod>>
 }
 
 impl Mod {
-    fn from_path(path: &Path) -> Self {
+    fn from_path(path: &Path) -> Result<Self, ModLoadError> {
         #[allow(unused)]
         #[derive(Debug, Deserialize)]
         struct ModInfo {
@@ -53,116 +59,405 @@ impl Mod {
             repository: String,
             #[serde(default)]
             exclude: Vec<String>,
+            #[serde(default)]
+            shader: Option<String>,
         }
 
-        let contents = std::fs::read_to_string(path.join("info.toml")).unwrap();
-        let mod_info: ModInfo = toml::from_str(&contents).unwrap();
+        // the name is only known once info.toml is read, until then the mod goes by its folder
+        let folder_name = path
+            .file_name()
+            .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+        let file = path.join("info.toml");
+        let contents = fs::read_to_string(&file).map_err(|source| ModLoadError::Io {
+            mod_name: folder_name.clone(),
+            file: file.clone(),
+            source,
+        })?;
+        let mod_info: ModInfo = toml::from_str(&contents).map_err(|source| ModLoadError::Toml {
+            mod_name: folder_name,
+            file,
+            source,
+        })?;
 
-        Self {
+        Ok(Self {
             name: mod_info.mod_data.name,
             path: path.to_path_buf(),
-        }
+            shader: mod_info.mod_data.shader,
+        })
+    }
+
+    /// Runs the `script` file of this mod, e.g. `data.lua`.
+    fn run(&self, lua: &Lua, script: &str) -> Result<(), ModLoadError> {
+        let file = self.path.join(script);
+        let chunk = fs::read_to_string(&file).map_err(|source| ModLoadError::Io {
+            mod_name: self.name.clone(),
+            file: file.clone(),
+            source,
+        })?;
+        lua.load(chunk)
+            .set_name(format!("@{}", file.display()))
+            .exec()
+            .map_err(|source| ModLoadError::Lua {
+                mod_name: self.name.clone(),
+                file,
+                source,
+            })
     }
 }
 
-/*
+/// Why loading mods failed. Errors in a mod name the mod and the file at fault.
 #[derive(Debug)]
-struct ModLoadError {
-    offender: Rc<Mod>,
-    reason: String
+pub enum ModLoadError {
+    /// The mods directory itself could not be listed.
+    ModsDirectory { path: PathBuf, source: std::io::Error },
+    /// A file of a mod could not be read.
+    Io {
+        mod_name: String,
+        file: PathBuf,
+        source: std::io::Error,
+    },
+    /// The `info.toml` of a mod is not valid.
+    Toml {
+        mod_name: String,
+        file: PathBuf,
+        source: toml::de::Error,
+    },
+    /// A data stage script of a mod raised an error.
+    Lua {
+        mod_name: String,
+        file: PathBuf,
+        source: mlua::Error,
+    },
+    /// No mod created the global `data` table the prototypes are read from.
+    MissingDataTable(mlua::Error),
+    /// A prototype in the `data` table could not be converted.
+    /// Every mod writes to the same table, so it is named by its kind and name instead of by mod.
+    Prototype {
+        kind: &'static str,
+        name: String,
+        source: anyhow::Error,
+    },
 }
 
 impl Display for ModLoadError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Failed to load mods.\n{}\n{}", self.offender.name, self.reason)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ModsDirectory { path, source } => {
+                write!(f, "could not read the mods directory {}: {source}", path.display())
+            }
+            Self::Io { mod_name, file, source } => {
+                write!(f, "mod \"{mod_name}\": could not read {}: {source}", file.display())
+            }
+            Self::Toml { mod_name, file, source } => {
+                write!(f, "mod \"{mod_name}\": {} is not valid: {source}", file.display())
+            }
+            Self::Lua { mod_name, file, source } => {
+                write!(f, "mod \"{mod_name}\": {} failed: {source}", file.display())
+            }
+            Self::MissingDataTable(source) => write!(f, "no mod created the data table: {source}"),
+            Self::Prototype { kind, name, source } => write!(f, "{kind} prototype \"{name}\" is invalid: {source:#}"),
+        }
+    }
+}
+
+impl Error for ModLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ModsDirectory { source, .. } | Self::Io { source, .. } => Some(source),
+            Self::Toml { source, .. } => Some(source),
+            Self::Lua { source, .. } | Self::MissingDataTable(source) => Some(source),
+            Self::Prototype { source, .. } => Some(&**source),
+        }
     }
 }
 
-impl Error for ModLoadError {}
+/// Every [`ModLoadError`] of a failed load, so all broken mods are reported at once.
+#[derive(Debug)]
+pub struct ModLoadErrors(pub Vec<ModLoadError>);
 
-impl From<RhaiError> for ModLoadError {
-    fn from(value: RhaiError) -> Self {
-        todo!()
+impl ModLoadErrors {
+    fn check(errors: Vec<ModLoadError>) -> Result<(), Self> {
+        if errors.is_empty() { Ok(()) } else { Err(Self(errors)) }
     }
 }
-*/
 
-fn detect_mods() -> Box<[Mod]> {
+impl From<ModLoadError> for ModLoadErrors {
+    fn from(error: ModLoadError) -> Self {
+        Self(vec![error])
+    }
+}
+
+impl Display for ModLoadErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to load mods.")?;
+        for error in &self.0 {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ModLoadErrors {}
+
+/// Where the game looks for mods.
+pub const MODS_PATH: &str = "assets/mods";
+
+fn detect_mods(mods_path: &Path) -> Result<Box<[Mod]>, ModLoadErrors> {
+    let mods_directory_error = |source| ModLoadError::ModsDirectory {
+        path: mods_path.to_path_buf(),
+        source,
+    };
     let mut mods: Vec<Mod> = vec![];
-    let mods_path: PathBuf = "assets/mods".into();
+    let mut errors = vec![];
 
-    for entry in fs::read_dir(mods_path).expect("Could not find mods directory.") {
-        let entry = entry.expect("Could not find mods directory.");
-        let path = entry.path();
+    for entry in fs::read_dir(mods_path).map_err(mods_directory_error)? {
+        let path = entry.map_err(mods_directory_error)?.path();
 
         // Check if the entry is a directory
         if path.is_dir() {
             // Check for info.toml in this directory
             let info_toml = path.join("info.toml");
             if info_toml.is_file() {
-                mods.push(Mod::from_path(&path));
+                match Mod::from_path(&path) {
+                    Ok(mod_) => mods.push(mod_),
+                    Err(error) => errors.push(error),
+                }
             }
         }
     }
 
-    mods.into_boxed_slice()
+    ModLoadErrors::check(errors)?;
+    Ok(mods.into_boxed_slice())
+}
+
+/// Runs `script` of every mod. The mods after a failing one still run, so all their errors are reported together.
+fn run_stage(lua: &Lua, mods: &[Mod], script: &str) -> Result<(), ModLoadErrors> {
+    let errors = mods.iter().filter_map(|mod_| mod_.run(lua, script).err()).collect();
+    ModLoadErrors::check(errors)
 }
 
-fn data_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
-    for mod_ in mods {
-        let chunk = fs::read_to_string(mod_.path.join("data.lua"))?;
-        lua.load(chunk).exec()?;
+/// Converts every prototype in `data[kind]`, along with its name, collecting the ones that fail in `errors`.
+fn raw_prototypes<T: FromLua>(
+    lua: &Lua,
+    data: &Table,
+    kind: &'static str,
+    errors: &mut Vec<ModLoadError>,
+) -> Vec<(String, T)> {
+    let invalid = |name: String, source: mlua::Error| ModLoadError::Prototype {
+        kind,
+        name,
+        source: source.into(),
+    };
+    let prototypes = match data.get::<Option<Table>>(kind) {
+        Ok(Some(prototypes)) => prototypes,
+        Ok(None) => return vec![],
+        Err(source) => {
+            errors.push(invalid(format!("data.{kind}"), source));
+            return vec![];
+        }
+    };
+
+    let mut raw_prototypes = vec![];
+    for pair in prototypes.pairs::<String, Value>() {
+        match pair {
+            Ok((name, value)) => match T::from_lua(value, lua) {
+                Ok(raw_prototype) => raw_prototypes.push((name, raw_prototype)),
+                Err(source) => errors.push(invalid(name, source)),
+            },
+            Err(source) => errors.push(invalid(format!("data.{kind}"), source)),
+        }
     }
-    Ok(())
+    raw_prototypes
 }
 
-fn data_updates_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
-    for mod_ in mods {
-        let chunk = fs::read_to_string(mod_.path.join("data_updates.lua"))?;
-        lua.load(chunk).exec()?;
+fn lua_setup(mut commands: Commands) {
+    let mods_path = Path::new(MODS_PATH);
+    let mods = detect_mods(mods_path).unwrap_or_else(|errors| panic!("{errors}"));
+    let (block_prototypes, recipe_prototypes, biome_prototypes) =
+        load_mod_prototypes(&mods).unwrap_or_else(|errors| panic!("{errors}"));
+    set_block_registry(&block_prototypes);
+    commands.insert_resource(block_prototypes);
+    commands.insert_resource(recipe_prototypes);
+    commands.insert_resource(biome_prototypes);
+    if let Some(shader_path) = chunk_shader_override(mods_path, &mods) {
+        commands.insert_resource(ChunkShaderPath(shader_path));
     }
-    Ok(())
 }
 
-fn data_final_fixes_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
-    for mod_ in mods {
-        let chunk = fs::read_to_string(mod_.path.join("data_final_fixes.lua"))?;
-        lua.load(chunk).exec()?;
+/// The asset path of the chunk shader one of `mods`, found in `mods_path`, declares with `shader` in its `info.toml`.
+/// The shader is relative to the mod folder, and `mods_path` has to sit directly in the assets folder.
+/// When several mods declare one the last mod wins.
+fn chunk_shader_override(mods_path: &Path, mods: &[Mod]) -> Option<String> {
+    let mut overrides = mods.iter().filter_map(|mod_| Some((mod_, mod_.shader.as_ref()?)));
+    let (mod_, shader) = overrides.next_back()?;
+    for (ignored, _) in overrides {
+        warn!("The chunk shader of mod \"{}\" is replaced by the one of mod \"{}\".", ignored.name, mod_.name);
     }
-    Ok(())
+    let asset_path = Path::new(mods_path.file_name()?).join(mod_.path.file_name()?).join(shader);
+    Some(asset_path.to_string_lossy().replace('\\', "/"))
 }
 
-fn lua_setup(mut commands: Commands) {
-    let mods = detect_mods();
+/// Runs every data stage of the mods in `assets/mods` and collects the resulting block prototypes.
+/// This does not touch the block registry. See `set_block_registry`.
+///
+/// # Panics
+/// If any mod fails to load.
+#[must_use]
+pub fn load_block_prototypes() -> BlockPrototypes {
+    load_prototypes().0
+}
+
+/// Runs every data stage of the mods in `assets/mods` and collects the resulting prototypes.
+/// This does not touch the block registry. See `set_block_registry`.
+///
+/// # Panics
+/// With every error of [`try_load_prototypes`], if any mod fails to load.
+#[must_use]
+pub fn load_prototypes() -> (BlockPrototypes, RecipePrototypes, BiomePrototypes) {
+    try_load_prototypes(Path::new(MODS_PATH)).unwrap_or_else(|errors| panic!("{errors}"))
+}
 
+/// Runs every data stage of the mods in `mods_path` and collects the resulting prototypes.
+/// This does not touch the block registry. See `set_block_registry`.
+///
+/// # Errors
+/// If a mod can't be read or fails a data stage, or a prototype is malformed or references an unknown block.
+/// Each stage reports the errors of every mod before giving up.
+pub fn try_load_prototypes(
+    mods_path: &Path,
+) -> Result<(BlockPrototypes, RecipePrototypes, BiomePrototypes), ModLoadErrors> {
+    load_mod_prototypes(&detect_mods(mods_path)?)
+}
+
+/// [`try_load_prototypes`] of mods which have already been detected.
+fn load_mod_prototypes(
+    mods: &[Mod],
+) -> Result<(BlockPrototypes, RecipePrototypes, BiomePrototypes), ModLoadErrors> {
     let lua = Lua::new();
     lua.enable_jit(true);
 
     //engine.set_module_resolver(FileModuleResolver::new_with_path("assets/mods"));
 
-    data_stage(&lua, &mods).expect("Failed to load data stage");
-    data_updates_stage(&lua, &mods).expect("Failed to load data updates stage");
-    data_final_fixes_stage(&lua, &mods).expect("Failed to load data final fixes stage");
+    run_stage(&lua, mods, "data.lua")?;
+    run_stage(&lua, mods, "data_updates.lua")?;
+    run_stage(&lua, mods, "data_final_fixes.lua")?;
 
     let globals = lua.globals();
-    let data = globals.get::<Table>("data").unwrap();
+    let data = globals.get::<Table>("data").map_err(ModLoadError::MissingDataTable)?;
+
+    let mut errors = vec![];
+    // Recipes and biomes are resolved after the blocks, since they may reference any block.
+    let raw_block_prototypes = raw_prototypes::<RawBlockPrototype>(&lua, &data, "block", &mut errors);
+    let raw_recipe_prototypes = raw_prototypes::<RawRecipePrototype>(&lua, &data, "recipe", &mut errors);
+    let raw_biome_prototypes = raw_prototypes::<RawBiomePrototype>(&lua, &data, "biome", &mut errors);
 
     let mut block_prototypes = BlockPrototypesBuilder::new();
+    for (name, raw_block_prototype) in raw_block_prototypes {
+        if let Err(source) = block_prototypes.add(raw_block_prototype) {
+            errors.push(ModLoadError::Prototype { kind: "block", name, source });
+        }
+    }
+    let block_prototypes = block_prototypes.build();
 
-    data.for_each(|k: String, v: Value| {
-        if k == "block" {
-            v.as_table().unwrap().for_each(|_: String, v: Value| {
-                block_prototypes.add(
-                    RawBlockPrototype::from_lua(v, &lua).expect("Could not parse block prototype"),
-                );
-                Ok(())
-            })?;
+    let mut recipe_prototypes = RecipePrototypesBuilder::new(&block_prototypes);
+    for (name, raw_recipe_prototype) in raw_recipe_prototypes {
+        if let Err(source) = recipe_prototypes.add(raw_recipe_prototype) {
+            errors.push(ModLoadError::Prototype { kind: "recipe", name, source });
         }
-        Ok(())
-    })
-    .expect("Found non-string key in data table.");
+    }
+    let recipe_prototypes = recipe_prototypes.build();
 
-    let block_prototypes = block_prototypes.build();
-    set_block_registry(&block_prototypes);
-    commands.insert_resource(block_prototypes);
+    let mut biome_prototypes = BiomePrototypesBuilder::new(&block_prototypes);
+    for (name, raw_biome_prototype) in raw_biome_prototypes {
+        if let Err(source) = biome_prototypes.add(raw_biome_prototype) {
+            errors.push(ModLoadError::Prototype { kind: "biome", name, source });
+        }
+    }
+    let biome_prototypes = biome_prototypes.build();
+
+    ModLoadErrors::check(errors)?;
+    Ok((block_prototypes, recipe_prototypes, biome_prototypes))
+}
+
+/// A mods directory in the temp directory holding one mod, `name`, with the given `info.toml` and no scripts.
+#[cfg(test)]
+fn test_mods_directory(name: &str, info_toml: &str) -> PathBuf {
+    let mods_path = std::env::temp_dir().join(format!("talc_mods_{name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&mods_path);
+    fs::create_dir_all(mods_path.join(name)).expect("The temp directory is writable.");
+    fs::write(mods_path.join(name).join("info.toml"), info_toml).expect("The temp directory is writable.");
+    mods_path
+}
+
+#[test]
+fn mod_without_data_lua_names_the_mod_and_file() {
+    let mods_path = test_mods_directory(
+        "no_data",
+        r#"
+            dependencies = {}
+
+            [mod]
+            name = "no_data"
+            version = "0.1.0"
+            talc_version = "0.1.0"
+            authors = []
+            description = ""
+            homepage = ""
+            repository = ""
+        "#,
+    );
+
+    let Err(ModLoadErrors(errors)) = try_load_prototypes(&mods_path) else {
+        panic!("The mod has no data.lua.");
+    };
+    let [ModLoadError::Io { mod_name, file, .. }] = errors.as_slice() else {
+        panic!("Expected one io error, got {errors:?}");
+    };
+    assert_eq!(mod_name, "no_data");
+    assert_eq!(file, &mods_path.join("no_data").join("data.lua"));
+}
+
+#[test]
+fn invalid_info_toml_names_the_mod_and_file() {
+    let mods_path = test_mods_directory("bad_toml", "[mod\nname = ");
+
+    let Err(errors) = try_load_prototypes(&mods_path) else {
+        panic!("The info.toml is not toml.");
+    };
+    let [ModLoadError::Toml { mod_name, file, .. }] = errors.0.as_slice() else {
+        panic!("Expected one toml error, got {errors:?}");
+    };
+    assert_eq!(mod_name, "bad_toml");
+    assert_eq!(file, &mods_path.join("bad_toml").join("info.toml"));
+
+    let message = errors.to_string();
+    assert!(message.starts_with("Failed to load mods.\n  mod \"bad_toml\": "), "{message}");
+}
+
+#[test]
+fn declared_shader_is_relative_to_the_mod_folder() {
+    let mods_path = test_mods_directory(
+        "shader",
+        r#"
+            dependencies = {}
+
+            [mod]
+            name = "shader"
+            version = "0.1.0"
+            talc_version = "0.1.0"
+            authors = []
+            description = ""
+            homepage = ""
+            repository = ""
+            shader = "shaders/chunk.wgsl"
+        "#,
+    );
+
+    let mods = detect_mods(&mods_path).expect("The info.toml is valid.");
+    let mods_folder = mods_path.file_name().expect("The mods directory has a name.").to_string_lossy();
+    assert_eq!(
+        chunk_shader_override(&mods_path, &mods),
+        Some(format!("{mods_folder}/shader/shaders/chunk.wgsl"))
+    );
+    assert_eq!(chunk_shader_override(&mods_path, &[]), None);
 }
@@ -29,17 +29,24 @@ pub(super) trait PrototypesBuilder {
     type BuiltFrom: RawPrototype;
     type Final: Prototypes;
     fn new() -> Self;
-    fn add(&mut self, prototype: Self::BuiltFrom);
+    /// # Errors
+    /// If the prototype can not be registered.
+    fn add(&mut self, prototype: Self::BuiltFrom) -> anyhow::Result<()>;
     fn build(self) -> Self::Final;
 }
 
 pub trait Prototypes {
     type T: Prototype;
     fn get(&self, name: &str) -> Option<&'static Self::T>;
+    /// In name order, the same on every run.
     fn iter(&self) -> Iter<'_, &'static str, &'static Self::T>;
 }
 
-#[derive(Resource, Clone)]
+/// How many block prototypes fit in the block registry, see `init_block_registry`.
+/// Block ids are `u16`, but only ids below this resolve to a block.
+pub const MAX_BLOCK_PROTOTYPES: usize = u8::MAX as usize;
+
+#[derive(Resource, Clone, Default)]
 pub struct BlockPrototypes(BTreeMap<&'static str, &'static BlockPrototype>);
 
 impl Prototypes for BlockPrototypes {
@@ -54,37 +61,70 @@ impl Prototypes for BlockPrototypes {
     }
 }
 
-pub(super) struct BlockPrototypesBuilder(usize, BTreeMap<&'static str, &'static BlockPrototype>);
+impl BlockPrototypes {
+    /// Every block in id order, the order chunk data and save files refer to them by.
+    #[must_use]
+    pub fn iter_by_id(&self) -> Vec<&'static BlockPrototype> {
+        let mut prototypes: Vec<_> = self.0.values().copied().collect();
+        prototypes.sort_unstable_by_key(|prototype| prototype.id);
+        prototypes
+    }
+}
+
+pub(super) struct BlockPrototypesBuilder {
+    next_id: usize,
+    next_texture_layer: u32,
+    prototypes: BTreeMap<&'static str, &'static BlockPrototype>,
+}
 
 impl PrototypesBuilder for BlockPrototypesBuilder {
     type BuiltFrom = RawBlockPrototype;
     type Final = BlockPrototypes;
 
     fn new() -> Self {
-        Self(0, BTreeMap::default())
+        Self {
+            next_id: 0,
+            next_texture_layer: 0,
+            prototypes: BTreeMap::default(),
+        }
     }
 
-    fn add(&mut self, prototype: Self::BuiltFrom) {
+    fn add(&mut self, prototype: Self::BuiltFrom) -> anyhow::Result<()> {
+        if self.next_id >= MAX_BLOCK_PROTOTYPES {
+            anyhow::bail!("Only {MAX_BLOCK_PROTOTYPES} block prototypes are allowed.");
+        }
+        // Each textured block gets its own layer in the block texture array.
+        let texture_layer = prototype.texture.as_ref().map(|_| {
+            self.next_texture_layer += 1;
+            self.next_texture_layer - 1
+        });
+
         let prototype = BlockPrototype {
-            id: u16::try_from(self.0).expect("Only 2^16 block prototypes are allowed."),
+            id: u16::try_from(self.next_id).expect("MAX_BLOCK_PROTOTYPES fits in a u16."),
             name: prototype.name,
             is_transparent: prototype.is_transparent,
             is_meshable: prototype.is_meshable,
+            is_fluid: prototype.is_fluid,
             color: prototype.color,
+            texture: prototype.texture,
+            texture_layer,
+            shape: prototype.shape,
+            emissive: prototype.emissive,
         };
 
         let name = prototype.name.clone();
         assert!(
-            self.1
+            self.prototypes
                 .insert(Box::leak(name.clone()), Box::leak(prototype.into()))
                 .is_none(),
             "Prototype {name} registered twice."
         );
-        self.0 += 1;
+        self.next_id += 1;
+        Ok(())
     }
 
     fn build(self) -> Self::Final {
-        BlockPrototypes(self.1)
+        BlockPrototypes(self.prototypes)
     }
 }
 
@@ -93,7 +133,11 @@ pub(super) struct RawBlockPrototype {
     name: Box<str>,
     is_transparent: bool,
     is_meshable: bool,
+    is_fluid: bool,
     color: Color,
+    texture: Option<Box<str>>,
+    shape: BlockShape,
+    emissive: f32,
 }
 
 impl RawPrototype for RawBlockPrototype {}
@@ -122,16 +166,51 @@ impl FromLua for RawBlockPrototype {
         let is_meshable = table
             .get::<bool>("is_meshable")
             .context("Could not parse BlockPrototype::is_meshable field.")?;
+        let is_fluid = table
+            .get::<Option<bool>>("is_fluid")
+            .context("Could not parse BlockPrototype::is_fluid field.")?
+            .unwrap_or(false);
         let color: Color = table
             .get::<LuaColor>("color")
             .context("Could not parse BlockPrototype::color field.")?
             .into();
+        let texture: Option<Box<str>> = table
+            .get::<Option<String>>("texture")
+            .context("Could not parse BlockPrototype::texture field.")?
+            .map(Into::into);
+        let shape = match table
+            .get::<Option<String>>("shape")
+            .context("Could not parse BlockPrototype::shape field.")?
+            .as_deref()
+        {
+            None | Some("full") => BlockShape::Full,
+            Some("bottom_slab") => BlockShape::BottomSlab,
+            Some("cross") => BlockShape::Cross,
+            Some(shape) => Err(error(format!(
+                "Unknown block shape \"{shape}\". Expected \"full\", \"bottom_slab\" or \"cross\"."
+            )))?,
+        };
+
+        let emissive = table
+            .get::<Option<f32>>("emissive")
+            .context("Could not parse BlockPrototype::emissive field.")?
+            .unwrap_or(0.);
+        if emissive < 0. {
+            Err(error(format!(
+                "Block emissive intensity must not be negative, got {emissive}."
+            )))?;
+        }
 
         Ok(Self {
             name,
-            is_transparent,
+            // crosses never hide the faces behind them, whatever the prototype says
+            is_transparent: is_transparent || shape == BlockShape::Cross,
             is_meshable,
+            is_fluid,
             color,
+            texture,
+            shape,
+            emissive,
         })
     }
 }
@@ -142,7 +221,67 @@ pub struct BlockPrototype {
     pub name: Box<str>,
     pub is_transparent: bool,
     pub is_meshable: bool,
+    /// Fluids are meshed like other blocks, but nothing collides with them, see [`BlockPrototype::is_solid`].
+    pub is_fluid: bool,
     pub color: Color,
+    /// Asset path of the texture drawn on every face of this block.
+    /// Blocks without a texture are drawn with their flat `color`.
+    pub texture: Option<Box<str>>,
+    /// Layer of this block's texture inside the block texture array.
+    pub texture_layer: Option<u32>,
+    pub shape: BlockShape,
+    /// HDR intensity this block glows with, 0 for blocks lit by the scene.
+    /// Above 1 the block blooms.
+    pub emissive: f32,
+}
+
+impl BlockPrototype {
+    /// True if this block hides every face touching it.
+    /// Translucent blocks, like glass and water, let the faces behind them show through.
+    #[inline]
+    #[must_use]
+    pub fn is_opaque_cube(&self) -> bool {
+        self.is_solid_cube() && self.color.alpha() >= 1.
+    }
+
+    /// True if this block fills its voxel and is not transparent, whether or not its color is translucent.
+    #[inline]
+    #[must_use]
+    pub fn is_solid_cube(&self) -> bool {
+        !self.is_transparent && self.shape == BlockShape::Full
+    }
+
+    /// True if this block collides, stops raycasts and occludes ambient light. Slabs count as a full voxel.
+    /// Fluids like water hide the faces of their own kind, but are not solid.
+    #[inline]
+    #[must_use]
+    pub fn is_solid(&self) -> bool {
+        !self.is_transparent && !self.is_fluid
+    }
+}
+
+/// The geometry of a block within its voxel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockShape {
+    #[default]
+    Full,
+    /// Fills the lower half of the voxel.
+    BottomSlab,
+    /// Two quads crossing diagonally through the voxel, for grass and flowers.
+    /// Drawn from both sides in the alpha tested `ChunkLayer::Cutout`, and never culled.
+    Cross,
+}
+
+impl BlockShape {
+    /// shape data is packed in the shader
+    #[must_use]
+    pub const fn shape_index(self) -> u32 {
+        match self {
+            Self::Full => 0,
+            Self::BottomSlab => 1,
+            Self::Cross => 2,
+        }
+    }
 }
 
 impl PartialEq for BlockPrototype {
@@ -152,3 +291,577 @@ impl PartialEq for BlockPrototype {
 }
 
 impl Prototype for BlockPrototype {}
+
+#[derive(Resource, Clone, Default)]
+pub struct RecipePrototypes(BTreeMap<&'static str, &'static RecipePrototype>);
+
+impl Prototypes for RecipePrototypes {
+    type T = RecipePrototype;
+
+    fn get(&self, name: &str) -> Option<&'static RecipePrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+/// Recipes link to the blocks they consume and produce, so they are built after every block is registered.
+/// Unlike blocks this builder can fail, because a mod may reference a block that does not exist.
+pub(super) struct RecipePrototypesBuilder<'a> {
+    block_prototypes: &'a BlockPrototypes,
+    prototypes: BTreeMap<&'static str, &'static RecipePrototype>,
+}
+
+impl<'a> RecipePrototypesBuilder<'a> {
+    pub(super) fn new(block_prototypes: &'a BlockPrototypes) -> Self {
+        Self {
+            block_prototypes,
+            prototypes: BTreeMap::default(),
+        }
+    }
+
+    pub(super) fn add(&mut self, prototype: RawRecipePrototype) -> anyhow::Result<()> {
+        let resolve = |items: Vec<RawRecipeItem>, role: &str| {
+            items
+                .into_iter()
+                .map(|item| {
+                    let block = self.block_prototypes.get(&item.name).with_context(|| {
+                        format!(
+                            "Recipe \"{}\" has {role} \"{}\", which is not a registered block or item.",
+                            prototype.name, item.name
+                        )
+                    })?;
+                    Ok((block, item.amount))
+                })
+                .collect::<anyhow::Result<Box<[_]>>>()
+        };
+
+        let ingredients = resolve(prototype.ingredients, "ingredient")?;
+        let results = resolve(prototype.results, "result")?;
+        let prototype = RecipePrototype {
+            name: prototype.name,
+            ingredients,
+            results,
+            time: prototype.time,
+        };
+
+        let name = prototype.name.clone();
+        if self.prototypes.contains_key(&*name) {
+            anyhow::bail!("Recipe \"{name}\" registered twice.");
+        }
+        self.prototypes
+            .insert(Box::leak(name), Box::leak(prototype.into()));
+        Ok(())
+    }
+
+    pub(super) fn build(self) -> RecipePrototypes {
+        RecipePrototypes(self.prototypes)
+    }
+}
+
+/// A block consumed or produced by a recipe.
+#[derive(Clone)]
+pub(super) struct RawRecipeItem {
+    name: Box<str>,
+    amount: u32,
+}
+
+impl FromLua for RawRecipeItem {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let Some(table) = value.as_table() else {
+            Err(mlua::Error::ToLuaConversionError {
+                message: Some("Recipe ingredients and results are expected to be a table.".to_string()),
+                to: "Rust Recipe Item",
+                from: "Lua Recipe Item".to_string(),
+            })?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse recipe item name field.")?
+            .into();
+        let amount = table
+            .get::<Option<u32>>("amount")
+            .context("Could not parse recipe item amount field.")?
+            .unwrap_or(1);
+
+        Ok(Self { name, amount })
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct RawRecipePrototype {
+    name: Box<str>,
+    ingredients: Vec<RawRecipeItem>,
+    results: Vec<RawRecipeItem>,
+    time: Option<f32>,
+}
+
+impl RawPrototype for RawRecipePrototype {}
+
+impl FromLua for RawRecipePrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let Some(table) = value.as_table() else {
+            Err(mlua::Error::ToLuaConversionError {
+                message: Some("Recipe prototypes are expected to be a table.".to_string()),
+                to: "Rust Recipe Prototype",
+                from: "Lua Recipe Prototype".to_string(),
+            })?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse RecipePrototype::name field.")?
+            .into();
+        let ingredients = table
+            .get::<Vec<RawRecipeItem>>("ingredients")
+            .context("Could not parse RecipePrototype::ingredients field.")?;
+        let results = table
+            .get::<Vec<RawRecipeItem>>("results")
+            .context("Could not parse RecipePrototype::results field.")?;
+        let time = table
+            .get::<Option<f32>>("time")
+            .context("Could not parse RecipePrototype::time field.")?;
+
+        Ok(Self {
+            name,
+            ingredients,
+            results,
+            time,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RecipePrototype {
+    pub name: Box<str>,
+    pub ingredients: Box<[(&'static BlockPrototype, u32)]>,
+    pub results: Box<[(&'static BlockPrototype, u32)]>,
+    /// Seconds needed to craft this recipe. `None` crafts instantly.
+    pub time: Option<f32>,
+}
+
+impl PartialEq for RecipePrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for RecipePrototype {}
+
+#[derive(Resource, Clone, Default)]
+pub struct BiomePrototypes(BTreeMap<&'static str, &'static BiomePrototype>);
+
+impl Prototypes for BiomePrototypes {
+    type T = BiomePrototype;
+
+    fn get(&self, name: &str) -> Option<&'static BiomePrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+impl BiomePrototypes {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The block every biome has at every height from `bottom` to `top`, `None` if they differ or there are no biomes.
+    #[must_use]
+    pub fn uniform_block(&self, bottom: i32, top: i32) -> Option<&'static BlockPrototype> {
+        let mut blocks = self.0.values().map(|biome| biome.uniform_block(bottom, top));
+        let first = blocks.next()??;
+        blocks.all(|block| block == Some(first)).then_some(first)
+    }
+}
+
+/// Biomes link to the blocks they are made of, so like recipes they are built after every block is registered.
+pub(super) struct BiomePrototypesBuilder<'a> {
+    block_prototypes: &'a BlockPrototypes,
+    prototypes: BTreeMap<&'static str, &'static BiomePrototype>,
+}
+
+impl<'a> BiomePrototypesBuilder<'a> {
+    pub(super) fn new(block_prototypes: &'a BlockPrototypes) -> Self {
+        Self {
+            block_prototypes,
+            prototypes: BTreeMap::default(),
+        }
+    }
+
+    pub(super) fn add(&mut self, prototype: RawBiomePrototype) -> anyhow::Result<()> {
+        if prototype.layers.is_empty() {
+            anyhow::bail!("Biome \"{}\" has no layers.", prototype.name);
+        }
+        let layers = prototype
+            .layers
+            .into_iter()
+            .map(|layer| {
+                let block = self.block_prototypes.get(&layer.name).with_context(|| {
+                    format!(
+                        "Biome \"{}\" has a layer of \"{}\", which is not a registered block.",
+                        prototype.name, layer.name
+                    )
+                })?;
+                Ok((layer.max_y, block))
+            })
+            .collect::<anyhow::Result<Box<[_]>>>()?;
+        let prototype = BiomePrototype {
+            name: prototype.name,
+            layers,
+        };
+
+        let name = prototype.name.clone();
+        if self.prototypes.contains_key(&*name) {
+            anyhow::bail!("Biome \"{name}\" registered twice.");
+        }
+        self.prototypes
+            .insert(Box::leak(name), Box::leak(prototype.into()));
+        Ok(())
+    }
+
+    pub(super) fn build(self) -> BiomePrototypes {
+        BiomePrototypes(self.prototypes)
+    }
+}
+
+/// A band of blocks in a biome, up to and including the world height `max_y`.
+#[derive(Clone)]
+pub(super) struct RawBiomeLayer {
+    name: Box<str>,
+    max_y: Option<i32>,
+}
+
+impl FromLua for RawBiomeLayer {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let Some(table) = value.as_table() else {
+            Err(mlua::Error::ToLuaConversionError {
+                message: Some("Biome layers are expected to be a table.".to_string()),
+                to: "Rust Biome Layer",
+                from: "Lua Biome Layer".to_string(),
+            })?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse biome layer name field.")?
+            .into();
+        let max_y = table
+            .get::<Option<i32>>("max_y")
+            .context("Could not parse biome layer max_y field.")?;
+
+        Ok(Self { name, max_y })
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct RawBiomePrototype {
+    name: Box<str>,
+    layers: Vec<RawBiomeLayer>,
+}
+
+impl RawPrototype for RawBiomePrototype {}
+
+impl FromLua for RawBiomePrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let Some(table) = value.as_table() else {
+            Err(mlua::Error::ToLuaConversionError {
+                message: Some("Biome prototypes are expected to be a table.".to_string()),
+                to: "Rust Biome Prototype",
+                from: "Lua Biome Prototype".to_string(),
+            })?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse BiomePrototype::name field.")?
+            .into();
+        let layers = table
+            .get::<Vec<RawBiomeLayer>>("layers")
+            .context("Could not parse BiomePrototype::layers field.")?;
+
+        Ok(Self { name, layers })
+    }
+}
+
+/// A kind of terrain, picked per column by the noise terrain.
+#[derive(Debug)]
+pub struct BiomePrototype {
+    pub name: Box<str>,
+    /// The solid blocks from the bottom up, each up to and including its world height.
+    /// The last layer also covers everything above, whatever its height.
+    pub layers: Box<[(Option<i32>, &'static BlockPrototype)]>,
+}
+
+impl BiomePrototype {
+    /// The solid block of this biome at world height `y`.
+    #[must_use]
+    pub fn block_at(&self, y: i32) -> &'static BlockPrototype {
+        let &(_, block) = self
+            .layers
+            .iter()
+            .find(|(max_y, _)| max_y.is_none_or(|max_y| y <= max_y))
+            .or_else(|| self.layers.last())
+            .expect("Biomes have at least one layer.");
+        block
+    }
+
+    /// The block of this biome at every height from `bottom` to `top`, `None` if its layers change in between.
+    #[must_use]
+    pub fn uniform_block(&self, bottom: i32, top: i32) -> Option<&'static BlockPrototype> {
+        let block = self.block_at(bottom);
+        // the block only changes right above the top of a layer
+        self.layers
+            .iter()
+            .filter_map(|&(max_y, _)| max_y)
+            .filter(|max_y| (bottom..top).contains(max_y))
+            .all(|max_y| self.block_at(max_y + 1) == block)
+            .then_some(block)
+    }
+}
+
+impl PartialEq for BiomePrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for BiomePrototype {}
+
+/// A leaked block prototype for the test fixtures below. Non solid and cross shaped blocks are transparent.
+/// `water` is the only fluid.
+#[cfg(test)]
+fn test_block(
+    id: u16,
+    name: &'static str,
+    is_solid: bool,
+    shape: BlockShape,
+    color: Color,
+) -> (&'static str, &'static BlockPrototype) {
+    let block = Box::leak(Box::new(BlockPrototype {
+        id,
+        name: name.into(),
+        // faces behind crosses stay visible
+        is_transparent: !is_solid || shape == BlockShape::Cross,
+        is_meshable: is_solid,
+        is_fluid: name == "water",
+        color,
+        texture: None,
+        texture_layer: None,
+        shape,
+        emissive: 0.,
+    }));
+    (name, block)
+}
+
+/// `air` (id 0), `stone` (id 1) and `slab` (id 2), for tests which do not run the mod loader.
+#[cfg(test)]
+pub(crate) fn test_block_prototypes() -> &'static BlockPrototypes {
+    static TEST_BLOCK_PROTOTYPES: std::sync::OnceLock<BlockPrototypes> = std::sync::OnceLock::new();
+
+    TEST_BLOCK_PROTOTYPES.get_or_init(|| {
+        BlockPrototypes(BTreeMap::from([
+            test_block(0, "air", false, BlockShape::Full, Color::WHITE),
+            test_block(1, "stone", true, BlockShape::Full, Color::WHITE),
+            test_block(2, "slab", true, BlockShape::BottomSlab, Color::WHITE),
+        ]))
+    })
+}
+
+/// [`test_block_prototypes`] along with the blocks single tests need: the translucent `tinted_glass` (id 3),
+/// `dirt` (id 4), the cross shaped `tall_grass` (id 5) and the translucent `water` (id 6).
+/// Kept apart, so tests listing every block don't change with each new fixture.
+#[cfg(test)]
+pub(crate) fn test_fixture_block_prototypes() -> &'static BlockPrototypes {
+    static TEST_FIXTURE_BLOCK_PROTOTYPES: std::sync::OnceLock<BlockPrototypes> = std::sync::OnceLock::new();
+
+    TEST_FIXTURE_BLOCK_PROTOTYPES.get_or_init(|| {
+        let mut blocks = test_block_prototypes().0.clone();
+        blocks.extend([
+            test_block(3, "tinted_glass", true, BlockShape::Full, Color::srgba(0.4, 0.6, 0.8, 0.5)),
+            test_block(4, "dirt", true, BlockShape::Full, Color::srgb(0.5, 0.35, 0.2)),
+            test_block(5, "tall_grass", true, BlockShape::Cross, Color::srgb(0.3, 0.7, 0.2)),
+            test_block(6, "water", true, BlockShape::Full, Color::srgba(0.2, 0.4, 0.8, 0.6)),
+        ]);
+        BlockPrototypes(blocks)
+    })
+}
+
+/// `plains`, dirt up to y 15 and stone above, and `rocky`, all stone. Made of [`test_fixture_block_prototypes`].
+#[cfg(test)]
+pub(crate) fn test_biome_prototypes() -> &'static BiomePrototypes {
+    static TEST_BIOME_PROTOTYPES: std::sync::OnceLock<BiomePrototypes> = std::sync::OnceLock::new();
+
+    TEST_BIOME_PROTOTYPES.get_or_init(|| {
+        let block = |name| {
+            test_fixture_block_prototypes()
+                .get(name)
+                .expect("Test blocks include dirt and stone.")
+        };
+        let biome = |name: &'static str, layers: &[(Option<i32>, &'static BlockPrototype)]| {
+            let biome: &'static BiomePrototype = Box::leak(Box::new(BiomePrototype {
+                name: name.into(),
+                layers: layers.into(),
+            }));
+            (name, biome)
+        };
+        BiomePrototypes(BTreeMap::from([
+            biome("plains", &[(Some(15), block("dirt")), (None, block("stone"))]),
+            biome("rocky", &[(None, block("stone"))]),
+        ]))
+    })
+}
+
+#[test]
+fn recipe_with_missing_ingredient() {
+    let lua = mlua::Lua::new();
+    let recipe = lua
+        .load(
+            r#"{
+                type = "recipe",
+                name = "compress",
+                ingredients = {{ name = "stone", amount = 4 }, { name = "copper" }},
+                results = {{ name = "stone" }},
+            }"#,
+        )
+        .eval::<mlua::Value>()
+        .expect("Test recipe is valid lua.");
+    let recipe = RawRecipePrototype::from_lua(recipe, &lua).expect("Test recipe is well formed.");
+
+    let mut recipe_prototypes = RecipePrototypesBuilder::new(test_block_prototypes());
+    let error = recipe_prototypes
+        .add(recipe)
+        .expect_err("copper is not a block.");
+    assert_eq!(
+        error.to_string(),
+        r#"Recipe "compress" has ingredient "copper", which is not a registered block or item."#
+    );
+}
+
+#[test]
+fn cross_blocks_are_transparent() {
+    let lua = mlua::Lua::new();
+    let block = lua
+        .load(
+            r#"{
+                type = "block",
+                name = "fern",
+                is_transparent = false,
+                is_meshable = true,
+                color = { r = 0.2, g = 0.6, b = 0.2 },
+                shape = "cross",
+            }"#,
+        )
+        .eval::<mlua::Value>()
+        .expect("Test block is valid lua.");
+    let block = RawBlockPrototype::from_lua(block, &lua).expect("Test block is well formed.");
+    assert_eq!(block.shape, BlockShape::Cross);
+    assert!(block.is_transparent);
+}
+
+#[test]
+fn biome_with_missing_block() {
+    let lua = mlua::Lua::new();
+    let biome = lua
+        .load(
+            r#"{
+                type = "biome",
+                name = "desert",
+                layers = {{ name = "stone", max_y = 100 }, { name = "sand" }},
+            }"#,
+        )
+        .eval::<mlua::Value>()
+        .expect("Test biome is valid lua.");
+    let biome = RawBiomePrototype::from_lua(biome, &lua).expect("Test biome is well formed.");
+
+    let mut biome_prototypes = BiomePrototypesBuilder::new(test_block_prototypes());
+    let error = biome_prototypes
+        .add(biome)
+        .expect_err("sand is not a block.");
+    assert_eq!(
+        error.to_string(),
+        r#"Biome "desert" has a layer of "sand", which is not a registered block."#
+    );
+}
+
+#[test]
+fn biome_layers_cover_every_height() {
+    let plains = test_biome_prototypes().get("plains").expect("Test biomes include plains.");
+    assert_eq!(&*plains.block_at(-1000).name, "dirt");
+    assert_eq!(&*plains.block_at(15).name, "dirt");
+    assert_eq!(&*plains.block_at(16).name, "stone");
+    assert_eq!(&*plains.block_at(1000).name, "stone");
+}
+
+#[test]
+fn uniform_blocks_span_whole_layers() {
+    let biomes = test_biome_prototypes();
+    let plains = biomes.get("plains").expect("Test biomes include plains.");
+    let name = |block: Option<&'static BlockPrototype>| block.map(|block| &*block.name);
+
+    assert_eq!(name(plains.uniform_block(-32, -1)), Some("dirt"));
+    assert_eq!(name(plains.uniform_block(0, 15)), Some("dirt"));
+    assert_eq!(name(plains.uniform_block(0, 31)), None);
+    assert_eq!(name(plains.uniform_block(16, 47)), Some("stone"));
+
+    // rocky is all stone, so the biomes only agree above the plains' dirt
+    assert_eq!(name(biomes.uniform_block(0, 15)), None);
+    assert_eq!(name(biomes.uniform_block(32, 63)), Some("stone"));
+    assert_eq!(name(BiomePrototypes::default().uniform_block(32, 63)), None);
+}
+
+#[test]
+fn block_iteration_order_is_stable() {
+    let block_prototypes = test_block_prototypes();
+    let by_name: Vec<_> = block_prototypes.iter().map(|(name, _)| *name).collect();
+    assert_eq!(by_name, block_prototypes.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+    assert!(by_name.is_sorted());
+
+    let by_id: Vec<u16> = block_prototypes.iter_by_id().iter().map(|prototype| prototype.id).collect();
+    assert_eq!(by_id, [0, 1, 2]);
+    assert_eq!(
+        by_id,
+        block_prototypes.iter_by_id().iter().map(|prototype| prototype.id).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn block_prototypes_past_the_registry_are_rejected() {
+    let block = |name: String| RawBlockPrototype {
+        name: name.into(),
+        is_transparent: false,
+        is_meshable: true,
+        is_fluid: false,
+        color: Color::WHITE,
+        texture: None,
+        shape: BlockShape::Full,
+        emissive: 0.,
+    };
+
+    let mut block_prototypes = BlockPrototypesBuilder::new();
+    for id in 0..MAX_BLOCK_PROTOTYPES {
+        block_prototypes
+            .add(block(format!("block_{id}")))
+            .expect("Ids below MAX_BLOCK_PROTOTYPES fit in the registry.");
+    }
+    let error = block_prototypes
+        .add(block("one_too_many".to_string()))
+        .expect_err("The registry is full.");
+    assert_eq!(error.to_string(), "Only 255 block prototypes are allowed.");
+
+    let block_prototypes = block_prototypes.build();
+    assert!(block_prototypes.get("one_too_many").is_none());
+    let last = block_prototypes.iter_by_id().last().map(|block| usize::from(block.id));
+    assert_eq!(last, Some(MAX_BLOCK_PROTOTYPES - 1));
+}
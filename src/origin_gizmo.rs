@@ -0,0 +1,82 @@
+//! Debug gizmos marking the world origin, for checking chunk and coordinate conversions by eye.
+//!
+//! F4 toggles X/Y/Z axis arrows at the origin and a faint grid on the y = 0 plane with a line on every chunk boundary.
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{color::palettes::basic::{BLUE, LIME, RED}, prelude::*};
+
+use crate::chunky::chunk::CHUNK_SIZE_F32;
+use crate::player::key_bindings::{Action, KeyBindings};
+
+/// Chunks the grid reaches out from the origin, along x and z.
+pub const GRID_CHUNK_RADIUS: u32 = 16;
+/// Length of the axis arrows, in blocks. One chunk, so they end on the first grid line.
+pub const AXIS_LENGTH: f32 = CHUNK_SIZE_F32;
+pub const GRID_COLOR: Color = Color::srgba(1., 1., 1., 0.15);
+
+/// Whether the origin gizmos are drawn. Off by default.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct OriginGizmo(pub bool);
+
+pub struct OriginGizmoPlugin;
+
+impl Plugin for OriginGizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OriginGizmo>()
+            .init_resource::<KeyBindings>()
+            .add_systems(Update, origin_gizmo_toggle_keybind)
+            .add_systems(
+                Update,
+                draw_origin_gizmo
+                    .after(origin_gizmo_toggle_keybind)
+                    .run_if(|origin_gizmo: Res<OriginGizmo>| origin_gizmo.0),
+            );
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn origin_gizmo_toggle_keybind(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut origin_gizmo: ResMut<OriginGizmo>,
+) {
+    if key_bindings.just_pressed(&keys, Action::ToggleOriginGizmo) {
+        origin_gizmo.0 = !origin_gizmo.0;
+    }
+}
+
+fn draw_origin_gizmo(mut gizmos: Gizmos) {
+    gizmos.arrow(Vec3::ZERO, Vec3::X * AXIS_LENGTH, RED);
+    gizmos.arrow(Vec3::ZERO, Vec3::Y * AXIS_LENGTH, LIME);
+    gizmos.arrow(Vec3::ZERO, Vec3::Z * AXIS_LENGTH, BLUE);
+
+    // the grid is centered on the origin, an even cell count puts a line on x = 0 and z = 0.
+    gizmos.grid(
+        Isometry3d::from_rotation(Quat::from_rotation_x(FRAC_PI_2)),
+        UVec2::splat(GRID_CHUNK_RADIUS * 2),
+        Vec2::splat(CHUNK_SIZE_F32),
+        GRID_COLOR,
+    );
+}
+
+#[test]
+fn f4_toggles_the_origin_gizmo() {
+    let mut app = App::new();
+    app.init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<KeyBindings>()
+        .init_resource::<OriginGizmo>()
+        .add_systems(Update, origin_gizmo_toggle_keybind);
+
+    let mut press_f4 = || {
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(KeyCode::F4);
+        keys.clear();
+        keys.press(KeyCode::F4);
+        app.update();
+        app.world().resource::<OriginGizmo>().0
+    };
+    assert!(press_f4());
+    assert!(!press_f4());
+    assert!(press_f4());
+}
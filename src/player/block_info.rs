@@ -0,0 +1,167 @@
+//! Block info overlay for modders.
+//! Shows the prototype of the block the camera points at, along with its world and chunk position.
+//! Hidden together with the FPS counter by [`Action::ToggleDebug`].
+
+use bevy::prelude::*;
+
+use crate::chunky::async_chunkloader::Chunks;
+use crate::chunky::collision::VoxelHit;
+
+use super::debug_camera::FlyCam;
+use super::key_bindings::{Action, KeyBindings};
+
+pub const BLOCK_INFO_FONT_SIZE: f32 = 18.;
+pub const BLOCK_INFO_FONT_COLOR: Color = Color::WHITE;
+/// How far the camera looks for a block, in blocks.
+pub const BLOCK_INFO_REACH: f32 = 64.;
+pub const STRING_NO_TARGET: &str = "No block targeted.";
+
+/// Whether the overlay is shown.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockInfoEnabled(pub bool);
+
+impl Default for BlockInfoEnabled {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// The marker on the text of the overlay.
+#[derive(Component)]
+pub struct BlockInfoText;
+
+pub struct BlockInfoPlugin;
+
+impl Plugin for BlockInfoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlockInfoEnabled>()
+            .init_resource::<KeyBindings>()
+            .add_systems(Startup, spawn_block_info)
+            .add_systems(Update, (block_info_toggle_keybind, update_block_info).chain());
+    }
+}
+
+fn spawn_block_info(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Block Info"),
+        Text::new(STRING_NO_TARGET),
+        TextFont {
+            font_size: BLOCK_INFO_FONT_SIZE,
+            ..default()
+        },
+        TextColor(BLOCK_INFO_FONT_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.),
+            bottom: Val::Px(8.),
+            ..default()
+        },
+        BlockInfoText,
+    ));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn block_info_toggle_keybind(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut enabled: ResMut<BlockInfoEnabled>,
+) {
+    if key_bindings.just_pressed(&keys, Action::ToggleDebug) {
+        enabled.0 = !enabled.0;
+    }
+}
+
+/// Casts a ray from the camera through every meshable block, so glass and water show up too.
+#[allow(clippy::needless_pass_by_value)]
+fn update_block_info(
+    enabled: Res<BlockInfoEnabled>,
+    chunks: Res<Chunks>,
+    cameras: Query<&Transform, With<FlyCam>>,
+    mut texts: Query<&mut Text, With<BlockInfoText>>,
+) {
+    let info = if enabled.0 {
+        let hit = cameras.iter().next().and_then(|camera| {
+            chunks.raycast_blocks(camera.translation, camera.forward().into(), BLOCK_INFO_REACH, |block| {
+                block.is_meshable
+            })
+        });
+        block_info(hit.as_ref())
+    } else {
+        String::new()
+    };
+    for mut text in &mut texts {
+        if text.0 != info {
+            text.0.clone_from(&info);
+        }
+    }
+}
+
+/// The overlay text for the block `hit` by the camera ray.
+#[must_use]
+pub fn block_info(hit: Option<&VoxelHit>) -> String {
+    let Some(hit) = hit else {
+        return STRING_NO_TARGET.to_string();
+    };
+    let block = hit.block;
+    let (chunk_position, _) = hit.position.to_chunk_local();
+    format!(
+        "block: {} (id {})\ntransparent: {}\nmeshable: {}\ncolor: {}\nposition: {}\nchunk: {}",
+        block.name,
+        block.id,
+        block.is_transparent,
+        block.is_meshable,
+        block.color.to_srgba().to_hex(),
+        hit.position.0,
+        chunk_position.0,
+    )
+}
+
+#[test]
+fn block_info_names_the_looked_at_block() {
+    use std::sync::Arc;
+
+    use crate::{
+        chunky::chunk::{ChunkData, init_test_block_registry},
+        mod_manager::prototypes::Prototypes,
+        position::ChunkPosition,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test blocks include air.");
+    let tinted_glass = block_prototypes.get("tinted_glass").expect("Test blocks include tinted glass.");
+    // a single translucent block, which is not transparent, in the chunk below the origin
+    let chunk_position = ChunkPosition::new(0, -1, 0);
+    let chunk_data = ChunkData::from_fn(chunk_position, |position| {
+        if *position == IVec3::new(3, 7, 9) { tinted_glass } else { air }
+    });
+    let mut chunks = Chunks::default();
+    chunks.0.insert(chunk_position, Arc::new(chunk_data));
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_resource(chunks)
+        .add_plugins(BlockInfoPlugin);
+    let text = app.world_mut().spawn((Text::default(), BlockInfoText)).id();
+    let camera = app
+        .world_mut()
+        .spawn((FlyCam, Transform::from_xyz(3.5, -10., 9.5).looking_to(Dir3::NEG_Y, Dir3::Z)))
+        .id();
+    let text_of = |app: &mut App| {
+        app.update();
+        app.world().get::<Text>(text).expect("The overlay has text.").0.clone()
+    };
+
+    let info = text_of(&mut app);
+    assert!(info.starts_with("block: tinted_glass (id 3)\ntransparent: false\nmeshable: true"), "{info}");
+    assert!(info.ends_with("position: [3, -25, 9]\nchunk: [0, -1, 0]"), "{info}");
+
+    // looking up, at nothing
+    app.world_mut()
+        .entity_mut(camera)
+        .insert(Transform::from_xyz(3.5, -10., 9.5).looking_to(Dir3::Y, Dir3::Z));
+    assert_eq!(text_of(&mut app), STRING_NO_TARGET);
+
+    app.world_mut().resource_mut::<BlockInfoEnabled>().0 = false;
+    assert_eq!(text_of(&mut app), "");
+}
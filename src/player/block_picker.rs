@@ -0,0 +1,223 @@
+//! Creative block picker overlay.
+//! Lists every placeable block prototype and stores the selected one in [`HeldBlock`].
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::mod_manager::prototypes::{BlockPrototype, BlockPrototypes, Prototypes};
+
+pub const PICKER_FONT_SIZE: f32 = 18.;
+pub const PICKER_SWATCH_SIZE: f32 = 16.;
+pub const PICKER_FONT_COLOR: Color = Color::WHITE;
+pub const PICKER_SELECTED_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+pub const STRING_NO_BLOCKS: &str = "No placeable blocks registered.";
+
+const NUMBER_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// The block the player currently has selected for placement.
+/// `None` if no placeable blocks are registered.
+#[derive(Resource, Default)]
+pub struct HeldBlock(pub Option<&'static BlockPrototype>);
+
+/// Every block shown in the picker, in display order.
+#[derive(Resource, Default)]
+struct PickerEntries {
+    blocks: Vec<&'static BlockPrototype>,
+    selected: usize,
+}
+
+/// Marker on the text of a picker row. Holds the row index into `PickerEntries::blocks`.
+#[derive(Component)]
+struct BlockPickerEntry(usize);
+
+pub struct BlockPickerPlugin;
+
+impl Plugin for BlockPickerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HeldBlock>()
+            .init_resource::<PickerEntries>()
+            .add_systems(
+                Update,
+                spawn_block_picker.run_if(resource_added::<BlockPrototypes>),
+            )
+            .add_systems(Update, (select_held_block, highlight_held_block).chain());
+    }
+}
+
+/// Builds the picker UI once the mod loader has registered all block prototypes.
+#[allow(clippy::needless_pass_by_value)]
+fn spawn_block_picker(
+    mut commands: Commands,
+    block_prototypes: Res<BlockPrototypes>,
+    mut entries: ResMut<PickerEntries>,
+    mut held_block: ResMut<HeldBlock>,
+) {
+    // Blocks which cannot be meshed (such as air) are not placeable.
+    entries.blocks = block_prototypes
+        .iter()
+        .map(|(_, &block)| block)
+        .filter(|block| block.is_meshable)
+        .collect();
+    entries.selected = 0;
+    held_block.0 = entries.blocks.first().copied();
+
+    commands
+        .spawn((
+            Name::new("Block Picker"),
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(8.),
+                top: Val::Px(8.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            if entries.blocks.is_empty() {
+                parent.spawn((
+                    Text::new(STRING_NO_BLOCKS),
+                    TextFont {
+                        font_size: PICKER_FONT_SIZE,
+                        ..default()
+                    },
+                    TextColor(PICKER_FONT_COLOR),
+                ));
+                return;
+            }
+
+            for (i, block) in entries.blocks.iter().enumerate() {
+                parent
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(6.),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Node {
+                                width: Val::Px(PICKER_SWATCH_SIZE),
+                                height: Val::Px(PICKER_SWATCH_SIZE),
+                                ..default()
+                            },
+                            BackgroundColor(block.color),
+                        ));
+                        let label = if i < NUMBER_KEYS.len() {
+                            format!("{} {}", i + 1, block.name)
+                        } else {
+                            block.name.to_string()
+                        };
+                        row.spawn((
+                            Text::new(label),
+                            TextFont {
+                                font_size: PICKER_FONT_SIZE,
+                                ..default()
+                            },
+                            TextColor(PICKER_FONT_COLOR),
+                            BlockPickerEntry(i),
+                        ));
+                    });
+            }
+        });
+}
+
+/// Number keys select a block directly, the scroll wheel cycles through them.
+#[allow(clippy::needless_pass_by_value)]
+fn select_held_block(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut scroll: EventReader<MouseWheel>,
+    mut entries: ResMut<PickerEntries>,
+    mut held_block: ResMut<HeldBlock>,
+) {
+    let count = entries.blocks.len();
+    if count == 0 {
+        scroll.clear();
+        return;
+    }
+
+    let mut selected = entries.selected;
+    for (i, key) in NUMBER_KEYS.iter().enumerate() {
+        if i < count && keys.just_pressed(*key) {
+            selected = i;
+        }
+    }
+    for ev in scroll.read() {
+        if ev.y > 0. {
+            selected = (selected + count - 1) % count;
+        } else if ev.y < 0. {
+            selected = (selected + 1) % count;
+        }
+    }
+
+    if selected != entries.selected || held_block.0.is_none() {
+        entries.selected = selected;
+        held_block.0 = Some(entries.blocks[selected]);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn highlight_held_block(
+    entries: Res<PickerEntries>,
+    mut rows: Query<(&BlockPickerEntry, &mut TextColor)>,
+) {
+    if !entries.is_changed() {
+        return;
+    }
+    for (entry, mut color) in &mut rows {
+        color.0 = if entry.0 == entries.selected {
+            PICKER_SELECTED_COLOR
+        } else {
+            PICKER_FONT_COLOR
+        };
+    }
+}
+
+#[test]
+fn scroll_wraps_around_the_picker() {
+    use bevy::input::mouse::MouseScrollUnit;
+
+    let mut app = App::new();
+    app.add_plugins(BlockPickerPlugin)
+        .add_event::<MouseWheel>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_resource(crate::mod_manager::prototypes::test_block_prototypes().clone());
+    app.update();
+
+    let held_block_name = |app: &App| {
+        app.world()
+            .resource::<HeldBlock>()
+            .0
+            .expect("Test blocks include placeable blocks.")
+            .name
+            .to_string()
+    };
+    // air is not placeable, the rest are listed by name
+    assert_eq!(held_block_name(&app), "slab");
+
+    let scroll = |app: &mut App, y| {
+        app.world_mut().send_event(MouseWheel {
+            unit: MouseScrollUnit::Line,
+            x: 0.,
+            y,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    };
+    scroll(&mut app, 1.);
+    assert_eq!(held_block_name(&app), "stone");
+    scroll(&mut app, -1.);
+    assert_eq!(held_block_name(&app), "slab");
+    scroll(&mut app, -1.);
+    assert_eq!(held_block_name(&app), "stone");
+}
@@ -0,0 +1,221 @@
+//! Field of view, clip planes and mouse sensitivity of the player camera, saved between runs.
+//! `[` and `]` narrow and widen the field of view.
+
+use std::{
+    fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::debug_camera::FlyCam;
+use super::key_bindings::{Action, KeyBindings};
+
+/// Vertical field of view limits, in degrees. Past these the view is a keyhole or a fisheye.
+pub const FOV_RANGE: RangeInclusive<f32> = 30.0..=110.0;
+/// How much `[` and `]` change the field of view, in degrees.
+pub const FOV_STEP: f32 = 5.;
+/// Near clip plane limits. Zero or negative breaks the projection, far out clips blocks next to the camera.
+pub const NEAR_RANGE: RangeInclusive<f32> = 0.01..=1.0;
+
+/// Applied to every `FlyCam` projection whenever it changes.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Vertical field of view, in degrees.
+    pub fov: f32,
+    pub near: f32,
+    /// Only used for culling, the camera projection has no far plane.
+    pub far: f32,
+    /// Mouse look speed, see `player_look`.
+    pub sensitivity: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            fov: 45.,
+            near: 0.1,
+            far: 1000.,
+            sensitivity: 0.00012,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Brings every field into a range the projection can use.
+    #[must_use]
+    pub fn clamped(self) -> Self {
+        let near = self.near.clamp(*NEAR_RANGE.start(), *NEAR_RANGE.end());
+        Self {
+            fov: self.fov.clamp(*FOV_RANGE.start(), *FOV_RANGE.end()),
+            near,
+            far: self.far.max(near * 2.),
+            sensitivity: self.sensitivity.max(0.),
+        }
+    }
+
+    /// Reads the settings from `path`, falling back to the defaults if it is missing or invalid.
+    #[must_use]
+    pub fn load(path: &Path) -> Self {
+        let settings = match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|err| {
+                warn!("Ignoring invalid camera settings in {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!("Could not read camera settings from {}: {err}", path.display());
+                Self::default()
+            }
+        };
+        settings.clamped()
+    }
+
+    /// Writes the settings to `path` as toml.
+    ///
+    /// # Errors
+    /// If the file or its directory can not be written.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let contents = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, contents)
+    }
+
+    const fn apply(&self, projection: &mut PerspectiveProjection) {
+        projection.fov = self.fov.to_radians();
+        projection.near = self.near;
+        projection.far = self.far;
+    }
+}
+
+/// Where [`CameraSettings`] are loaded from and saved to.
+#[derive(Resource, Clone, Debug)]
+pub struct CameraSettingsPath(pub PathBuf);
+
+pub struct CameraSettingsPlugin {
+    pub path: PathBuf,
+}
+
+impl Default for CameraSettingsPlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("config/camera.toml"),
+        }
+    }
+}
+
+impl Plugin for CameraSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CameraSettings::load(&self.path))
+            .insert_resource(CameraSettingsPath(self.path.clone()))
+            .init_resource::<KeyBindings>()
+            .add_systems(
+                Update,
+                (adjust_fov, apply_camera_settings, save_camera_settings).chain(),
+            );
+    }
+}
+
+/// `[` and `]` narrow and widen the field of view
+#[allow(clippy::needless_pass_by_value)]
+fn adjust_fov(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut settings: ResMut<CameraSettings>,
+) {
+    let widen = key_bindings.just_pressed(&keys, Action::WidenFov);
+    let narrow = key_bindings.just_pressed(&keys, Action::NarrowFov);
+    if widen == narrow {
+        return;
+    }
+
+    let step = if widen { FOV_STEP } else { -FOV_STEP };
+    let adjusted = CameraSettings {
+        fov: settings.fov + step,
+        ..*settings
+    }
+    .clamped();
+    // at the limit nothing changes, so nothing is saved either
+    settings.set_if_neq(adjusted);
+}
+
+/// Writes the settings into new cameras, and into all of them when the settings change.
+/// Bevy rebuilds the projection matrix from the changed `Projection` in the same frame.
+#[allow(clippy::needless_pass_by_value)]
+fn apply_camera_settings(
+    settings: Res<CameraSettings>,
+    mut projections: Query<&mut Projection, With<FlyCam>>,
+) {
+    for mut projection in &mut projections {
+        if !(settings.is_changed() || projection.is_added()) {
+            continue;
+        }
+        if let Projection::Perspective(perspective) = &mut *projection {
+            settings.apply(perspective);
+        } else {
+            warn!("Camera settings only apply to perspective cameras.");
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn save_camera_settings(settings: Res<CameraSettings>, path: Res<CameraSettingsPath>) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    if let Err(err) = settings.save(&path.0) {
+        error!("Could not save camera settings to {}: {err}", path.0.display());
+    }
+}
+
+#[test]
+fn fov_keys_are_clamped_and_saved() {
+    let path = std::env::temp_dir()
+        .join(format!("talc_camera_settings_{}", std::process::id()))
+        .join("camera.toml");
+    let _ = fs::remove_file(&path);
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<ButtonInput<KeyCode>>()
+        .add_plugins(CameraSettingsPlugin { path: path.clone() });
+    let camera = app
+        .world_mut()
+        .spawn((FlyCam, Projection::Perspective(PerspectiveProjection::default())))
+        .id();
+    app.update();
+    assert!(!path.exists(), "Nothing changed, nothing to save.");
+
+    let fov = |app: &App| match app.world().get::<Projection>(camera) {
+        Some(Projection::Perspective(perspective)) => perspective.fov.to_degrees(),
+        _ => panic!("The camera has a perspective projection."),
+    };
+    assert!((fov(&app) - CameraSettings::default().fov).abs() < 1e-3);
+
+    for _ in 0..30 {
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(KeyCode::BracketRight);
+        keys.clear();
+        keys.press(KeyCode::BracketRight);
+        app.update();
+    }
+    assert!((fov(&app) - FOV_RANGE.end()).abs() < 1e-3);
+
+    let saved = CameraSettings::load(&path);
+    assert!((saved.fov - FOV_RANGE.end()).abs() < f32::EPSILON);
+    assert_eq!(saved, *app.world().resource::<CameraSettings>());
+
+    // out of range values from a hand edited file are clamped on load
+    fs::write(&path, "fov = 1.0\nnear = -2.0\n").expect("The settings directory exists.");
+    let loaded = CameraSettings::load(&path);
+    assert!((loaded.fov - FOV_RANGE.start()).abs() < f32::EPSILON);
+    assert!(loaded.near > 0.);
+    assert!((loaded.far - CameraSettings::default().far).abs() < f32::EPSILON);
+
+    let _ = fs::remove_dir_all(path.parent().expect("The settings file is in a directory."));
+}
@@ -1,50 +1,23 @@
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
-use bevy::window::{CursorGrabMode, PrimaryWindow};
+use bevy::window::{CursorGrabMode, PrimaryWindow, WindowFocused};
+
+use super::camera_settings::CameraSettings;
+use super::key_bindings::{Action, KeyBindings};
 
 pub mod prelude {
     pub use crate::*;
 }
 
-/// Mouse sensitivity and movement speed
+/// Movement speed. Mouse sensitivity is in [`CameraSettings`].
 #[derive(Resource)]
 pub struct MovementSettings {
-    pub sensitivity: f32,
     pub speed: f32,
 }
 
 impl Default for MovementSettings {
     fn default() -> Self {
-        Self {
-            sensitivity: 0.00012,
-            speed: 50.,
-        }
-    }
-}
-
-/// Key configuration
-#[derive(Resource)]
-pub struct KeyBindings {
-    pub move_forward: KeyCode,
-    pub move_backward: KeyCode,
-    pub move_left: KeyCode,
-    pub move_right: KeyCode,
-    pub move_ascend: KeyCode,
-    pub move_descend: KeyCode,
-    pub toggle_grab_cursor: KeyCode,
-}
-
-impl Default for KeyBindings {
-    fn default() -> Self {
-        Self {
-            move_forward: KeyCode::KeyW,
-            move_backward: KeyCode::KeyS,
-            move_left: KeyCode::KeyA,
-            move_right: KeyCode::KeyD,
-            move_ascend: KeyCode::Space,
-            move_descend: KeyCode::ShiftLeft,
-            toggle_grab_cursor: KeyCode::Escape,
-        }
+        Self { speed: 50. }
     }
 }
 
@@ -90,22 +63,25 @@ fn player_move(
             let forward = -Vec3::new(local_z.x, 0., local_z.z);
             let right = Vec3::new(local_z.z, 0., -local_z.x);
 
-            for key in keys.get_pressed() {
-                if window.cursor_options.grab_mode != CursorGrabMode::None {
-                    let key = *key;
-                    if key == key_bindings.move_forward {
-                        velocity += forward;
-                    } else if key == key_bindings.move_backward {
-                        velocity -= forward;
-                    } else if key == key_bindings.move_left {
-                        velocity -= right;
-                    } else if key == key_bindings.move_right {
-                        velocity += right;
-                    } else if key == key_bindings.move_ascend {
-                        velocity += Vec3::Y;
-                    } else if key == key_bindings.move_descend {
-                        velocity -= Vec3::Y;
-                    }
+            if window.cursor_options.grab_mode != CursorGrabMode::None {
+                let pressed = |action| key_bindings.pressed(&keys, action);
+                if pressed(Action::MoveForward) {
+                    velocity += forward;
+                }
+                if pressed(Action::MoveBackward) {
+                    velocity -= forward;
+                }
+                if pressed(Action::MoveLeft) {
+                    velocity -= right;
+                }
+                if pressed(Action::MoveRight) {
+                    velocity += right;
+                }
+                if pressed(Action::MoveAscend) {
+                    velocity += Vec3::Y;
+                }
+                if pressed(Action::MoveDescend) {
+                    velocity -= Vec3::Y;
                 }
             }
 
@@ -118,30 +94,49 @@ fn player_move(
     }
 }
 
+/// Just short of straight up/down, past this yaw flips around and the view turns upside down.
+const MAX_PITCH: f32 = 1.54;
+
+/// Turns `rotation` by a mouse `delta` in degrees, keeping the pitch within `MAX_PITCH` and the roll at zero.
+fn look(rotation: Quat, delta: Vec2) -> Quat {
+    let (mut yaw, mut pitch, _) = rotation.to_euler(EulerRot::YXZ);
+    pitch -= delta.y.to_radians();
+    yaw -= delta.x.to_radians();
+
+    pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+
+    // Order is important to prevent unintended roll
+    Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch)
+}
+
 /// Handles looking around if cursor is locked
 #[allow(clippy::needless_pass_by_value)]
 fn player_look(
-    settings: Res<MovementSettings>,
+    settings: Res<CameraSettings>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
     mut state: EventReader<MouseMotion>,
+    mut focus_changes: EventReader<WindowFocused>,
     mut query: Query<&mut Transform, With<FlyCam>>,
 ) {
     if let Ok(window) = primary_window.single() {
+        // motion while unfocused, and the jump reported as focus comes back, is not the player looking around
+        let focus_changed = focus_changes.read().count() > 0;
+        if !window.focused || focus_changed {
+            state.clear();
+            return;
+        }
+
         for mut transform in &mut query {
             for ev in state.read() {
-                let (mut yaw, mut pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
-                if window.cursor_options.grab_mode != CursorGrabMode::None {
+                let delta = if window.cursor_options.grab_mode == CursorGrabMode::None {
+                    Vec2::ZERO
+                } else {
                     // Using smallest of height or width ensures equal vertical and horizontal sensitivity
                     let window_scale = window.height().min(window.width());
-                    pitch -= (settings.sensitivity * ev.delta.y * window_scale).to_radians();
-                    yaw -= (settings.sensitivity * ev.delta.x * window_scale).to_radians();
-                }
+                    settings.sensitivity * ev.delta * window_scale
+                };
 
-                pitch = pitch.clamp(-1.54, 1.54);
-
-                // Order is important to prevent unintended roll
-                transform.rotation =
-                    Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+                transform.rotation = look(transform.rotation, delta);
             }
         }
     } else {
@@ -156,7 +151,7 @@ fn cursor_grab(
     mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     if let Ok(mut window) = primary_window.single_mut() {
-        if keys.just_pressed(key_bindings.toggle_grab_cursor) {
+        if key_bindings.just_pressed(&keys, Action::ToggleGrabCursor) {
             toggle_grab_cursor(&mut window);
         }
     } else {
@@ -186,6 +181,7 @@ pub struct NoCameraPlayerPlugin;
 impl Plugin for NoCameraPlayerPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MovementSettings>()
+            .init_resource::<CameraSettings>()
             .init_resource::<KeyBindings>()
             .add_systems(Startup, initial_grab_cursor)
             .add_systems(Startup, initial_grab_on_flycam_spawn)
@@ -194,3 +190,18 @@ impl Plugin for NoCameraPlayerPlugin {
             .add_systems(Update, cursor_grab);
     }
 }
+
+#[test]
+fn look_stops_at_the_poles() {
+    let mut rotation = Quat::IDENTITY;
+    for _ in 0..100 {
+        rotation = look(rotation, Vec2::new(0., -10.));
+    }
+    assert!((rotation * Vec3::Y).y > 0.);
+    assert!((rotation * Vec3::NEG_Z).y < 1.);
+
+    rotation = look(rotation, Vec2::new(90., 2000.));
+    let (_, pitch, roll) = rotation.to_euler(EulerRot::YXZ);
+    assert!((pitch + MAX_PITCH).abs() < 1e-4);
+    assert!(roll.abs() < 1e-4);
+}
@@ -0,0 +1,176 @@
+//! Rebindable keys. Input systems ask [`KeyBindings`] about an [`Action`] instead of a literal `KeyCode`.
+//!
+//! Bindings are read from a toml file mapping action names to lists of keys, e.g. `toggle_debug = ["F1"]`.
+//! Actions missing from the file keep their default keys.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Everything a key can be bound to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveAscend,
+    MoveDescend,
+    ToggleGrabCursor,
+    /// The FPS counter and chunk statistics.
+    ToggleDebug,
+    CyclePresentMode,
+    ToggleOriginGizmo,
+    ReloadAllChunks,
+    /// Freezes chunk loading, see `ChunkLoadingPaused`.
+    ToggleChunkLoading,
+    IncreaseRenderDistance,
+    DecreaseRenderDistance,
+    WidenFov,
+    NarrowFov,
+    /// Speeds up the daylight cycle while held.
+    FastForwardTime,
+    /// Picks the chunk material parameter the next two actions adjust.
+    CycleLightingParameter,
+    RaiseLightingParameter,
+    LowerLightingParameter,
+    TeleportToNextPreset,
+}
+
+impl Action {
+    /// The keys bound to this action when the config file does not say otherwise.
+    #[must_use]
+    pub const fn default_keys(self) -> &'static [KeyCode] {
+        match self {
+            Self::MoveForward => &[KeyCode::KeyW],
+            Self::MoveBackward => &[KeyCode::KeyS],
+            Self::MoveLeft => &[KeyCode::KeyA],
+            Self::MoveRight => &[KeyCode::KeyD],
+            Self::MoveAscend => &[KeyCode::Space],
+            Self::MoveDescend => &[KeyCode::ShiftLeft],
+            Self::ToggleGrabCursor => &[KeyCode::Escape],
+            Self::ToggleDebug => &[KeyCode::F3],
+            Self::CyclePresentMode => &[KeyCode::KeyV],
+            Self::ToggleOriginGizmo => &[KeyCode::F4],
+            Self::ReloadAllChunks => &[KeyCode::F5],
+            Self::ToggleChunkLoading => &[KeyCode::F6],
+            Self::IncreaseRenderDistance => &[KeyCode::Equal, KeyCode::NumpadAdd],
+            Self::DecreaseRenderDistance => &[KeyCode::Minus, KeyCode::NumpadSubtract],
+            Self::WidenFov => &[KeyCode::BracketRight],
+            Self::NarrowFov => &[KeyCode::BracketLeft],
+            Self::FastForwardTime => &[KeyCode::KeyI],
+            Self::CycleLightingParameter => &[KeyCode::KeyL],
+            Self::RaiseLightingParameter => &[KeyCode::Period],
+            Self::LowerLightingParameter => &[KeyCode::Comma],
+            Self::TeleportToNextPreset => &[KeyCode::KeyP],
+        }
+    }
+}
+
+/// The keys bound to each [`Action`]. An action bound to no keys can not be triggered.
+#[derive(Resource, Clone, Default, Debug, PartialEq, Eq)]
+pub struct KeyBindings {
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl KeyBindings {
+    /// The keys bound to `action`.
+    #[must_use]
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(action.default_keys(), Vec::as_slice)
+    }
+
+    /// Replaces the keys bound to `action`.
+    pub fn bind(&mut self, action: Action, keys: impl IntoIterator<Item = KeyCode>) {
+        self.keys.insert(action, keys.into_iter().collect());
+    }
+
+    /// Whether any key bound to `action` is held.
+    #[must_use]
+    pub fn pressed(&self, input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    /// Whether any key bound to `action` was pressed this frame.
+    #[must_use]
+    pub fn just_pressed(&self, input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        input.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    /// Reads the bindings from `path`, falling back to the defaults if it is missing or invalid.
+    #[must_use]
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(keys) => Self { keys },
+                Err(err) => {
+                    warn!("Ignoring invalid key bindings in {}: {err}", path.display());
+                    Self::default()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!("Could not read key bindings from {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+}
+
+pub struct KeyBindingsPlugin {
+    pub path: PathBuf,
+}
+
+impl Default for KeyBindingsPlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("config/keys.toml"),
+        }
+    }
+}
+
+impl Plugin for KeyBindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(KeyBindings::load(&self.path));
+    }
+}
+
+#[test]
+fn overridden_binding_triggers_its_action() {
+    let path = std::env::temp_dir()
+        .join(format!("talc_key_bindings_{}", std::process::id()))
+        .join("keys.toml");
+    fs::create_dir_all(path.parent().expect("The bindings file is in a directory."))
+        .expect("The temp directory is writable.");
+    fs::write(&path, "toggle_debug = [\"F1\"]\nreload_all_chunks = []\n")
+        .expect("The temp directory is writable.");
+
+    let bindings = KeyBindings::load(&path);
+    assert_eq!(bindings.keys(Action::ToggleDebug), [KeyCode::F1]);
+    assert!(bindings.keys(Action::ReloadAllChunks).is_empty());
+    // untouched actions keep their defaults
+    assert_eq!(bindings.keys(Action::MoveForward), [KeyCode::KeyW]);
+
+    let mut input = ButtonInput::<KeyCode>::default();
+    input.press(KeyCode::F1);
+    assert!(bindings.just_pressed(&input, Action::ToggleDebug));
+    assert!(!KeyBindings::default().just_pressed(&input, Action::ToggleDebug));
+
+    input.clear();
+    input.press(KeyCode::F3);
+    input.press(KeyCode::F5);
+    assert!(!bindings.just_pressed(&input, Action::ToggleDebug));
+    assert!(!bindings.just_pressed(&input, Action::ReloadAllChunks));
+
+    // a file that does not parse leaves every binding at its default
+    fs::write(&path, "toggle_debug = \"not a list\"\n").expect("The temp directory is writable.");
+    assert_eq!(KeyBindings::load(&path), KeyBindings::default());
+
+    let _ = fs::remove_dir_all(path.parent().expect("The bindings file is in a directory."));
+}
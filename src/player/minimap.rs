@@ -0,0 +1,212 @@
+//! Top-down minimap overlay.
+//! Each pixel is the color of the topmost meshable block of a column, read straight from the loaded `ChunkData`.
+
+use std::time::Duration;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    image::ImageSampler,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    chunky::{async_chunkloader::Chunks, chunk::CHUNK_SIZE_I32},
+    player::render_distance::Scanner,
+    position::{ChunkPosition, FloatingPosition, Position, RelativePosition},
+};
+
+/// Blocks covered along each side of the minimap, one pixel per block.
+pub const MINIMAP_BLOCKS: usize = 128;
+/// Size of the minimap on screen.
+pub const MINIMAP_SCREEN_SIZE: f32 = 192.;
+/// Chunks searched above and below the player for the surface.
+pub const MINIMAP_VERTICAL_CHUNKS: i32 = 4;
+/// The minimap is redrawn at most this often, and only after chunks changed or the player moved.
+pub const MINIMAP_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+const UNLOADED_COLOR: [u8; 4] = [0, 0, 0, 96];
+const PLAYER_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+#[derive(Resource)]
+struct Minimap {
+    image: Handle<Image>,
+    timer: Timer,
+    /// Chunks were loaded, unloaded or edited since the last redraw.
+    dirty: bool,
+    /// The block the minimap was centered on at the last redraw.
+    center: Option<Position>,
+}
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_minimap)
+            .add_systems(Update, update_minimap);
+    }
+}
+
+fn spawn_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let size = MINIMAP_BLOCKS as u32;
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &UNLOADED_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let image = images.add(image);
+
+    commands.spawn((
+        Name::new("Minimap"),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(8.),
+            bottom: Val::Px(8.),
+            width: Val::Px(MINIMAP_SCREEN_SIZE),
+            height: Val::Px(MINIMAP_SCREEN_SIZE),
+            ..default()
+        },
+        ImageNode::new(image.clone()),
+    ));
+    commands.insert_resource(Minimap {
+        image,
+        timer: Timer::new(MINIMAP_UPDATE_INTERVAL, TimerMode::Repeating),
+        dirty: true,
+        center: None,
+    });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_minimap(
+    minimap: Option<ResMut<Minimap>>,
+    mut images: ResMut<Assets<Image>>,
+    chunks: Res<Chunks>,
+    scanners: Query<&GlobalTransform, With<Scanner>>,
+    time: Res<Time>,
+) {
+    let Some(mut minimap) = minimap else {
+        return;
+    };
+    minimap.dirty |= chunks.is_changed();
+    if !minimap.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(scanner) = scanners.single() else {
+        return;
+    };
+    let center = Position::from(FloatingPosition(scanner.translation()));
+    if !minimap.dirty && minimap.center == Some(center) {
+        return;
+    }
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+
+    let pixels = surface_pixels(&chunks, center, MINIMAP_BLOCKS);
+    if let Some(data) = image.data.as_mut() {
+        data.copy_from_slice(&pixels);
+    }
+    minimap.dirty = false;
+    minimap.center = Some(center);
+}
+
+/// RGBA pixels of the `size` x `size` blocks around `center`, north (-z) up.
+/// Columns without a loaded meshable block near `center` are drawn dimmed, the center pixel marks the player.
+fn surface_pixels(chunks: &Chunks, center: Position, size: usize) -> Vec<u8> {
+    let half = size as i32 / 2;
+    let center_chunk = ChunkPosition::from(center);
+    let mut pixels = Vec::with_capacity(size * size * 4);
+
+    for row in 0..size as i32 {
+        for column in 0..size as i32 {
+            let x = center.x - half + column;
+            let z = center.z - half + row;
+            let color = if (x, z) == (center.x, center.z) {
+                PLAYER_COLOR
+            } else {
+                surface_color(chunks, x, z, center_chunk.y).unwrap_or(UNLOADED_COLOR)
+            };
+            pixels.extend_from_slice(&color);
+        }
+    }
+    pixels
+}
+
+/// Color of the topmost meshable block at world (x, z), searching the loaded chunks near `center_chunk_y`.
+fn surface_color(chunks: &Chunks, x: i32, z: i32, center_chunk_y: i32) -> Option<[u8; 4]> {
+    let chunk_x = x.div_euclid(CHUNK_SIZE_I32);
+    let chunk_z = z.div_euclid(CHUNK_SIZE_I32);
+    let relative_x = x.rem_euclid(CHUNK_SIZE_I32);
+    let relative_z = z.rem_euclid(CHUNK_SIZE_I32);
+
+    let chunk_ys = (center_chunk_y - MINIMAP_VERTICAL_CHUNKS)..=(center_chunk_y + MINIMAP_VERTICAL_CHUNKS);
+    for chunk_y in chunk_ys.rev() {
+        let Some(chunk_data) = chunks.0.get(&ChunkPosition::new(chunk_x, chunk_y, chunk_z)) else {
+            continue;
+        };
+        // sky chunks are stored homogeneous, so most of the search skips them whole
+        let block = chunk_data
+            .homogeneous_block()
+            .map_or_else(
+                || {
+                    (0..CHUNK_SIZE_I32)
+                        .rev()
+                        .map(|y| chunk_data.get_block(RelativePosition::new(relative_x, y, relative_z).into()))
+                        .find(|block| block.is_meshable)
+                },
+                Some,
+            )
+            .filter(|block| block.is_meshable);
+        if let Some(block) = block {
+            return Some(block.color.to_srgba().to_u8_array());
+        }
+    }
+    None
+}
+
+#[test]
+fn surface_pixels_show_the_topmost_block() {
+    use std::sync::Arc;
+
+    use crate::{
+        chunky::chunk::{ChunkData, init_test_block_registry},
+        mod_manager::prototypes::Prototypes,
+    };
+
+    let block_prototypes = init_test_block_registry();
+    let air = block_prototypes.get("air").expect("Test registry has air.");
+    let stone = block_prototypes.get("stone").expect("Test registry has stone.");
+
+    // a stone floor at y 3 in chunk (0, 0, 0), and a sky chunk above it
+    let mut chunks = Chunks::default();
+    let floor = ChunkData::from_fn(ChunkPosition::new(0, 0, 0), |position| {
+        if position.y <= 3 { stone } else { air }
+    });
+    chunks.0.insert(floor.position, Arc::new(floor));
+    let sky = ChunkData::filled(ChunkPosition::new(0, 1, 0), air);
+    chunks.0.insert(sky.position, Arc::new(sky));
+
+    let size = 8;
+    let center = Position::new(4, 40, 4);
+    let pixels = surface_pixels(&chunks, center, size);
+    assert_eq!(pixels.len(), size * size * 4);
+
+    let pixel = |column: usize, row: usize| &pixels[(row * size + column) * 4..][..4];
+    let stone_color = stone.color.to_srgba().to_u8_array();
+    // (0, 0) is the block at x 0, z 0
+    assert_eq!(pixel(0, 0), stone_color);
+    assert_eq!(pixel(4, 4), PLAYER_COLOR);
+    assert_eq!(pixel(7, 7), stone_color);
+
+    // columns in x < 0 have no loaded chunks
+    let pixels = surface_pixels(&chunks, Position::new(0, 40, 4), size);
+    assert_eq!(&pixels[..4], UNLOADED_COLOR);
+    assert_eq!(&pixels[4 * 4..][..4], stone_color);
+}
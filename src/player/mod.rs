@@ -1,2 +1,9 @@
+pub mod block_info;
+pub mod block_picker;
+pub mod camera_settings;
 pub mod debug_camera;
+pub mod key_bindings;
+pub mod minimap;
 pub mod render_distance;
+pub mod spawn;
+pub mod teleport;
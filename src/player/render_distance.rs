@@ -2,34 +2,47 @@
 scanner is responsible for identifying what chunks needs to be loaded (mesh/data)
 the current implementation is exellent for low render distances, 1-15
 but anything above that might induce some frame lag, due to how the load/unload data is calculated.
-`scanner::new()` can also be very slow on high render distances, giving an initial slow execution time.
+the sampling offsets for each render distance are generated once and shared by every `Scanner::new()`.
 */
 
 use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock, Mutex};
 
-use bevy::platform::collections::HashSet;
+use bevy::platform::collections::{HashMap, HashSet};
 use bevy::prelude::*;
 
-use crate::chunky::async_chunkloader::Chunks;
+use crate::chunky::async_chunkloader::{ChunkLoaderBudget, Chunks, chunk_loading_running};
 use crate::chunky::chunks_refs::ChunkRefs;
+use crate::chunky::terrain::Terrain;
 use crate::render::chunk_material::RenderableChunk;
-use crate::{position::ChunkPosition};
+use crate::position::{ChunkPosition, FloatingPosition};
+use crate::player::key_bindings::{Action, KeyBindings};
 
-use crate::chunky::{async_chunkloader::AsyncChunkloader, chunk::CHUNK_SIZE_I32};
+use crate::chunky::{async_chunkloader::AsyncChunkloader, chunk::CHUNK_SIZE_F32};
 
-pub const MAX_DATA_TASKS: usize = 9;
-pub const MAX_MESH_TASKS: usize = 3;
+pub const MIN_RENDER_DISTANCE: u32 = 2;
+pub const MAX_RENDER_DISTANCE: u32 = 48;
+/// Render distance is a diameter, so this grows the radius by one chunk.
+pub const RENDER_DISTANCE_STEP: u32 = 2;
+/// Chunks are only unloaded once they are this much further than the load distance (diameter),
+/// so walking back and forth over a chunk border doesn't unload and reload the same ring.
+pub const UNLOAD_DISTANCE_MARGIN: u32 = 2;
+/// Default [`Scanner::prefetch_lead`], in blocks.
+pub const DEFAULT_PREFETCH_LEAD: f32 = 48.;
 
-pub const MAX_SCANS: usize = 26000;
+/// Never reached by a real scanner. Forces `detect_move` to queue the whole area.
+const UNSCANNED_CHUNK_POS: ChunkPosition = ChunkPosition::new(777, 777, 777);
 
 pub struct ScannerPlugin;
 
 impl Plugin for ScannerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.init_resource::<KeyBindings>().add_systems(
             PreUpdate,
             (
-                detect_move,
+                adjust_render_distance.before(detect_move),
+                track_velocity,
+                detect_move.run_if(chunk_loading_running),
                 scan_data,
                 scan_data_unload,
                 scan_mesh_unload,
@@ -41,6 +54,8 @@ impl Plugin for ScannerPlugin {
 
 #[derive(Component)]
 pub struct Scanner {
+    /// Diameter of the meshed area, in chunks.
+    pub distance: u32,
     pub prev_chunk_pos: ChunkPosition,
 
     // chunk positions we are yet to check we need need to load
@@ -53,8 +68,18 @@ pub struct Scanner {
 
     // on detecting a scanner move, these offsets are used to
     // identify the location of what chunks need to be checked
-    pub worldgen_sampling_offsets: Vec<ChunkPosition>,
-    pub mesh_sampling_offsets: Vec<ChunkPosition>,
+    pub worldgen_sampling_offsets: Arc<[ChunkPosition]>,
+    pub mesh_sampling_offsets: Arc<[ChunkPosition]>,
+    // same as above, grown by `UNLOAD_DISTANCE_MARGIN`. chunks outside these get unloaded.
+    pub worldgen_unload_sampling_offsets: Arc<[ChunkPosition]>,
+    pub mesh_unload_sampling_offsets: Arc<[ChunkPosition]>,
+
+    /// Blocks per second, from the position change over the last frame.
+    pub velocity: Vec3,
+    prev_translation: Option<Vec3>,
+    /// Chunks are loaded and meshed in order of distance to where the scanner will be in a second,
+    /// but at most this many blocks ahead of it.
+    pub prefetch_lead: f32,
 }
 
 impl Scanner {
@@ -67,14 +92,134 @@ impl Scanner {
         let worldgen_distance = distance + 1;
 
         Self {
-            worldgen_sampling_offsets: make_offset_vec(worldgen_distance),
-            mesh_sampling_offsets: make_offset_vec(mesh_distance),
+            distance,
+            worldgen_sampling_offsets: cached_offset_vec(worldgen_distance),
+            mesh_sampling_offsets: cached_offset_vec(mesh_distance),
+            worldgen_unload_sampling_offsets: cached_offset_vec(
+                worldgen_distance + UNLOAD_DISTANCE_MARGIN,
+            ),
+            mesh_unload_sampling_offsets: cached_offset_vec(mesh_distance + UNLOAD_DISTANCE_MARGIN),
             unresolved_data_load: Vec::default(),
-            prev_chunk_pos: ChunkPosition::new(777, 777, 777),
+            prev_chunk_pos: UNSCANNED_CHUNK_POS,
             unresolved_mesh_load: Vec::default(),
             unresolved_data_unload: VecDeque::default(),
             unresolved_mesh_unload: VecDeque::default(),
+            velocity: Vec3::ZERO,
+            prev_translation: None,
+            prefetch_lead: DEFAULT_PREFETCH_LEAD,
+        }
+    }
+
+    /// The point chunks are prioritized around, `translation` led forward along the velocity.
+    #[must_use]
+    pub fn prefetch_origin(&self, translation: Vec3) -> FloatingPosition {
+        FloatingPosition(translation + self.velocity.clamp_length_max(self.prefetch_lead))
+    }
+
+    /// change the render distance in place
+    /// chunks which fall out of range are queued for unloading, and `detect_move` rescans the new area.
+    pub fn set_distance(&mut self, distance: u32) {
+        if distance == self.distance {
+            return;
+        }
+
+        let center = self.prev_chunk_pos;
+        let old_worldgen_area = area(center, &self.worldgen_unload_sampling_offsets);
+        let old_mesh_area = area(center, &self.mesh_unload_sampling_offsets);
+
+        let rescanned = Self::new(distance);
+        self.distance = distance;
+        self.worldgen_sampling_offsets = rescanned.worldgen_sampling_offsets;
+        self.mesh_sampling_offsets = rescanned.mesh_sampling_offsets;
+        self.worldgen_unload_sampling_offsets = rescanned.worldgen_unload_sampling_offsets;
+        self.mesh_unload_sampling_offsets = rescanned.mesh_unload_sampling_offsets;
+
+        if center != UNSCANNED_CHUNK_POS {
+            let new_worldgen_area = area(center, &self.worldgen_unload_sampling_offsets);
+            let new_mesh_area = area(center, &self.mesh_unload_sampling_offsets);
+            self.unresolved_data_unload
+                .extend(old_worldgen_area.difference(&new_worldgen_area));
+            self.unresolved_mesh_unload
+                .extend(old_mesh_area.difference(&new_mesh_area));
         }
+        self.prev_chunk_pos = UNSCANNED_CHUNK_POS;
+    }
+
+    /// Chunks within the unload margin of `center`, for worldgen and for meshing.
+    /// Chunks outside of these are unloaded once `detect_move` sees the scanner there.
+    #[must_use]
+    pub fn retained_areas(&self, center: ChunkPosition) -> (HashSet<ChunkPosition>, HashSet<ChunkPosition>) {
+        (
+            area(center, &self.worldgen_unload_sampling_offsets),
+            area(center, &self.mesh_unload_sampling_offsets),
+        )
+    }
+
+    /// Whether a scanner centered on `center` keeps `chunk_position` meshed, unload margin included.
+    #[must_use]
+    pub fn retains_mesh(&self, center: ChunkPosition, chunk_position: ChunkPosition) -> bool {
+        self.mesh_unload_sampling_offsets.contains(&(chunk_position - center))
+    }
+
+    /// Drops the velocity, so a jump to another place does not read as the scanner flying there.
+    pub fn forget_motion(&mut self) {
+        self.velocity = Vec3::ZERO;
+        self.prev_translation = None;
+    }
+
+    /// Forgets everything scanned so far, so `detect_move` queues the whole area again.
+    pub fn rescan(&mut self) {
+        self.prev_chunk_pos = UNSCANNED_CHUNK_POS;
+        self.unresolved_data_load.clear();
+        self.unresolved_mesh_load.clear();
+        self.unresolved_data_unload.clear();
+        self.unresolved_mesh_unload.clear();
+    }
+}
+
+fn area(center: ChunkPosition, offsets: &[ChunkPosition]) -> HashSet<ChunkPosition> {
+    offsets.iter().map(|offset| center + *offset).collect()
+}
+
+/// The chunk a scanner at `translation` is centered on.
+/// Rounds down on every side of the origin, so negative coordinates land in the correct chunk.
+#[must_use]
+pub fn scanner_chunk_position(translation: Vec3) -> ChunkPosition {
+    ChunkPosition::from(FloatingPosition(translation - CHUNK_SIZE_F32 / 2.))
+}
+
+/// +/- grow and shrink the render distance of every scanner
+#[allow(clippy::needless_pass_by_value)]
+fn adjust_render_distance(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut scanners: Query<&mut Scanner>,
+) {
+    let grow = key_bindings.just_pressed(&keys, Action::IncreaseRenderDistance);
+    let shrink = key_bindings.just_pressed(&keys, Action::DecreaseRenderDistance);
+    if grow == shrink {
+        return;
+    }
+
+    for mut scanner in &mut scanners {
+        let distance = if grow {
+            scanner.distance + RENDER_DISTANCE_STEP
+        } else {
+            scanner.distance.saturating_sub(RENDER_DISTANCE_STEP)
+        };
+        scanner.set_distance(distance.clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn track_velocity(time: Res<Time>, mut scanners: Query<(&mut Scanner, &GlobalTransform)>) {
+    let delta = time.delta_secs();
+    for (mut scanner, g_transform) in &mut scanners {
+        let translation = g_transform.translation();
+        if let Some(prev_translation) = scanner.prev_translation.filter(|_| delta > 0.) {
+            scanner.velocity = (translation - prev_translation) / delta;
+        }
+        scanner.prev_translation = Some(translation);
     }
 }
 
@@ -84,9 +229,7 @@ fn detect_move(
     mut chunkloader: ResMut<AsyncChunkloader>,
 ) {
     for (mut scanner, g_transform) in &mut scanners {
-        let chunk_pos = (g_transform.translation().as_ivec3() - IVec3::splat(CHUNK_SIZE_I32 / 2))
-            / CHUNK_SIZE_I32;
-        let chunk_pos = ChunkPosition(chunk_pos);
+        let chunk_pos = scanner_chunk_position(g_transform.translation());
         let previous_chunk_pos = scanner.prev_chunk_pos;
         let chunk_pos_changed = chunk_pos != scanner.prev_chunk_pos;
         scanner.prev_chunk_pos = chunk_pos;
@@ -94,40 +237,36 @@ fn detect_move(
             return;
         }
 
-        let load_data_area = scanner
-            .worldgen_sampling_offsets
-            .iter()
-            .map(|offset| chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let unload_data_area = scanner
-            .worldgen_sampling_offsets
-            .iter()
-            .map(|offset| previous_chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let load_mesh_area = scanner
-            .mesh_sampling_offsets
-            .iter()
-            .map(|offset| chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let unload_mesh_area = scanner
-            .mesh_sampling_offsets
-            .iter()
-            .map(|offset| previous_chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let data_load = load_data_area.difference(&unload_data_area);
-        let data_unload = unload_data_area.difference(&load_data_area);
-        let mesh_load = load_mesh_area.difference(&unload_mesh_area);
-        let mesh_unload = unload_mesh_area.difference(&load_mesh_area);
+        let load_data_area = area(chunk_pos, &scanner.worldgen_sampling_offsets);
+        let prev_load_data_area = area(previous_chunk_pos, &scanner.worldgen_sampling_offsets);
+        let load_mesh_area = area(chunk_pos, &scanner.mesh_sampling_offsets);
+        let prev_load_mesh_area = area(previous_chunk_pos, &scanner.mesh_sampling_offsets);
+
+        // unloading uses the wider areas, so a chunk has to leave the margin before it goes
+        let retain_data_area = area(chunk_pos, &scanner.worldgen_unload_sampling_offsets);
+        let prev_retain_data_area =
+            area(previous_chunk_pos, &scanner.worldgen_unload_sampling_offsets);
+        let retain_mesh_area = area(chunk_pos, &scanner.mesh_unload_sampling_offsets);
+        let prev_retain_mesh_area = area(previous_chunk_pos, &scanner.mesh_unload_sampling_offsets);
+
+        let data_load = load_data_area.difference(&prev_load_data_area);
+        let data_unload = prev_retain_data_area.difference(&retain_data_area);
+        let mesh_load = load_mesh_area.difference(&prev_load_mesh_area);
+        let mesh_unload = prev_retain_mesh_area.difference(&retain_mesh_area);
 
         scanner.unresolved_data_load.extend(data_load);
         scanner.unresolved_data_unload.extend(data_unload);
         scanner.unresolved_mesh_unload.extend(mesh_unload);
         scanner.unresolved_mesh_load.extend(mesh_load);
 
+        // chunks back in range before their grace period ran out stay loaded
+        for p in retain_data_area.difference(&prev_retain_data_area) {
+            chunkloader.pending_chunk_unloads.remove(p);
+        }
+        for p in retain_mesh_area.difference(&prev_retain_mesh_area) {
+            chunkloader.pending_mesh_unloads.remove(p);
+        }
+
         // deconstruct scanner mutable references because rust :P
         let Scanner {
             unresolved_data_load,
@@ -180,25 +319,55 @@ fn detect_move(
     }
 }
 
+/// `make_offset_vec` is slow at high distances, so each distance is only computed once.
+fn cached_offset_vec(diameter: u32) -> Arc<[ChunkPosition]> {
+    static OFFSET_CACHE: LazyLock<Mutex<HashMap<u32, Arc<[ChunkPosition]>>>> =
+        LazyLock::new(Mutex::default);
+
+    let mut cache = OFFSET_CACHE
+        .lock()
+        .expect("Offset cache was poisoned by a panic.");
+    cache
+        .entry(diameter)
+        .or_insert_with(|| make_offset_vec(diameter).into())
+        .clone()
+}
+
 /// constructs a cylinder of chunk positions with the provided chunk radius
-fn make_offset_vec(diameter: u32) -> Vec<ChunkPosition> {
-    let mut sampling_offsets = vec![];
+/// the offsets are ordered by distance from the center, nearest first.
+#[must_use]
+pub fn make_offset_vec(diameter: u32) -> Vec<ChunkPosition> {
     let diameter = diameter as i32;
     let radius = diameter / 2;
+
+    let mut offsets = vec![];
     for x in -radius..radius {
         for z in -radius..radius {
             if IVec2::new(x, z).distance_squared(IVec2::ZERO) <= radius * radius {
                 for y in -radius..radius {
-                    sampling_offsets.push(ChunkPosition::new(x, y, z));
+                    offsets.push(ChunkPosition::new(x, y, z));
                 }
             }
         }
     }
-    
-    sampling_offsets.sort_by(|a, b| {
-        a.distance_squared(IVec3::ZERO)
-            .cmp(&b.distance_squared(IVec3::ZERO))
-    });
+
+    // counting sort over shells of equal squared distance.
+    // much faster than a comparison sort, and keeps the same order as a stable one.
+    let shell = |offset: &ChunkPosition| offset.distance_squared(IVec3::ZERO) as usize;
+    let mut shell_starts = vec![0; (3 * radius * radius + 2) as usize];
+    for offset in &offsets {
+        shell_starts[shell(offset) + 1] += 1;
+    }
+    for i in 1..shell_starts.len() {
+        shell_starts[i] += shell_starts[i - 1];
+    }
+
+    let mut sampling_offsets = vec![ChunkPosition::new(0, 0, 0); offsets.len()];
+    for offset in offsets {
+        let start = &mut shell_starts[shell(&offset)];
+        sampling_offsets[*start] = offset;
+        *start += 1;
+    }
 
     sampling_offsets
 }
@@ -207,15 +376,17 @@ fn make_offset_vec(diameter: u32) -> Vec<ChunkPosition> {
 pub fn scan_data(
     mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
     mut chunkloader: ResMut<AsyncChunkloader>,
+    budget: Res<ChunkLoaderBudget>,
     chunks: Res<Chunks>,
 ) {
     for (mut scanner, _g_transform) in &mut scanners {
-        if chunkloader.worldgen_tasks.len() >= MAX_DATA_TASKS {
+        if chunkloader.worldgen_tasks.len() >= budget.max_scanner_data_tasks {
             return;
         }
         let l = scanner.unresolved_data_load.len();
         // for chunk_pos in scanner.unresolved_data_load.drain(..) {
-        for chunk_pos in scanner.unresolved_data_load.drain(0..MAX_SCANS.min(l)) {
+        for chunk_pos in scanner.unresolved_data_load.drain(0..budget.max_scans.min(l)) {
+            chunkloader.pending_chunk_unloads.remove(&chunk_pos);
             // want to load chunk
             let is_busy = chunks.0.contains_key(&chunk_pos)
                 || chunkloader.load_chunk_queue.contains(&chunk_pos)
@@ -241,27 +412,32 @@ pub fn scan_data_unload(
     mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
     mut chunkloader: ResMut<AsyncChunkloader>,
     chunks: Res<Chunks>,
+    time: Res<Time>,
 ) {
+    let now = time.elapsed();
     // find all loaded and check if in range
     for (mut scanner, _g_transform) in &mut scanners {
         for chunk_pos in scanner.unresolved_data_unload.drain(..) {
             // want to load chunk
             let is_busy = !chunks.0.contains_key(&chunk_pos);
             if !is_busy {
-                chunkloader.unload_chunk_queue.push(chunk_pos);
+                chunkloader.pending_chunk_unloads.entry(chunk_pos).or_insert(now);
             }
         }
     }
 }
 
+#[allow(clippy::needless_pass_by_value)]
 pub fn scan_mesh_unload(
     mut scanners: Query<&mut Scanner>,
     mut chunkloader: ResMut<AsyncChunkloader>,
+    time: Res<Time>,
 ) {
+    let now = time.elapsed();
     // find all loaded and check if in range
     for mut scanner in &mut scanners {
         for chunk_pos in scanner.unresolved_mesh_unload.drain(..) {
-            chunkloader.unload_mesh_queue.push(chunk_pos);
+            chunkloader.pending_mesh_unloads.entry(chunk_pos).or_insert(now);
         }
     }
 }
@@ -270,15 +446,15 @@ pub fn scan_mesh_unload(
 pub fn scan_mesh(
     mut scanners: Query<&mut Scanner>,
     mut chunkloader: ResMut<AsyncChunkloader>,
+    budget: Res<ChunkLoaderBudget>,
+    terrain: Res<Terrain>,
     chunks: Res<Chunks>,
 ) {
     for mut scanner in &mut scanners {
-        // if chunkloader.worldgen_tasks.len() >= MAX_MESH_TASKS {
-        //     return;
-        // }
         let mut retries = Vec::new();
         let l = scanner.unresolved_mesh_load.len();
-        for chunk_position in scanner.unresolved_mesh_load.drain(0..MAX_SCANS.min(l)) {
+        for chunk_position in scanner.unresolved_mesh_load.drain(0..budget.max_scans.min(l)) {
+            chunkloader.pending_mesh_unloads.remove(&chunk_position);
             let busy = chunkloader
                 .load_mesh_queue
                 .iter()
@@ -288,12 +464,24 @@ pub fn scan_mesh(
                 continue;
             }
 
+            // sky chunks mesh to nothing, skip them before waiting on and gathering all 26 neighbours.
+            if chunks.0.get(&chunk_position).is_some_and(|chunk_data| chunk_data.meshes_to_nothing()) {
+                continue;
+            }
+
             // all 27 adjacent voxel datas are available. we are safe to start a mesh thread.
             let Some(adjacent_chunks) = ChunkRefs::try_new(&chunks, chunk_position) else {
                 retries.push(chunk_position);
                 continue;
             };
 
+            // away from the terrain surface most chunks would mesh to nothing, don't spawn a task for them.
+            if !terrain.0.may_have_faces(chunk_position)
+                && (adjacent_chunks.is_all_voxels_same() || adjacent_chunks.is_occluded())
+            {
+                continue;
+            }
+
             chunkloader.load_mesh_queue.push(adjacent_chunks);
 
             // abort unload
@@ -317,3 +505,57 @@ pub fn scan_mesh(
         scanner.unresolved_mesh_load.append(&mut retries);
     }
 }
+
+#[test]
+fn offset_vec_is_sorted_by_distance() {
+    for diameter in [0, 1, 2, 7, 12, 13] {
+        let offsets = make_offset_vec(diameter);
+        let mut sorted = offsets.clone();
+        sorted.sort_by_key(|offset| offset.distance_squared(IVec3::ZERO));
+        assert_eq!(offsets, sorted);
+    }
+}
+
+#[test]
+fn scanner_chunk_position_rounds_down_below_zero() {
+    for (x, chunk) in [(16., 0), (15.9, -1), (47.9, 0), (48., 1), (-16., -1), (-16.1, -2), (-48., -2)] {
+        assert_eq!(scanner_chunk_position(Vec3::new(x, x, x)), ChunkPosition::new(chunk, chunk, chunk), "at {x}");
+    }
+}
+
+#[test]
+fn crossing_a_chunk_border_keeps_loaded_chunks() {
+    let mut app = App::new();
+    app.init_resource::<AsyncChunkloader>()
+        .add_systems(Update, detect_move);
+    let scanner = app
+        .world_mut()
+        .spawn((Scanner::new(8), GlobalTransform::from_xyz(48.0, 48.0, 48.0)))
+        .id();
+    app.update();
+
+    let mut loaded_areas = HashSet::new();
+    let mut unloaded = HashSet::new();
+    for x in [80.0, 48.0, 80.0, 48.0] {
+        {
+            let mut scanner_ref = app.world_mut().entity_mut(scanner);
+            let mut scanner = scanner_ref.get_mut::<Scanner>().expect("Scanner was spawned.");
+            let center = scanner.prev_chunk_pos;
+            loaded_areas.extend(area(center, &scanner.worldgen_sampling_offsets));
+            scanner.unresolved_data_unload.clear();
+            scanner.unresolved_mesh_unload.clear();
+            scanner_ref.insert(GlobalTransform::from_xyz(x, 48.0, 48.0));
+        }
+        app.update();
+
+        let scanner = app
+            .world()
+            .get::<Scanner>(scanner)
+            .expect("Scanner was spawned.");
+        unloaded.extend(scanner.unresolved_data_unload.iter().copied());
+        unloaded.extend(scanner.unresolved_mesh_unload.iter().copied());
+    }
+
+    assert!(!unloaded.is_empty());
+    assert!(unloaded.is_disjoint(&loaded_areas));
+}
@@ -0,0 +1,117 @@
+//! Where the camera starts, read from `config/spawn.toml`, e.g. `position = [0.0, 80.0, 0.0]`.
+//!
+//! The camera spawns before any chunk is loaded, so a position inside a hill starts underground.
+//! `above_terrain = true` lifts it over every chunk the generator can put blocks in at that column.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::chunky::{chunk::CHUNK_SIZE_I32, terrain::TerrainGenerator};
+use crate::position::ChunkPosition;
+
+use super::render_distance::scanner_chunk_position;
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct SpawnConfig {
+    pub position: Vec3,
+    /// Raise the spawn out of the terrain, see [`SpawnConfig::translation`].
+    pub above_terrain: bool,
+}
+
+impl Default for SpawnConfig {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0., 200., 0.5),
+            above_terrain: false,
+        }
+    }
+}
+
+impl SpawnConfig {
+    /// The configured position. With `above_terrain`, at least the top of the column's `vertical_extent`,
+    /// which only needs the generator and not the chunks.
+    #[must_use]
+    pub fn translation(&self, terrain: &dyn TerrainGenerator) -> Vec3 {
+        if !self.above_terrain {
+            return self.position;
+        }
+        let ChunkPosition(column) = scanner_chunk_position(self.position);
+        let top = (terrain.vertical_extent(column.x, column.z).end() + 1) * CHUNK_SIZE_I32;
+        self.position.with_y(self.position.y.max(top as f32))
+    }
+
+    /// Reads the spawn from `path`, falling back to the default if it is missing or invalid.
+    #[must_use]
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|err| {
+                warn!("Ignoring invalid spawn config in {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!("Could not read spawn config from {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+}
+
+pub struct SpawnConfigPlugin {
+    pub path: PathBuf,
+}
+
+impl Default for SpawnConfigPlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("config/spawn.toml"),
+        }
+    }
+}
+
+impl Plugin for SpawnConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpawnConfig::load(&self.path));
+    }
+}
+
+#[test]
+fn camera_spawns_at_the_configured_position() {
+    use crate::chunky::terrain::FlatTerrain;
+
+    let path = std::env::temp_dir()
+        .join(format!("talc_spawn_{}", std::process::id()))
+        .join("spawn.toml");
+    fs::create_dir_all(path.parent().expect("The spawn file is in a directory."))
+        .expect("The temp directory is writable.");
+    fs::write(&path, "position = [10.0, 50.0, -3.5]\n").expect("The temp directory is writable.");
+
+    let mut app = App::new();
+    app.add_plugins(SpawnConfigPlugin { path: path.clone() });
+    let spawn = *app.world().resource::<SpawnConfig>();
+    let terrain = FlatTerrain { surface_y: 70 };
+    assert_eq!(spawn.translation(&terrain), Vec3::new(10., 50., -3.5));
+
+    // the surface chunk spans y 64 to 95
+    let lifted = SpawnConfig {
+        above_terrain: true,
+        ..spawn
+    };
+    assert_eq!(lifted.translation(&terrain), Vec3::new(10., 96., -3.5));
+    let high = SpawnConfig {
+        position: Vec3::new(10., 500., -3.5),
+        ..lifted
+    };
+    assert_eq!(high.translation(&terrain), high.position);
+
+    fs::write(&path, "position = \"nowhere\"\n").expect("The temp directory is writable.");
+    assert_eq!(SpawnConfig::load(&path), SpawnConfig::default());
+
+    let _ = fs::remove_dir_all(path.parent().expect("The spawn file is in a directory."));
+}
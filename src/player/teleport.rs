@@ -0,0 +1,144 @@
+//! Debug teleport, for reproducing bugs that only show up far from the origin.
+//! P jumps every scanner to the next of the [`TeleportPresets`], [`TeleportTo`] jumps to any position.
+
+use bevy::prelude::*;
+
+use crate::chunky::async_chunkloader::AsyncChunkloader;
+use crate::position::{FloatingPosition, Position};
+
+use super::key_bindings::{Action, KeyBindings};
+use super::render_distance::{Scanner, scanner_chunk_position};
+
+/// Moves every scanner, and so the camera carrying it, to the position.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TeleportTo(pub Position);
+
+/// The positions P cycles through.
+#[derive(Resource, Clone, Debug)]
+pub struct TeleportPresets {
+    pub positions: Vec<Position>,
+    next: usize,
+}
+
+impl Default for TeleportPresets {
+    fn default() -> Self {
+        Self::new(vec![
+            Position::new(0, 200, 0),
+            // far enough for float precision to start showing in the meshes
+            Position::new(100_000, 200, 0),
+            Position::new(-1_000_000, 200, 1_000_000),
+        ])
+    }
+}
+
+impl TeleportPresets {
+    #[must_use]
+    pub const fn new(positions: Vec<Position>) -> Self {
+        Self { positions, next: 0 }
+    }
+}
+
+pub struct TeleportPlugin;
+
+impl Plugin for TeleportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TeleportTo>()
+            .init_resource::<TeleportPresets>()
+            .init_resource::<KeyBindings>()
+            .add_systems(Update, (teleport_keybind, teleport).chain());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn teleport_keybind(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut presets: ResMut<TeleportPresets>,
+    mut teleports: EventWriter<TeleportTo>,
+) {
+    if !key_bindings.just_pressed(&keys, Action::TeleportToNextPreset) || presets.positions.is_empty() {
+        return;
+    }
+    let position = presets.positions[presets.next % presets.positions.len()];
+    presets.next = (presets.next + 1) % presets.positions.len();
+    teleports.write(TeleportTo(position));
+}
+
+/// Moves the scanners and cancels the work queued for chunks the new area does not keep.
+///
+/// The previous scan position is kept on purpose. `detect_move` diffs the old area against the new one,
+/// which queues the old chunks for unloading and every new chunk for loading, even into ungenerated space.
+/// Worldgen tasks are only cancelled when their chunk is unloaded, so ones still running for the old area are dropped here.
+fn teleport(
+    mut teleports: EventReader<TeleportTo>,
+    mut scanners: Query<(&mut Scanner, &mut Transform)>,
+    mut chunkloader: ResMut<AsyncChunkloader>,
+) {
+    let Some(&TeleportTo(position)) = teleports.read().last() else {
+        return;
+    };
+    info!("Teleporting to {:?}.", position.0);
+
+    let translation = FloatingPosition::from(position).0;
+    for (mut scanner, mut transform) in &mut scanners {
+        transform.translation = translation;
+        scanner.forget_motion();
+
+        let (data_area, mesh_area) = scanner.retained_areas(scanner_chunk_position(translation));
+        scanner.unresolved_data_load.retain(|chunk_position| data_area.contains(chunk_position));
+        scanner.unresolved_mesh_load.retain(|chunk_position| mesh_area.contains(chunk_position));
+
+        let chunkloader = chunkloader.as_mut();
+        chunkloader.load_chunk_queue.retain(|chunk_position| data_area.contains(chunk_position));
+        chunkloader
+            .load_mesh_queue
+            .retain(|chunk_refs| mesh_area.contains(&chunk_refs.center_chunk_position));
+        chunkloader.worldgen_tasks.retain(|chunk_position| data_area.contains(chunk_position));
+        chunkloader.mesh_tasks.retain(|chunk_position| mesh_area.contains(chunk_position));
+    }
+}
+
+#[test]
+fn teleporting_into_ungenerated_space_converges() {
+    use std::sync::Arc;
+
+    use crate::{
+        chunky::{
+            async_chunkloader::AsyncChunkloaderPlugin,
+            chunk::{TestSky, init_test_block_registry, settle_chunk_loading},
+            terrain::Terrain,
+        },
+        player::render_distance::ScannerPlugin,
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        bevy::transform::TransformPlugin,
+        AsyncChunkloaderPlugin,
+        ScannerPlugin,
+        TeleportPlugin,
+    ))
+    .insert_resource(init_test_block_registry().clone())
+    .insert_resource(Terrain(Arc::new(TestSky)))
+    .init_resource::<ButtonInput<KeyCode>>();
+    let scanner = Scanner::new(4);
+    let (expected_far_area, _) = scanner.retained_areas(scanner_chunk_position(Vec3::new(100_000., 200., 0.)));
+    app.world_mut().spawn((scanner, Transform::default()));
+
+    let home = settle_chunk_loading(&mut app);
+    assert!(!home.is_empty());
+
+    app.world_mut().send_event(TeleportTo(Position::new(100_000, 200, 0)));
+    // the scanners only see the new position in the frame after the teleport
+    app.update();
+    let far = settle_chunk_loading(&mut app);
+    assert!(!far.is_empty());
+    assert!(far.is_disjoint(&home), "The old area is unloaded.");
+    assert!(far.is_subset(&expected_far_area));
+
+    // and back again, to the same chunks as before
+    app.world_mut().send_event(TeleportTo(Position::new(0, 0, 0)));
+    app.update();
+    assert_eq!(settle_chunk_loading(&mut app), home);
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::ops::{Add, Div, Mul, Sub};
 
 use bevy::prelude::*;
@@ -17,11 +19,33 @@ pub struct FloatingPosition(pub Vec3);
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Deref)]
 pub struct ChunkPosition(pub IVec3);
 
+/// A grid aligned position local to a chunk.
+/// The x, y, z components are each in the range `0..chunk::CHUNK_SIZE`
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Deref)]
+pub struct RelativePosition(pub IVec3);
+
 impl Position {
     #[must_use]
     pub const fn new(x: i32, y: i32, z: i32) -> Self {
         Self(IVec3 { x, y, z })
     }
+
+    /// Splits this position into the chunk containing it and the offset within that chunk.
+    /// Uses euclidean division so negative coordinates land in the correct chunk.
+    #[must_use]
+    pub const fn to_chunk_local(self) -> (ChunkPosition, RelativePosition) {
+        let chunk_position = ChunkPosition::new(
+            self.0.x.div_euclid(CHUNK_SIZE_I32),
+            self.0.y.div_euclid(CHUNK_SIZE_I32),
+            self.0.z.div_euclid(CHUNK_SIZE_I32),
+        );
+        let relative_position = RelativePosition::new(
+            self.0.x.rem_euclid(CHUNK_SIZE_I32),
+            self.0.y.rem_euclid(CHUNK_SIZE_I32),
+            self.0.z.rem_euclid(CHUNK_SIZE_I32),
+        );
+        (chunk_position, relative_position)
+    }
 }
 
 impl FloatingPosition {
@@ -29,6 +53,36 @@ impl FloatingPosition {
     pub const fn new(x: f32, y: f32, z: f32) -> Self {
         Self(Vec3 { x, y, z })
     }
+
+    /// Moves toward `target` by at most `max_delta` blocks, stopping exactly on it instead of overshooting.
+    #[must_use]
+    pub fn move_toward(self, target: Self, max_delta: f32) -> Self {
+        let to_target = target.0 - self.0;
+        let distance = to_target.length();
+        if distance <= max_delta || distance == 0. {
+            return target;
+        }
+        Self(self.0 + to_target / distance * max_delta)
+    }
+
+    /// Linear interpolation, `t` = 0 is `self` and `t` = 1 is `target`.
+    #[must_use]
+    pub fn lerp(self, target: Self, t: f32) -> Self {
+        Self(self.0.lerp(target.0, t))
+    }
+
+    /// The nearest grid point, for snapping things like placement cursors.
+    /// Halves round away from zero. Use `Position::from` for the voxel containing the point.
+    #[must_use]
+    pub fn round_to_position(self) -> Position {
+        Position(self.0.round().as_ivec3())
+    }
+
+    /// Each axis rounded up, the max corner of the voxels overlapping a point.
+    #[must_use]
+    pub fn ceil_to_position(self) -> Position {
+        Position(self.0.ceil().as_ivec3())
+    }
 }
 
 impl ChunkPosition {
@@ -38,13 +92,22 @@ impl ChunkPosition {
     }
 }
 
+impl RelativePosition {
+    #[must_use]
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self(IVec3 { x, y, z })
+    }
+}
+
 impl From<Position> for ChunkPosition {
     fn from(position: Position) -> Self {
-        Self(IVec3 {
-            x: position.0.x / CHUNK_SIZE_I32,
-            y: position.0.y / CHUNK_SIZE_I32,
-            z: position.0.z / CHUNK_SIZE_I32,
-        })
+        position.to_chunk_local().0
+    }
+}
+
+impl From<RelativePosition> for Position {
+    fn from(relative_position: RelativePosition) -> Self {
+        Self(relative_position.0)
     }
 }
 
@@ -58,6 +121,7 @@ impl From<ChunkPosition> for Position {
     }
 }
 
+/// The voxel containing the point. See [`FloatingPosition::round_to_position`] for the nearest grid point.
 impl From<FloatingPosition> for Position {
     fn from(position: FloatingPosition) -> Self {
         Self(IVec3 {
@@ -129,3 +193,116 @@ macro_rules! impl_arithmetic_ops {
 impl_arithmetic_ops!(Position);
 impl_arithmetic_ops!(ChunkPosition);
 impl_arithmetic_ops!(FloatingPosition);
+
+/// `Display` as `(x, y, z)` and `Ord` comparing x, then y, then z.
+macro_rules! impl_display_ord {
+    ($type:ty) => {
+        impl fmt::Display for $type {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "({}, {}, {})", self.0.x, self.0.y, self.0.z)
+            }
+        }
+
+        impl Ord for $type {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.to_array().cmp(&other.0.to_array())
+            }
+        }
+
+        impl PartialOrd for $type {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+    };
+}
+
+impl_display_ord!(Position);
+impl_display_ord!(ChunkPosition);
+
+#[test]
+fn chunk_local_round_trip() {
+    for z in -70..70 {
+        for y in -70..70 {
+            for x in -70..70 {
+                let position = Position::new(x, y, z);
+                let (chunk_position, relative_position) = position.to_chunk_local();
+                assert!(
+                    (0..CHUNK_SIZE_I32).contains(&relative_position.x)
+                        && (0..CHUNK_SIZE_I32).contains(&relative_position.y)
+                        && (0..CHUNK_SIZE_I32).contains(&relative_position.z),
+                    "relative position out of range for {position:?}: {relative_position:?}"
+                );
+                assert_eq!(
+                    Position::from(chunk_position) + Position::from(relative_position),
+                    position
+                );
+                assert_eq!(ChunkPosition::from(position), chunk_position);
+            }
+        }
+    }
+}
+
+#[test]
+fn ordering_is_total_and_matches_equality() {
+    let positions: Vec<ChunkPosition> = (0..27)
+        .map(|i| ChunkPosition::new(i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1))
+        .collect();
+    for a in &positions {
+        for b in &positions {
+            assert_eq!(a.cmp(b) == Ordering::Equal, a == b, "{a} vs {b}");
+            assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{a} vs {b}");
+        }
+    }
+
+    let mut sorted = positions;
+    sorted.sort();
+    assert!(sorted.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(sorted[0], ChunkPosition::new(-1, -1, -1));
+    assert_eq!(sorted[1], ChunkPosition::new(-1, -1, 0));
+    assert_eq!(sorted[26], ChunkPosition::new(1, 1, 1));
+
+    assert_eq!(Position::new(1, -2, 3).to_string(), "(1, -2, 3)");
+    assert!(Position::new(0, 5, 5) < Position::new(1, 0, 0));
+}
+
+#[test]
+fn move_toward_and_lerp() {
+    let start = FloatingPosition::new(0., 0., 0.);
+    let target = FloatingPosition::new(0., -4., 3.);
+
+    let step = start.move_toward(target, 1.);
+    assert!((step.0 - Vec3::new(0., -0.8, 0.6)).length() < 1e-6);
+
+    // max_delta exceeds the remaining distance of 5 blocks
+    assert_eq!(start.move_toward(target, 7.).0, target.0);
+    assert_eq!(target.move_toward(target, 1.).0, target.0);
+
+    assert_eq!(start.lerp(target, 0.).0, start.0);
+    assert_eq!(start.lerp(target, 0.5).0, Vec3::new(0., -2., 1.5));
+    assert_eq!(start.lerp(target, 1.).0, target.0);
+}
+
+#[test]
+fn floor_round_and_ceil_to_position() {
+    let convert = |value: f32| {
+        let position = FloatingPosition::new(value, value, value);
+        [
+            Position::from(position).x,
+            position.round_to_position().x,
+            position.ceil_to_position().x,
+        ]
+    };
+    // [floor, round, ceil]
+    assert_eq!(convert(2.4), [2, 2, 3]);
+    assert_eq!(convert(2.5), [2, 3, 3]);
+    assert_eq!(convert(2.6), [2, 3, 3]);
+    assert_eq!(convert(-2.4), [-3, -2, -2]);
+    assert_eq!(convert(-2.5), [-3, -3, -2]);
+    assert_eq!(convert(-2.6), [-3, -3, -2]);
+    assert_eq!(convert(3.0), [3, 3, 3]);
+
+    let position = FloatingPosition::new(0.4, -0.5, 1.6);
+    assert_eq!(position.round_to_position(), Position::new(0, -1, 2));
+    assert_eq!(position.ceil_to_position(), Position::new(1, 0, 2));
+}
@@ -0,0 +1,293 @@
+//! Loads the textures of every block prototype into a single texture array.
+//!
+//! Each textured block owns one layer of the array (see `BlockPrototype::texture_layer`).
+//! Quads carry their layer in `PackedQuad`, and the chunk shader repeats the texture
+//! once per voxel across greedy merged quads.
+//!
+//! The [`BlockPalette`] is bound next to the array, quads index it by block id.
+
+use bevy::{
+    asset::{LoadState, RenderAssetUsages},
+    ecs::system::{SystemParamItem, lifetimeless::SRes},
+    image::{ImageAddressMode, ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
+    prelude::*,
+    render::{
+        Render, RenderApp, RenderSystems,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, Extent3d,
+            SamplerBindingType, ShaderStages, StorageBuffer, TextureDimension, TextureFormat,
+            TextureSampleType, TextureViewDescriptor, TextureViewDimension,
+            binding_types::{sampler, storage_buffer_read_only, texture_2d_array},
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::GpuImage,
+    },
+};
+
+use crate::mod_manager::prototypes::{BlockPrototypes, Prototypes};
+
+/// Layers whose image failed to load are filled with this color.
+const MISSING_TEXTURE_RGBA: [u8; 4] = [255, 0, 255, 255];
+const BLOCK_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+pub struct BlockTexturesPlugin;
+
+impl Plugin for BlockTexturesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractResourcePlugin::<BlockTextureArray>::default());
+        app.add_plugins(ExtractResourcePlugin::<BlockPalette>::default());
+        app.add_systems(
+            Update,
+            (load_block_textures, build_block_palette).run_if(resource_added::<BlockPrototypes>),
+        );
+        app.add_systems(
+            Update,
+            build_block_texture_array.run_if(
+                resource_exists::<BlockTextureHandles>
+                    .and(not(resource_exists::<BlockTextureArray>)),
+            ),
+        );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<BlockTextureBindGroup>();
+        render_app.add_systems(
+            Render,
+            prepare_block_texture_bind_group.in_set(RenderSystems::PrepareBindGroups),
+        );
+    }
+}
+
+/// The individual block textures, indexed by texture layer.
+#[derive(Resource)]
+struct BlockTextureHandles(Vec<Handle<Image>>);
+
+/// Every block texture stacked into one array image.
+#[derive(Resource, Clone, ExtractResource)]
+pub struct BlockTextureArray(pub Handle<Image>);
+
+/// The emissive intensity of every block, indexed by block id (see `PackedQuad::with_palette_index`).
+/// The fragment shader multiplies the color of emissive quads by it instead of lighting them.
+#[derive(Resource, Clone, ExtractResource, Debug, PartialEq)]
+pub struct BlockPalette(pub Vec<f32>);
+
+impl BlockPalette {
+    #[must_use]
+    pub fn new(block_prototypes: &BlockPrototypes) -> Self {
+        Self(block_prototypes.iter_by_id().iter().map(|block| block.emissive).collect())
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn build_block_palette(mut commands: Commands, block_prototypes: Res<BlockPrototypes>) {
+    commands.insert_resource(BlockPalette::new(&block_prototypes));
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn load_block_textures(
+    mut commands: Commands,
+    block_prototypes: Res<BlockPrototypes>,
+    asset_server: Res<AssetServer>,
+) {
+    let mut textures: Vec<(u32, &str)> = block_prototypes
+        .iter()
+        .filter_map(|(_, block)| Some((block.texture_layer?, block.texture.as_deref()?)))
+        .collect();
+    textures.sort_by_key(|(layer, _)| *layer);
+
+    let handles = textures
+        .into_iter()
+        .map(|(_, path)| asset_server.load::<Image>(path.to_string()))
+        .collect();
+    commands.insert_resource(BlockTextureHandles(handles));
+}
+
+/// Waits for every block texture to finish loading, then stacks them into the texture array.
+/// All layers share the size of the first texture. Failed or mismatched textures are replaced.
+#[allow(clippy::needless_pass_by_value)]
+fn build_block_texture_array(
+    mut commands: Commands,
+    handles: Res<BlockTextureHandles>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let finished = handles.0.iter().all(|handle| {
+        matches!(
+            asset_server.load_state(handle),
+            LoadState::Loaded | LoadState::Failed(_)
+        )
+    });
+    if !finished {
+        return;
+    }
+
+    let layers: Vec<Option<Image>> = handles
+        .0
+        .iter()
+        .map(|handle| {
+            let image = images.get(handle)?;
+            let converted = image.convert(BLOCK_TEXTURE_FORMAT);
+            if converted.is_none() {
+                error!("Block texture {:?} has an unsupported format.", handle.path());
+            }
+            converted
+        })
+        .collect();
+
+    let size = layers
+        .iter()
+        .flatten()
+        .map(|image| image.texture_descriptor.size)
+        .next()
+        .unwrap_or(Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        });
+    let layer_pixels = (size.width * size.height) as usize;
+
+    let mut data = Vec::with_capacity(layer_pixels * MISSING_TEXTURE_RGBA.len() * layers.len());
+    for (layer, image) in layers.iter().enumerate() {
+        match image {
+            Some(image) if image.texture_descriptor.size == size => {
+                data.extend_from_slice(image.data.as_deref().unwrap_or_default());
+            }
+            _ => {
+                if image.is_some() {
+                    error!(
+                        "Block texture {:?} must be {}x{} to match the other block textures.",
+                        handles.0[layer].path(),
+                        size.width,
+                        size.height
+                    );
+                }
+                data.extend(MISSING_TEXTURE_RGBA.repeat(layer_pixels));
+            }
+        }
+    }
+
+    // The shader always samples the array, so it needs at least one layer even if no block is textured.
+    let layer_count = layers.len().max(1) as u32;
+    if layers.is_empty() {
+        data.extend(MISSING_TEXTURE_RGBA.repeat(layer_pixels));
+    }
+
+    let mut texture_array = Image::new(
+        Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: layer_count,
+        },
+        TextureDimension::D2,
+        data,
+        BLOCK_TEXTURE_FORMAT,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    texture_array.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..default()
+    });
+    // Repeat so greedy merged quads tile the texture once per voxel.
+    texture_array.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        mag_filter: ImageFilterMode::Nearest,
+        min_filter: ImageFilterMode::Nearest,
+        ..default()
+    });
+
+    commands.insert_resource(BlockTextureArray(images.add(texture_array)));
+}
+
+#[derive(Resource, Default)]
+pub(super) struct BlockTextureBindGroup {
+    palette: StorageBuffer<Vec<f32>>,
+    bind_group: Option<BindGroup>,
+}
+
+/// Block prototypes never change after loading, so the palette is uploaded once with the array.
+#[allow(clippy::needless_pass_by_value)]
+fn prepare_block_texture_bind_group(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    texture_array: Option<Res<BlockTextureArray>>,
+    palette: Option<Res<BlockPalette>>,
+    mut bind_group: ResMut<BlockTextureBindGroup>,
+) {
+    if bind_group.bind_group.is_some() {
+        return;
+    }
+    let Some(gpu_image) = texture_array.and_then(|texture_array| gpu_images.get(&texture_array.0))
+    else {
+        return;
+    };
+    let Some(palette) = palette else {
+        return;
+    };
+
+    let BlockTextureBindGroup { palette: buffer, bind_group } = bind_group.as_mut();
+    buffer.set(palette.0.clone());
+    buffer.write_buffer(&render_device, &render_queue);
+    let Some(binding) = buffer.binding() else {
+        return;
+    };
+    *bind_group = Some(render_device.create_bind_group(
+        Some("block texture bind group"),
+        &texture_bind_group_layout(&render_device),
+        &BindGroupEntries::sequential((&gpu_image.texture_view, &gpu_image.sampler, binding)),
+    ));
+}
+
+pub(super) fn texture_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+    render_device.create_bind_group_layout(
+        Some("block texture bind group layout"),
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::FRAGMENT,
+            (
+                texture_2d_array(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+                storage_buffer_read_only::<Vec<f32>>(false),
+            ),
+        ),
+    )
+}
+
+/// Binds the block texture array and palette. Chunks are skipped until both have been uploaded.
+pub(super) struct SetBlockTextureBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetBlockTextureBindGroup<I> {
+    type Param = SRes<BlockTextureBindGroup>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        _entity: Option<()>,
+        bind_group: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = bind_group.into_inner().bind_group.as_ref() else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+#[test]
+fn palette_holds_the_emissive_intensity_by_block_id() {
+    let block_prototypes = crate::chunky::chunk::init_test_block_registry();
+    let palette = BlockPalette::new(block_prototypes);
+
+    assert_eq!(palette.0.len(), block_prototypes.iter().count());
+    for (_, block) in block_prototypes.iter() {
+        assert_eq!(palette.0[usize::from(block.id)].to_bits(), block.emissive.to_bits());
+    }
+}
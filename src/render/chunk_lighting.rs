@@ -0,0 +1,221 @@
+//! Material parameters shared by every chunk, tweakable live for art direction.
+//!
+//! L picks the parameter, `.` and `,` raise and lower it. The chunk shader reads them from a single
+//! uniform, so every chunk already on screen picks up the change on the next frame.
+
+use bevy::{
+    ecs::system::{SystemParamItem, lifetimeless::SRes},
+    prelude::*,
+    render::{
+        Render, RenderApp, RenderSystems,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+        render_resource::{
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, ShaderStages,
+            ShaderType, UniformBuffer, binding_types::uniform_buffer,
+        },
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+
+use crate::player::key_bindings::{Action, KeyBindings};
+
+/// How much one key press changes the selected parameter.
+pub const LIGHTING_STEP: f32 = 0.05;
+
+/// Mirrors the parameters of the same name on `StandardMaterial`. All of them range from 0 to 1.
+#[derive(Resource, ExtractResource, ShaderType, Clone, Copy, Debug, PartialEq)]
+pub struct ChunkLighting {
+    /// Specular intensity of non-metals. 0.5 is a 4% reflectance, which fits most blocks.
+    pub reflectance: f32,
+    pub perceptual_roughness: f32,
+    pub metallic: f32,
+}
+
+impl Default for ChunkLighting {
+    fn default() -> Self {
+        Self {
+            reflectance: 0.5,
+            perceptual_roughness: 0.9,
+            metallic: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightingParameter {
+    #[default]
+    Reflectance,
+    PerceptualRoughness,
+    Metallic,
+}
+
+impl LightingParameter {
+    #[must_use]
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Reflectance => Self::PerceptualRoughness,
+            Self::PerceptualRoughness => Self::Metallic,
+            Self::Metallic => Self::Reflectance,
+        }
+    }
+
+    const fn value_mut(self, lighting: &mut ChunkLighting) -> &mut f32 {
+        match self {
+            Self::Reflectance => &mut lighting.reflectance,
+            Self::PerceptualRoughness => &mut lighting.perceptual_roughness,
+            Self::Metallic => &mut lighting.metallic,
+        }
+    }
+}
+
+/// The parameter `.` and `,` adjust.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SelectedLightingParameter(pub LightingParameter);
+
+pub struct ChunkLightingPlugin;
+
+impl Plugin for ChunkLightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkLighting>()
+            .init_resource::<SelectedLightingParameter>()
+            .init_resource::<KeyBindings>()
+            .add_plugins(ExtractResourcePlugin::<ChunkLighting>::default())
+            .add_systems(Update, chunk_lighting_keybinds);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<ChunkLightingBindGroup>();
+        render_app.add_systems(
+            Render,
+            prepare_chunk_lighting_bind_group.in_set(RenderSystems::PrepareBindGroups),
+        );
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn chunk_lighting_keybinds(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut selected: ResMut<SelectedLightingParameter>,
+    mut lighting: ResMut<ChunkLighting>,
+) {
+    if key_bindings.just_pressed(&keys, Action::CycleLightingParameter) {
+        selected.0 = selected.0.next();
+        info!("Adjusting chunk {:?}.", selected.0);
+    }
+
+    let raise = key_bindings.just_pressed(&keys, Action::RaiseLightingParameter);
+    let lower = key_bindings.just_pressed(&keys, Action::LowerLightingParameter);
+    if raise == lower {
+        return;
+    }
+    let step = if raise { LIGHTING_STEP } else { -LIGHTING_STEP };
+    let mut adjusted = *lighting;
+    let value = selected.0.value_mut(&mut adjusted);
+    *value = (*value + step).clamp(0., 1.);
+    // at the limit nothing changes, so nothing is uploaded either
+    if lighting.set_if_neq(adjusted) {
+        info!("Chunk lighting: {adjusted:?}");
+    }
+}
+
+#[derive(Resource, Default)]
+pub(super) struct ChunkLightingBindGroup {
+    buffer: UniformBuffer<ChunkLighting>,
+    bind_group: Option<BindGroup>,
+}
+
+/// Uploads the parameters whenever they change. The buffer keeps its size, so the bind group stays valid.
+#[allow(clippy::needless_pass_by_value)]
+fn prepare_chunk_lighting_bind_group(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    lighting: Option<Res<ChunkLighting>>,
+    mut bind_group: ResMut<ChunkLightingBindGroup>,
+) {
+    let Some(lighting) = lighting else {
+        return;
+    };
+    if bind_group.bind_group.is_some() && !lighting.is_changed() {
+        return;
+    }
+    let ChunkLightingBindGroup { buffer, bind_group } = bind_group.as_mut();
+    buffer.set(*lighting);
+    buffer.write_buffer(&render_device, &render_queue);
+    let Some(binding) = buffer.binding() else {
+        return;
+    };
+    if bind_group.is_none() {
+        *bind_group = Some(render_device.create_bind_group(
+            Some("chunk lighting bind group"),
+            &lighting_bind_group_layout(&render_device),
+            &BindGroupEntries::single(binding),
+        ));
+    }
+}
+
+pub(super) fn lighting_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+    render_device.create_bind_group_layout(
+        Some("chunk lighting bind group layout"),
+        &BindGroupLayoutEntries::single(ShaderStages::FRAGMENT, uniform_buffer::<ChunkLighting>(false)),
+    )
+}
+
+/// Binds the [`ChunkLighting`] uniform. Chunks are skipped until it has been uploaded.
+pub(super) struct SetChunkLightingBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetChunkLightingBindGroup<I> {
+    type Param = SRes<ChunkLightingBindGroup>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        _entity: Option<()>,
+        bind_group: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(bind_group) = bind_group.into_inner().bind_group.as_ref() else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+#[test]
+fn lighting_keys_select_and_clamp_parameters() {
+    let mut app = App::new();
+    app.init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<KeyBindings>()
+        .init_resource::<ChunkLighting>()
+        .init_resource::<SelectedLightingParameter>()
+        .add_systems(Update, chunk_lighting_keybinds);
+
+    let mut press = |action: Action| {
+        let key = action.default_keys()[0];
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        keys.clear();
+        keys.press(key);
+        app.update();
+        *app.world().resource::<ChunkLighting>()
+    };
+
+    let lowered = press(Action::LowerLightingParameter);
+    assert!((lowered.reflectance - (0.5 - LIGHTING_STEP)).abs() < 1e-6);
+
+    press(Action::CycleLightingParameter);
+    press(Action::CycleLightingParameter);
+    for _ in 0..30 {
+        press(Action::RaiseLightingParameter);
+    }
+    let raised = press(Action::RaiseLightingParameter);
+    assert!((raised.metallic - 1.).abs() < f32::EPSILON);
+    assert!((raised.perceptual_roughness - ChunkLighting::default().perceptual_roughness).abs() < f32::EPSILON);
+    assert!((raised.reflectance - lowered.reflectance).abs() < f32::EPSILON);
+}
@@ -7,7 +7,11 @@
 //! implementation using bevy's low level rendering api.
 //! It's generally recommended to try the built-in instancing before going with this approach.
 
-use std::sync::{Arc, OnceLock};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, OnceLock},
+};
 
 use bevy::{
     prelude::*,
@@ -21,7 +25,22 @@ use bevy::{
 };
 use bytemuck::{Pod, Zeroable};
 
-use crate::position::{ChunkPosition, Position};
+use crate::{
+    chunky::{chunk::CHUNK_SIZE, face_direction::FaceDir},
+    mod_manager::prototypes::{BlockPrototype, BlockShape},
+    position::{ChunkPosition, Position},
+};
+
+/// Bits of each position and strech field in `PackedQuad::packed_u32`.
+/// The budget is 3 * 5 position + 3 normal + 2 unused + 2 * 5 strech + 2 shape = 32 bits.
+pub const QUAD_AXIS_BITS: u32 = 5;
+
+// A larger chunk would silently wrap quad positions into the wrong voxel.
+// chunk.wgsl also multiplies chunk positions by 32.
+const _: () = assert!(
+    CHUNK_SIZE == 1 << QUAD_AXIS_BITS,
+    "CHUNK_SIZE must fit the PackedQuad position fields. Widen them and chunk.wgsl to change it."
+);
 
 /// In talc we draw quads instead of triangles.
 /// This struct repersents bit packed data for each quad ready to be sent to the GPU.
@@ -34,31 +53,125 @@ pub struct PackedQuad {
     /// y: 00000 (10)
     /// z: 00000 (15)
     /// normal: 000 (18)
-    /// ao: 00 (20)
+    /// unused: 00 (20)
     /// x strech: 00000 (25)
     /// y strech: 00000 (30)
-    /// 2 bits are free :)
+    /// shape: 00 (32), see `BlockShape::shape_index`
     packed_u32: u32,
     /// The color of the quad.
     color: u32,
+    /// FORMAT
+    /// texture: 16 bits, the block texture array layer sampled by the quad, offset by one.
+    ///     0 means the quad is untextured and drawn with `color`.
+    /// unused: 16 bits
+    texture: u32,
+    /// FORMAT
+    /// corner ao: 0000 0000 (8), 2 bits per corner, interpolated across the quad by the shader.
+    ///     Corner `u + 2 * v` of the unit quad is at bits `2 * corner`, 0 is unoccluded and 3 fully occluded.
+    /// palette index: 16 bits, the id of the block, see `with_palette_index` and `BlockPalette`.
+    /// unused: 8 bits
+    shading: u32,
+}
+
+/// Why [`PackedQuad::try_new`] rejected a quad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackedQuadError {
+    /// Each axis must be in `0..CHUNK_SIZE`.
+    Position(Position),
+    /// Must be a `FaceDir::normal_index`, 0..6.
+    Normal(u32),
+    /// 2 bits per corner, 0..=255.
+    CornerAo(u32),
+    /// Each strech must be in `1..=CHUNK_SIZE`.
+    Strech { x_strech: u32, y_strech: u32 },
+    /// Must be below 65535, the texture field stores it offset by one.
+    TextureLayer(u32),
+}
+
+impl fmt::Display for PackedQuadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Position(position) => {
+                write!(f, "quad position {position} is outside of the chunk, expected 0..=31 on each axis")
+            }
+            Self::Normal(normal) => write!(f, "quad normal {normal} is out of range, expected 0..=5"),
+            Self::CornerAo(corner_ao) => {
+                write!(f, "quad corner ao {corner_ao} is out of range, expected 0..=255")
+            }
+            Self::Strech { x_strech, y_strech } => write!(
+                f,
+                "quad strech ({x_strech}, {y_strech}) is out of range, expected 1..=32 on each axis"
+            ),
+            Self::TextureLayer(layer) => {
+                write!(f, "quad texture layer {layer} is out of range, expected 0..65535")
+            }
+        }
+    }
 }
 
+impl std::error::Error for PackedQuadError {}
+
 impl PackedQuad {
+    /// Like [`PackedQuad::new`], but checks every field instead of only asserting in debug builds.
+    /// Out of range fields would otherwise wrap into neighbouring bits and draw the quad in the wrong place.
+    ///
+    /// # Errors
+    /// If any field does not fit its bits.
+    pub fn try_new(
+        position: Position,
+        normal: u32,
+        corner_ao: u32,
+        x_strech: u32,
+        y_strech: u32,
+        color: u32,
+        texture_layer: Option<u32>,
+    ) -> Result<Self, PackedQuadError> {
+        let in_chunk = |axis: i32| (0..CHUNK_SIZE as i32).contains(&axis);
+        if !(in_chunk(position.x) && in_chunk(position.y) && in_chunk(position.z)) {
+            return Err(PackedQuadError::Position(position));
+        }
+        if normal >= 6 {
+            return Err(PackedQuadError::Normal(normal));
+        }
+        if corner_ao > 0xFF {
+            return Err(PackedQuadError::CornerAo(corner_ao));
+        }
+        let strech = 1..=CHUNK_SIZE as u32;
+        if !(strech.contains(&x_strech) && strech.contains(&y_strech)) {
+            return Err(PackedQuadError::Strech { x_strech, y_strech });
+        }
+        if let Some(layer) = texture_layer.filter(|&layer| layer >= 0xFFFF) {
+            return Err(PackedQuadError::TextureLayer(layer));
+        }
+
+        Ok(Self::new(
+            position,
+            normal,
+            corner_ao,
+            x_strech,
+            y_strech,
+            color,
+            texture_layer,
+        ))
+    }
+
+    /// Packs a quad without checking its fields in release builds.
+    /// Use [`PackedQuad::try_new`] when the fields are not known to be valid.
     #[inline]
     #[must_use]
     pub fn new(
         position: Position,
         normal: u32,
-        _ao: u32,
+        corner_ao: u32,
         x_strech: u32,
         y_strech: u32,
         color: u32,
+        texture_layer: Option<u32>,
     ) -> PackedQuad {
         let x = position.x;
         let y = position.y;
         let z = position.z;
 
-        let ao = 0; // todo
         let x_strech = x_strech - 1;
         let y_strech = y_strech - 1;
 
@@ -68,23 +181,123 @@ impl PackedQuad {
             debug_assert!(0 <= position.y && position.y < 32, "y position out of range. expected 0..=31, got {y}");
             debug_assert!(0 <= position.z && position.z < 32, "z position out of range. expected 0..=31, got {z}");
             debug_assert!(normal < 6, "normal out of range. expected 0..=6, got {normal}");
-            debug_assert!(ao < 4, "ao out of range. expected 0..=3, got {ao}");
+            debug_assert!(corner_ao <= 0xFF, "corner ao out of range. expected 0..=255, got {corner_ao}");
             debug_assert!(x_strech < 32, "x strech out of range. expected 0..=31, got {x_strech}");
             debug_assert!(y_strech < 32, "y strech out of range. expected 0..=31, got {y_strech}");
+            debug_assert!(texture_layer.is_none_or(|layer| layer < 0xFFFF), "texture layer out of range. expected 0..65535, got {texture_layer:?}");
         }
         
         let packed_u32: u32 = x as u32
             | ((y as u32) << 5u32)
             | ((z as u32) << 10u32)
             | (normal << 15u32)
-            | (ao << 18u32)
             | (x_strech << 20u32)
             | (y_strech << 25u32);
         
-        Self { packed_u32, color }
+        let texture = texture_layer.map_or(0, |layer| layer + 1);
+
+        Self {
+            packed_u32,
+            color,
+            texture,
+            shading: corner_ao,
+        }
+    }
+
+    /// The voxel the quad starts at, relative to its chunk.
+    #[must_use]
+    pub const fn position(&self) -> Position {
+        Position::new(
+            (self.packed_u32 & 0b11111) as i32,
+            ((self.packed_u32 >> 5u32) & 0b11111) as i32,
+            ((self.packed_u32 >> 10u32) & 0b11111) as i32,
+        )
+    }
+
+    /// How many voxels the quad covers along its x and y.
+    #[must_use]
+    pub const fn strech(&self) -> (u32, u32) {
+        (((self.packed_u32 >> 20u32) & 0b11111) + 1, ((self.packed_u32 >> 25u32) & 0b11111) + 1)
+    }
+
+    /// See `FaceDir::normal_index`.
+    #[must_use]
+    pub const fn normal(&self) -> u32 {
+        (self.packed_u32 >> 15u32) & 0b111
+    }
+
+    /// The direction the quad faces, decoded from [`PackedQuad::normal`].
+    /// `None` only for a normal past the 6 directions, which `try_new` rejects.
+    #[must_use]
+    pub const fn face_dir(&self) -> Option<FaceDir> {
+        FaceDir::from_normal_index(self.normal())
+    }
+
+    /// See `BlockShape::shape_index`.
+    #[must_use]
+    pub const fn shape(&self) -> u32 {
+        self.packed_u32 >> 30u32
+    }
+
+    /// The ambient occlusion of each corner, 2 bits per corner.
+    #[must_use]
+    pub const fn corner_ao(&self) -> u32 {
+        self.shading & 0xFF
+    }
+
+    /// The block palette entry the shader looks up, see [`PackedQuad::with_palette_index`].
+    #[must_use]
+    pub const fn palette_index(&self) -> u16 {
+        (self.shading >> 8u32) as u16
+    }
+
+    /// Points the quad at the palette entry of block `id`, which holds its emissive intensity.
+    /// Quads of every block must set it, since entry 0 is air.
+    #[inline]
+    #[must_use]
+    pub fn with_palette_index(mut self, id: u16) -> Self {
+        self.shading |= u32::from(id) << 8u32;
+        self
+    }
+
+    /// Shrinks the quad to the block shape in the shader.
+    /// Only unmerged quads may be shaped, since the shape is applied to a single voxel.
+    #[inline]
+    #[must_use]
+    pub const fn with_shape(mut self, shape: BlockShape) -> Self {
+        self.packed_u32 |= shape.shape_index() << 30u32;
+        self
+    }
+}
+
+/// Chunk quads are split into layers, each drawn with its own pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChunkLayer {
+    Opaque,
+    /// Drawn from both sides, fragments below half alpha are discarded instead of blended.
+    Cutout,
+    /// Alpha blended and sorted back to front with other chunks.
+    /// Faces behind translucent blocks are kept, only faces between two of the same translucent block are culled.
+    Alpha,
+}
+
+impl ChunkLayer {
+    /// Cross shaped blocks are alpha tested, other blocks with a translucent color are alpha blended.
+    #[must_use]
+    pub fn of(block: &BlockPrototype) -> Self {
+        if block.shape == BlockShape::Cross {
+            Self::Cutout
+        } else if block.color.alpha() < 1. {
+            Self::Alpha
+        } else {
+            Self::Opaque
+        }
     }
 }
 
+/// The meshes of one chunk by layer. Layers without quads are left out.
+pub type ChunkLayers = BTreeMap<ChunkLayer, RenderableChunk>;
+
 /// Note the [`ExtractComponent`] trait implementation: this is necessary to
 /// tell Bevy that this object should be pulled into the render world. Also note
 /// the `on_add` hook, which is needed to tell Bevy's `check_visibility` system
@@ -95,14 +308,35 @@ impl PackedQuad {
 pub struct RenderableChunk(Arc<ChunkMaterial>);
 
 impl RenderableChunk {
-    pub fn new(quads: Vec<PackedQuad>, chunk_position: ChunkPosition) -> Self {
+    pub fn new(quads: Vec<PackedQuad>, chunk_position: ChunkPosition, layer: ChunkLayer) -> Self {
         RenderableChunk(Arc::new(ChunkMaterial {
-            quads,
+            quads: Arc::new(ChunkQuads {
+                quads,
+                instance_buffer: OnceLock::new(),
+            }),
+            chunk_position,
+            layer,
+            baked: OnceLock::new(),
+        }))
+    }
+
+    /// The same quads drawn at another chunk. Both share one instance buffer on the GPU.
+    #[must_use]
+    pub fn at(&self, chunk_position: ChunkPosition) -> Self {
+        Self(Arc::new(ChunkMaterial {
+            quads: self.0.quads.clone(),
             chunk_position,
+            layer: self.0.layer,
             baked: OnceLock::new(),
         }))
     }
 
+    /// Whether both chunks draw from the same instance buffer.
+    #[must_use]
+    pub fn shares_quads_with(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.quads, &other.0.quads)
+    }
+
     #[inline]
     pub fn render<'w>(
         &'w self,
@@ -115,18 +349,53 @@ impl RenderableChunk {
     pub fn chunk_position(&self) -> ChunkPosition {
         self.0.chunk_position
     }
+
+    #[must_use]
+    pub fn layer(&self) -> ChunkLayer {
+        self.0.layer
+    }
+
+    #[must_use]
+    pub fn quads(&self) -> &[PackedQuad] {
+        &self.0.quads
+    }
 }
 
 struct BakedChunkMaterial {
-    instance_buffer: Buffer,
-    instance_buffer_length: usize,
     uniform_bind_group: BindGroup,
     simple_quad: SimpleQuad,
 }
 
-struct ChunkMaterial {
+/// Quads are relative to their chunk, so chunks which mesh identically can share them.
+struct ChunkQuads {
     quads: Vec<PackedQuad>,
+    instance_buffer: OnceLock<Buffer>,
+}
+
+impl ChunkQuads {
+    fn instance_buffer(&self, render_device: &RenderDevice) -> &Buffer {
+        self.instance_buffer.get_or_init(|| {
+            render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("chunk per-instance data buffer"),
+                contents: bytemuck::cast_slice(&self.quads),
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            })
+        })
+    }
+}
+
+impl std::ops::Deref for ChunkQuads {
+    type Target = [PackedQuad];
+
+    fn deref(&self) -> &Self::Target {
+        &self.quads
+    }
+}
+
+struct ChunkMaterial {
+    quads: Arc<ChunkQuads>,
     chunk_position: ChunkPosition,
+    layer: ChunkLayer,
     baked: OnceLock<BakedChunkMaterial>,
 }
 
@@ -134,12 +403,6 @@ impl ChunkMaterial {
     #[inline]
     fn bake(&self, render_device: &RenderDevice) -> &BakedChunkMaterial {
         self.baked.get_or_init(|| {
-            let instance_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-                label: Some("chunk per-instance data buffer"),
-                contents: bytemuck::cast_slice(&self.quads),
-                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            });
-            
             let uniform_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
                 label: Some("chunk uniform buffer"),
                 contents: bytemuck::cast_slice(&self.chunk_position.to_array()),
@@ -160,9 +423,7 @@ impl ChunkMaterial {
             );
 
             BakedChunkMaterial {
-                instance_buffer,
                 uniform_bind_group,
-                instance_buffer_length: self.quads.len(),
                 simple_quad: SimpleQuad::new(render_device),
             }
         })
@@ -171,12 +432,11 @@ impl ChunkMaterial {
     #[inline]
     fn render<'w>(&'w self, render_device: &RenderDevice, render_pass: &mut TrackedRenderPass<'w>) {
         let BakedChunkMaterial {
-            instance_buffer,
-            instance_buffer_length,
             uniform_bind_group,
             simple_quad: simple_quad_index_buffer,
         } = self.bake(render_device);
-        let instance_buffer_length = *instance_buffer_length as u32;
+        let instance_buffer = self.quads.instance_buffer(render_device);
+        let instance_buffer_length = self.quads.len() as u32;
 
         render_pass.set_index_buffer(
             simple_quad_index_buffer.index_buffer.slice(..),
@@ -243,3 +503,46 @@ impl SimpleQuad {
         }
     }
 }
+
+#[test]
+fn palette_index_keeps_texture_and_ao() {
+    let quad = PackedQuad::new(Position::new(1, 2, 3), 3, 0b1001_0110, 1, 1, 0, Some(700));
+    assert_eq!(quad.face_dir(), Some(FaceDir::Up));
+    assert_eq!(quad.texture, 701);
+    assert_eq!(quad.palette_index(), 0);
+
+    let indexed = quad.with_palette_index(u16::MAX);
+    assert_eq!(indexed.palette_index(), u16::MAX);
+    assert_eq!(indexed.corner_ao(), 0b1001_0110);
+    assert_eq!(indexed.texture, 701);
+    assert_eq!(indexed.shading >> 24u32, 0);
+}
+
+#[test]
+fn try_new_rejects_each_out_of_range_field() {
+    let position = Position::new(31, 0, 31);
+    let try_new = |position, normal, corner_ao, x_strech, y_strech, texture_layer| {
+        PackedQuad::try_new(position, normal, corner_ao, x_strech, y_strech, 0, texture_layer)
+            .map(|quad| quad.packed_u32)
+    };
+
+    assert_eq!(
+        try_new(position, 5, 0xFF, 32, 1, Some(0xFFFE)),
+        Ok(PackedQuad::new(position, 5, 0xFF, 32, 1, 0, Some(0xFFFE)).packed_u32)
+    );
+    for position in [Position::new(32, 0, 0), Position::new(0, -1, 0), Position::new(0, 0, 32)] {
+        assert_eq!(try_new(position, 0, 0, 1, 1, None), Err(PackedQuadError::Position(position)));
+    }
+    assert_eq!(try_new(position, 6, 0, 1, 1, None), Err(PackedQuadError::Normal(6)));
+    assert_eq!(try_new(position, 0, 0x100, 1, 1, None), Err(PackedQuadError::CornerAo(0x100)));
+    for (x_strech, y_strech) in [(0, 1), (1, 0), (33, 1), (1, 33)] {
+        assert_eq!(
+            try_new(position, 0, 0, x_strech, y_strech, None),
+            Err(PackedQuadError::Strech { x_strech, y_strech })
+        );
+    }
+    assert_eq!(
+        try_new(position, 0, 0, 1, 1, Some(0xFFFF)),
+        Err(PackedQuadError::TextureLayer(0xFFFF))
+    );
+}
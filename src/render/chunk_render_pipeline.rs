@@ -1,4 +1,5 @@
 use bevy::{
+    platform::collections::HashSet,
     core_pipeline::core_3d::{Transparent3d, CORE_3D_DEPTH_FORMAT},
     ecs::system::{
         lifetimeless::{Read, SRes}, SystemParamItem
@@ -6,12 +7,13 @@ use bevy::{
     pbr::{MeshPipeline, MeshPipelineKey, MeshPipelineViewLayoutKey, SetMeshViewBindGroup},
     prelude::*,
     render::{
-        extract_component::ExtractComponentPlugin, mesh::{PrimitiveTopology, VertexBufferLayout}, render_phase::{
+        extract_component::ExtractComponentPlugin, extract_resource::{ExtractResource, ExtractResourcePlugin}, mesh::{PrimitiveTopology, VertexBufferLayout}, render_phase::{
             AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
             RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
         }, render_resource::{
-            BindGroupLayout, ColorTargetState, ColorWrites, CompareFunction, DepthStencilState,
-            Face, FragmentState, MultisampleState, PipelineCache, PolygonMode,
+            BindGroupLayout, BlendState, CachedPipelineState, CachedRenderPipelineId, ColorTargetState,
+            ColorWrites, CompareFunction, DepthStencilState, Face, FragmentState,
+            MultisampleState, PipelineCache, PolygonMode,
             PrimitiveState, RenderPipelineDescriptor, SpecializedRenderPipeline,
             SpecializedRenderPipelines, TextureFormat, VertexAttribute, VertexFormat, VertexState,
             VertexStepMode,
@@ -19,9 +21,22 @@ use bevy::{
     },
 };
 
-use super::chunk_material::{RenderableChunk, bind_group_layout, PackedQuad};
+use super::block_textures::{BlockTexturesPlugin, SetBlockTextureBindGroup, texture_bind_group_layout};
+use super::chunk_lighting::{ChunkLightingPlugin, SetChunkLightingBindGroup, lighting_bind_group_layout};
+use super::chunk_material::{ChunkLayer, RenderableChunk, bind_group_layout, PackedQuad};
 
-const SHADER_ASSET_PATH: &str = "shaders/chunk.wgsl";
+pub const SHADER_ASSET_PATH: &str = "shaders/chunk.wgsl";
+
+/// Asset path of the shader chunks are drawn with. Mods can replace it, see `mod_loader::chunk_shader_override`.
+/// A shader which fails to load or compile falls back to [`SHADER_ASSET_PATH`].
+#[derive(Resource, ExtractResource, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkShaderPath(pub String);
+
+impl Default for ChunkShaderPath {
+    fn default() -> Self {
+        Self(SHADER_ASSET_PATH.to_string())
+    }
+}
 
 // When writing custom rendering code it's generally recommended to use a plugin.
 // The main reason for this is that it gives you access to the finish() hook
@@ -30,6 +45,10 @@ pub struct ChunkRenderPipelinePlugin;
 impl Plugin for ChunkRenderPipelinePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ExtractComponentPlugin::<RenderableChunk>::default()); // TODO
+        app.add_plugins(BlockTexturesPlugin);
+        app.add_plugins(ChunkLightingPlugin);
+        app.init_resource::<ChunkShaderPath>();
+        app.add_plugins(ExtractResourcePlugin::<ChunkShaderPath>::default());
 
         // We make sure to add these to the render app, not the main app.
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
@@ -41,16 +60,24 @@ impl Plugin for ChunkRenderPipelinePlugin {
         render_app.add_systems(
             Render,
             (
-                queue_custom_render_pipeline.in_set(RenderSystems::Queue),
+                (
+                    apply_chunk_shader.run_if(resource_changed::<ChunkShaderPath>),
+                    queue_custom_render_pipeline,
+                )
+                    .chain()
+                    .in_set(RenderSystems::Queue),
                 //prepare_instance_buffers.in_set(RenderSystems::PrepareResources),
             ),
         );
     }
 
     fn finish(&self, app: &mut App) {
+        let shader_path = app.world().resource::<ChunkShaderPath>().clone();
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
         };
+        // only extracted from the first frame on, the pipeline is built before that
+        render_app.insert_resource(shader_path);
         // Creating this pipeline needs the RenderDevice and RenderQueue
         // which are only available once rendering plugins are initialized.
         render_app.init_resource::<CustomPipeline>();
@@ -59,15 +86,29 @@ impl Plugin for ChunkRenderPipelinePlugin {
 
 /// A render-world system that enqueues the entity with custom rendering into
 /// the opaque render phases of each view.
+///
+/// The shader is hot reloaded when the `file_watcher` feature of bevy is enabled.
+/// `PipelineCache` recompiles every pipeline using the modified shader under the same
+/// `CachedRenderPipelineId`, so the ids cached by `SpecializedRenderPipelines` stay valid.
+#[allow(clippy::too_many_arguments)]
 fn queue_custom_render_pipeline(
     transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
-    custom_pipeline: Res<CustomPipeline>,
+    mut custom_pipeline: ResMut<CustomPipeline>,
+    asset_server: Res<AssetServer>,
     mut pipelines: ResMut<SpecializedRenderPipelines<CustomPipeline>>,
     pipeline_cache: Res<PipelineCache>,
     mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
     views: Query<(&RenderVisibleEntities, &ExtractedView, &Msaa)>,
     material_meshes: Query<(Entity, &MainEntity, &RenderableChunk)>,
+    mut failed_pipelines: Local<HashSet<CachedRenderPipelineId>>,
 ) {
+    if custom_pipeline.shader_path != SHADER_ASSET_PATH && asset_server.load_state(&custom_pipeline.shader_handle).is_failed() {
+        error!("Chunk shader {} failed to load, falling back to {SHADER_ASSET_PATH}.", custom_pipeline.shader_path);
+        custom_pipeline.set_shader(&asset_server, SHADER_ASSET_PATH);
+        *pipelines = default();
+        return;
+    }
+
     // Get the id for our custom draw function
     let draw_custom = transparent_3d_draw_functions.read().id::<DrawCustom>();
 
@@ -85,17 +126,34 @@ fn queue_custom_render_pipeline(
 
         let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
         let rangefinder = view.rangefinder3d();
-        for (render_entity, visible_entity, renderable_chunk) in &material_meshes // TODO: frustrum culling. see https://github.com/bevyengine/bevy/blob/19ee692f9621f89f305096f423507e925b748b9a/examples/shader/specialized_mesh_pipeline.rs#L353
-        {
-            // Specialize the key for the current mesh entity
-            // For this example we only specialize based on the mesh topology
-            // but you could have more complex keys and that's where you'd need to create those keys
-            let key = view_key
-                | MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList);
 
-            // Finally, we can specialize the pipeline based on the key
-            let pipeline = pipelines.specialize(&pipeline_cache, &custom_pipeline, key);
+        // Every chunk shares the same topology, so the pipeline only depends on the view and the layer.
+        let mesh_key = view_key | MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList);
+        let mut failed = false;
+        let mut layer_pipeline = |layer| {
+            let pipeline = pipelines.specialize(&pipeline_cache, &custom_pipeline, ChunkPipelineKey { mesh_key, layer });
+            failed |= report_pipeline_error(&pipeline_cache, pipeline, &mut failed_pipelines);
+            pipeline
+        };
+        let opaque_pipeline = layer_pipeline(ChunkLayer::Opaque);
+        let cutout_pipeline = layer_pipeline(ChunkLayer::Cutout);
+        let alpha_pipeline = layer_pipeline(ChunkLayer::Alpha);
+
+        // a broken mod shader is not hot reloaded into working order, unlike the default one being edited
+        if failed && custom_pipeline.shader_path != SHADER_ASSET_PATH {
+            error!("Chunk shader {} failed to compile, falling back to {SHADER_ASSET_PATH}.", custom_pipeline.shader_path);
+            custom_pipeline.set_shader(&asset_server, SHADER_ASSET_PATH);
+            *pipelines = default();
+            return;
+        }
 
+        for (render_entity, visible_entity, renderable_chunk) in &material_meshes // TODO: frustrum culling. see https://github.com/bevyengine/bevy/blob/19ee692f9621f89f305096f423507e925b748b9a/examples/shader/specialized_mesh_pipeline.rs#L353
+        {
+            let pipeline = match renderable_chunk.layer() {
+                ChunkLayer::Opaque => opaque_pipeline,
+                ChunkLayer::Cutout => cutout_pipeline,
+                ChunkLayer::Alpha => alpha_pipeline,
+            };
             // Add the mesh with our specialized pipeline
             transparent_phase.add(Transparent3d {
                 entity: (render_entity, *visible_entity),
@@ -110,27 +168,98 @@ fn queue_custom_render_pipeline(
     }
 }
 
+/// Chunks are silently skipped while their pipeline is broken, for example after saving
+/// a shader with a syntax error. Log the failure once, until a reload fixes it.
+/// True while the pipeline is broken.
+fn report_pipeline_error(
+    pipeline_cache: &PipelineCache,
+    pipeline: CachedRenderPipelineId,
+    failed_pipelines: &mut HashSet<CachedRenderPipelineId>,
+) -> bool {
+    match pipeline_cache.get_render_pipeline_state(pipeline) {
+        CachedPipelineState::Err(err) => {
+            if failed_pipelines.insert(pipeline) {
+                error!("Chunk render pipeline failed to compile, chunks will not be drawn: {err}");
+            }
+            true
+        }
+        CachedPipelineState::Ok(_) => {
+            if failed_pipelines.remove(&pipeline) {
+                info!("Chunk render pipeline recompiled.");
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Switches the pipeline to the shader a mod picked once it is extracted.
+/// The pipelines specialized with the previous shader are dropped, so every layer is specialized again.
+#[allow(clippy::needless_pass_by_value)]
+fn apply_chunk_shader(
+    shader_path: Res<ChunkShaderPath>,
+    asset_server: Res<AssetServer>,
+    mut custom_pipeline: ResMut<CustomPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<CustomPipeline>>,
+) {
+    if custom_pipeline.shader_path != shader_path.0 {
+        info!("Drawing chunks with {}.", shader_path.0);
+        custom_pipeline.set_shader(&asset_server, &shader_path.0);
+        *pipelines = default();
+    }
+}
+
 #[derive(Resource)]
 pub(super) struct CustomPipeline {
+    shader_path: String,
     shader_handle: Handle<Shader>,
     mesh_pipeline: MeshPipeline,
     bind_group_layout: BindGroupLayout,
+    texture_bind_group_layout: BindGroupLayout,
+    lighting_bind_group_layout: BindGroupLayout,
 }
 
 impl FromWorld for CustomPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
         let bind_group_layout = bind_group_layout(render_device);
-        let mesh_pipeline = world.resource::<MeshPipeline>();
+        let texture_bind_group_layout = texture_bind_group_layout(render_device);
+        let lighting_bind_group_layout = lighting_bind_group_layout(render_device);
+        let mesh_pipeline = world.resource::<MeshPipeline>().clone();
+        let (shader_path, shader_handle) = load_chunk_shader(world);
 
         CustomPipeline {
-            shader_handle: world.load_asset(SHADER_ASSET_PATH),
-            mesh_pipeline: mesh_pipeline.clone(),
+            shader_path,
+            shader_handle,
+            mesh_pipeline,
             bind_group_layout: bind_group_layout,
+            texture_bind_group_layout,
+            lighting_bind_group_layout,
         }
     }
 }
 
+impl CustomPipeline {
+    fn set_shader(&mut self, asset_server: &AssetServer, shader_path: &str) {
+        self.shader_path = shader_path.to_string();
+        self.shader_handle = asset_server.load(shader_path.to_string());
+    }
+}
+
+/// Loads the shader [`ChunkShaderPath`] points at, the default one without the resource.
+fn load_chunk_shader(world: &World) -> (String, Handle<Shader>) {
+    let shader_path = world.get_resource::<ChunkShaderPath>().cloned().unwrap_or_default().0;
+    let shader_handle = world.resource::<AssetServer>().load(shader_path.clone());
+    (shader_path, shader_handle)
+}
+
+/// Chunk pipelines are specialized for the view and for each [`ChunkLayer`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) struct ChunkPipelineKey {
+    mesh_key: MeshPipelineKey,
+    layer: ChunkLayer,
+}
+
 /// The custom draw commands that Bevy executes for each entity we enqueue into
 /// the render phase.
 pub(super) type DrawCustom = (
@@ -138,14 +267,18 @@ pub(super) type DrawCustom = (
     SetItemPipeline,
     // Set the view uniform at bind group 0
     SetMeshViewBindGroup<0>,
+    // Set the block texture array at bind group 2
+    SetBlockTextureBindGroup<2>,
+    // Set the chunk lighting uniform at bind group 3
+    SetChunkLightingBindGroup<3>,
     DrawChunk,
 );
 
 // Set a custom vertex buffer layout for our render pipeline.
 impl SpecializedRenderPipeline for CustomPipeline {
-    type Key = MeshPipelineKey;
+    type Key = ChunkPipelineKey;
 
-    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+    fn specialize(&self, ChunkPipelineKey { mesh_key: key, layer }: Self::Key) -> RenderPipelineDescriptor {
         // Define a buffer layout for our vertex buffer. Our vertex buffer only has one entry which is a packed u32
         let vertex_buffer_layout = VertexBufferLayout {
             array_stride: std::mem::size_of::<[f32; 3]>() as u64,
@@ -173,9 +306,24 @@ impl SpecializedRenderPipeline for CustomPipeline {
                     offset: std::mem::size_of::<u32>() as u64,
                     shader_location: 2,
                 },
+                VertexAttribute {
+                    format: VertexFormat::Uint32,
+                    offset: 2 * std::mem::size_of::<u32>() as u64,
+                    shader_location: 3,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Uint32,
+                    offset: 3 * std::mem::size_of::<u32>() as u64,
+                    shader_location: 4,
+                },
             ],
         };
         
+        let shader_defs = match layer {
+            ChunkLayer::Cutout => vec!["CUTOUT".into()],
+            ChunkLayer::Opaque | ChunkLayer::Alpha => vec![],
+        };
+
         RenderPipelineDescriptor {
             label: Some("Specialized Mesh Pipeline".into()),
             layout: vec![
@@ -185,6 +333,10 @@ impl SpecializedRenderPipeline for CustomPipeline {
                     .clone(),
                 // Bind group 1 is the chunk position.
                 self.bind_group_layout.clone(),
+                // Bind group 2 is the block texture array.
+                self.texture_bind_group_layout.clone(),
+                // Bind group 3 is the chunk lighting uniform.
+                self.lighting_bind_group_layout.clone(),
             ],
             push_constant_ranges: vec![],
             vertex: VertexState {
@@ -196,7 +348,7 @@ impl SpecializedRenderPipeline for CustomPipeline {
             },
             fragment: Some(FragmentState {
                 shader: self.shader_handle.clone(),
-                shader_defs: vec![],
+                shader_defs,
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
                     // This isn't required, but bevy supports HDR and non-HDR rendering
@@ -206,16 +358,18 @@ impl SpecializedRenderPipeline for CustomPipeline {
                     } else {
                         TextureFormat::bevy_default()
                     },
-                    // For this example we only use opaque meshes,
-                    // but if you wanted to use alpha blending you would need to set it here
-                    blend: None,
+                    blend: match layer {
+                        ChunkLayer::Opaque | ChunkLayer::Cutout => None,
+                        ChunkLayer::Alpha => Some(BlendState::ALPHA_BLENDING),
+                    },
                     write_mask: ColorWrites::ALL,
                 })],
             }),
             primitive: PrimitiveState {
                 topology: PrimitiveTopology::TriangleList,
                 front_face: bevy::render::render_resource::FrontFace::Ccw,
-                cull_mode: Some(Face::Front),
+                // crossed quads are seen from both sides
+                cull_mode: (layer != ChunkLayer::Cutout).then_some(Face::Front),
                 unclipped_depth: false,
                 polygon_mode: PolygonMode::Fill,
                 conservative: false, // Enabling this requires `Features::CONSERVATIVE_RASTERIZATION` to be enabled.
@@ -223,9 +377,12 @@ impl SpecializedRenderPipeline for CustomPipeline {
             },
             // Note that if your view has no depth buffer this will need to be
             // changed.
+            // The chunks share the depth buffer of bevy's 3d pass, which uses reverse-Z:
+            // it is cleared to 0 and the far plane is at 0, so nearer fragments pass with `GreaterEqual`.
             depth_stencil: Some(DepthStencilState {
                 format: CORE_3D_DEPTH_FORMAT,
-                depth_write_enabled: true,
+                // translucent chunks must not hide the chunks drawn after them
+                depth_write_enabled: layer != ChunkLayer::Alpha,
                 depth_compare: CompareFunction::GreaterEqual,
                 stencil: default(),
                 bias: default(),
@@ -263,3 +420,22 @@ impl<P: PhaseItem> RenderCommand<P> for DrawChunk {
         RenderCommandResult::Success
     }
 }
+
+#[test]
+fn the_pipeline_loads_the_shader_override() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default())).init_asset::<Shader>();
+
+    let (shader_path, _) = load_chunk_shader(app.world());
+    assert_eq!(shader_path, SHADER_ASSET_PATH);
+
+    app.insert_resource(ChunkShaderPath("mods/fancy/chunk.wgsl".to_string()));
+    let (shader_path, shader_handle) = load_chunk_shader(app.world());
+    assert_eq!(shader_path, "mods/fancy/chunk.wgsl");
+    let asset_path = app
+        .world()
+        .resource::<AssetServer>()
+        .get_path(&shader_handle)
+        .expect("The shader is loaded from a path.");
+    assert_eq!(asset_path.path(), std::path::Path::new("mods/fancy/chunk.wgsl"));
+}
@@ -0,0 +1,57 @@
+/*!
+frustum planes for culling outside of bevy's own visibility checks.
+bevy already culls `RenderableChunk` entities through their `Aabb`,
+these are for code that only has a view-projection matrix.
+*/
+
+use bevy::prelude::*;
+
+/// Extracts the left, right, bottom, top and near planes from a view-projection matrix (Gribb-Hartmann).
+///
+/// Each plane is normalized, `plane.xyz().dot(point) + plane.w` is the signed distance, positive inside.
+/// Expects bevy's reverse-z depth, where clip space z goes from w at the near plane to 0 at the far plane.
+/// The far plane is left out: bevy's perspective puts it at infinity, where its normal is zero.
+#[must_use]
+pub fn frustum_planes(view_projection: &Mat4) -> [Vec4; 5] {
+    let row = |i| view_projection.row(i);
+    [
+        row(3) + row(0),
+        row(3) - row(0),
+        row(3) + row(1),
+        row(3) - row(1),
+        row(3) - row(2),
+    ]
+    .map(|plane| plane / plane.xyz().length())
+}
+
+/// true if any part of the box is on the inside of every plane.
+///
+/// may report boxes just outside a frustum corner as intersecting, which is fine for culling.
+#[must_use]
+pub fn aabb_intersects_frustum(planes: &[Vec4; 5], min: Vec3, max: Vec3) -> bool {
+    planes.iter().all(|plane| {
+        // the corner furthest along the plane normal
+        let corner = Vec3::select(plane.xyz().cmpge(Vec3::ZERO), max, min);
+        plane.xyz().dot(corner) + plane.w >= 0.
+    })
+}
+
+#[test]
+fn planes_from_perspective() {
+    let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+    let projection = Mat4::perspective_infinite_reverse_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1);
+    let planes = frustum_planes(&(projection * view));
+    assert!(planes.iter().all(|plane| plane.is_finite()), "{planes:?}");
+
+    let inside = |point: Vec3| planes.iter().all(|plane| plane.xyz().dot(point) + plane.w >= 0.);
+    assert!(inside(Vec3::new(0., 0., -10.)));
+    assert!(!inside(Vec3::new(0., 0., 10.)));
+    assert!(!inside(Vec3::new(0., 0., -0.05)));
+    assert!(inside(Vec3::new(0., 0., -1e6)));
+    // a 90 degree fov reaches x = 10 at z = -10
+    assert!(inside(Vec3::new(9., 0., -10.)));
+    assert!(!inside(Vec3::new(11., 0., -10.)));
+
+    assert!(aabb_intersects_frustum(&planes, Vec3::new(8., -1., -12.), Vec3::new(12., 1., -8.)));
+    assert!(!aabb_intersects_frustum(&planes, Vec3::new(-1., -1., 1.), Vec3::new(1., 1., 3.)));
+}
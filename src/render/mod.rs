@@ -1,2 +1,5 @@
+pub mod block_textures;
+pub mod chunk_lighting;
 pub mod chunk_material;
 pub mod chunk_render_pipeline;
+pub mod frustum;
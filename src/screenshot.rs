@@ -0,0 +1,101 @@
+//! Renders a fixed number of frames, saves a screenshot of the primary window and exits.
+//!
+//! Meant for golden image tests of the chunk renderer, run with `cargo run -- --screenshot out.png --frames 300`.
+//! Time advances a fixed step per frame so the sun and chunk animations match between runs,
+//! but chunks still load asynchronously. Give them enough frames to finish.
+
+use std::{path::PathBuf, time::Duration};
+
+use bevy::{
+    prelude::*,
+    render::view::screenshot::{Screenshot, ScreenshotCaptured, save_to_disk},
+    time::TimeUpdateStrategy,
+};
+
+/// Time each frame advances by while waiting for the screenshot.
+pub const SCREENSHOT_FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// Insert to take a screenshot after `frames` frames and write it to `path`. The format follows the extension.
+#[derive(Resource, Clone, Debug)]
+pub struct ScreenshotAfterFrames {
+    pub frames: u32,
+    pub path: PathBuf,
+}
+
+impl ScreenshotAfterFrames {
+    /// Reads `--screenshot <path>` and optionally `--frames <count>` (default 300) from `args`.
+    #[must_use]
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        let mut path = None;
+        let mut frames = 300;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--screenshot" => path = args.next().map(PathBuf::from),
+                "--frames" => {
+                    if let Some(count) = args.next().and_then(|count| count.parse().ok()) {
+                        frames = count;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(Self { frames, path: path? })
+    }
+}
+
+/// Frames rendered since startup, while a screenshot is pending.
+#[derive(Resource, Default)]
+struct FramesRendered(u32);
+
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<ScreenshotAfterFrames>() {
+            return;
+        }
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(SCREENSHOT_FRAME_TIME))
+            .init_resource::<FramesRendered>()
+            .add_systems(Last, capture_screenshot);
+    }
+}
+
+/// Spawns the `Screenshot` on the target frame.
+/// Bevy copies the window texture into a buffer,
+/// maps it asynchronously and triggers `ScreenshotCaptured` once the pixels are back, a few frames later.
+#[allow(clippy::needless_pass_by_value)]
+fn capture_screenshot(
+    mut commands: Commands,
+    screenshot: Res<ScreenshotAfterFrames>,
+    mut frames_rendered: ResMut<FramesRendered>,
+) {
+    frames_rendered.0 += 1;
+    if frames_rendered.0 != screenshot.frames {
+        return;
+    }
+
+    info!("Capturing screenshot to {}", screenshot.path.display());
+    let mut save = save_to_disk(screenshot.path.clone());
+    commands.spawn(Screenshot::primary_window()).observe(
+        move |trigger: Trigger<ScreenshotCaptured>, mut exit: EventWriter<AppExit>| {
+            save(trigger);
+            exit.write(AppExit::Success);
+        },
+    );
+}
+
+#[test]
+fn screenshot_args() {
+    let args = |args: &[&str]| {
+        ScreenshotAfterFrames::from_args(args.iter().map(ToString::to_string))
+            .map(|screenshot| (screenshot.frames, screenshot.path))
+    };
+    assert_eq!(args(&["talc"]), None);
+    assert_eq!(args(&["talc", "--frames", "10"]), None);
+    assert_eq!(args(&["talc", "--screenshot", "out.png"]), Some((300, "out.png".into())));
+    assert_eq!(
+        args(&["talc", "--frames", "10", "--screenshot", "out.png"]),
+        Some((10, "out.png".into()))
+    );
+}
@@ -1,23 +1,22 @@
 use crate::position::FloatingPosition;
 use bevy::prelude::*;
-use std::time::Duration;
 
+/// Moves the entity by `offset` at a constant speed, then removes itself.
 #[derive(Component)]
 #[require(Transform)]
 pub struct SmoothTransformTo {
-    direction: Vec3,
+    offset: FloatingPosition,
+    moved: FloatingPosition,
     blocks_per_second: f32,
-    end_timestamp: Duration,
 }
 
 impl SmoothTransformTo {
     #[must_use]
-    pub fn new(timer: &Time, end: FloatingPosition, blocks_per_second: f32) -> Self {
+    pub const fn new(offset: FloatingPosition, blocks_per_second: f32) -> Self {
         Self {
-            direction: end.0.normalize(),
+            offset,
+            moved: FloatingPosition::new(0., 0., 0.),
             blocks_per_second,
-            end_timestamp: timer.elapsed()
-                + Duration::from_secs_f32(end.0.distance(Vec3::ZERO) / blocks_per_second),
         }
     }
 }
@@ -25,22 +24,19 @@ impl SmoothTransformTo {
 #[allow(clippy::needless_pass_by_value)]
 pub fn smooth_transform(
     mut commands: Commands,
-    mut to_move: Query<(Entity, &mut Transform, &SmoothTransformTo)>,
+    mut to_move: Query<(Entity, &mut Transform, &mut SmoothTransformTo)>,
     timer: Res<Time>,
 ) {
-    for (entity, mut transform, smooth_transform) in &mut to_move {
-        let delta_seconds = if timer.elapsed() < smooth_transform.end_timestamp {
-            timer.delta_secs()
-        } else {
-            commands.entity(entity).try_remove::<SmoothTransformTo>();
-            let time_of_previous_update = timer.elapsed() - timer.delta();
-            if time_of_previous_update >= smooth_transform.end_timestamp {
-                return;
-            }
-            (smooth_transform.end_timestamp - time_of_previous_update).as_secs_f32()
-        };
+    for (entity, mut transform, mut smooth_transform) in &mut to_move {
+        let moved = smooth_transform.moved.move_toward(
+            smooth_transform.offset,
+            timer.delta_secs() * smooth_transform.blocks_per_second,
+        );
+        transform.translation += moved.0 - smooth_transform.moved.0;
+        smooth_transform.moved = moved;
 
-        transform.translation +=
-            smooth_transform.direction * delta_seconds * smooth_transform.blocks_per_second;
+        if moved.0 == smooth_transform.offset.0 {
+            commands.entity(entity).try_remove::<SmoothTransformTo>();
+        }
     }
 }
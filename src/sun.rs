@@ -2,10 +2,29 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 
+use crate::player::key_bindings::{Action, KeyBindings};
+
 pub const DAY_TIME_SEC: f32 = 60.0;
 pub const NIGHT_TIME_SEC: f32 = 10.0;
 pub const CYCLE_TIME: f32 = DAY_TIME_SEC + NIGHT_TIME_SEC;
 
+/// background colors the `ClearColor` fades between over the day.
+/// the atmosphere draws over it, it only shows on cameras without an `Atmosphere`.
+#[derive(Resource, Clone, Copy)]
+pub struct SkyColor {
+    pub day: Color,
+    pub night: Color,
+}
+
+impl Default for SkyColor {
+    fn default() -> Self {
+        Self {
+            day: Color::srgb(0.6, 0.9, 1.0),
+            night: Color::srgb(0.01, 0.01, 0.03),
+        }
+    }
+}
+
 /// current time of day
 #[derive(Resource)]
 struct SkyTime(pub f32);
@@ -23,10 +42,13 @@ pub struct SunPlugin;
 impl Plugin for SunPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(SkyTime(0f32));
+        app.init_resource::<SkyColor>();
+        app.insert_resource(ClearColor(SkyColor::default().day));
         app.insert_resource(CycleTimer(Timer::new(
             Duration::from_millis(50),
             TimerMode::Repeating,
         )));
+        app.init_resource::<KeyBindings>();
         app.add_systems(Update, daylight_cycle);
     }
 }
@@ -36,15 +58,18 @@ fn daylight_cycle(
     mut query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
     mut timer: ResMut<SkyTime>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
     time: Res<Time>,
     mut cycle_timer: ResMut<CycleTimer>,
+    sky_color: Res<SkyColor>,
+    mut clear_color: ResMut<ClearColor>,
 ) {
     cycle_timer.0.tick(time.delta());
 
     if !cycle_timer.0.just_finished() {
         return;
     }
-    let multiplier = if keyboard.pressed(KeyCode::KeyI) {
+    let multiplier = if key_bindings.pressed(&keyboard, Action::FastForwardTime) {
         6.0
     } else {
         1.0
@@ -64,4 +89,6 @@ fn daylight_cycle(
         directional.illuminance =
             percent.sin().max(0.0).powi(2) * light_consts::lux::AMBIENT_DAYLIGHT * 0.4;
     }
+
+    clear_color.0 = sky_color.night.mix(&sky_color.day, percent.sin().max(0.0));
 }
@@ -5,6 +5,9 @@ use crate::{
     position::{ChunkPosition, Position},
 };
 
+/// The `i`th position of a `bounds`³ cube, x varying fastest and z slowest.
+/// This is the layout of [`ChunkRefs`](crate::chunky::chunks_refs::ChunkRefs),
+/// use it for anything indexed through [`ChunkRefs::vec3_to_chunk_index`](crate::chunky::chunks_refs::ChunkRefs::vec3_to_chunk_index).
 #[inline]
 #[must_use]
 pub const fn index_to_ivec3_bounds(i: i32, bounds: i32) -> IVec3 {
@@ -14,6 +17,8 @@ pub const fn index_to_ivec3_bounds(i: i32, bounds: i32) -> IVec3 {
     IVec3::new(x, y, z)
 }
 
+/// Like [`index_to_ivec3_bounds`] with the axes swapped, z varying fastest and x slowest.
+/// Only for data laid out z first. Mixing the two up mirrors positions across the x = z diagonal.
 #[inline]
 #[must_use]
 pub const fn index_to_ivec3_bounds_reverse(i: i32, bounds: i32) -> IVec3 {
@@ -50,6 +55,18 @@ pub fn get_edging_chunk(pos: Position) -> Option<ChunkPosition> {
     }
 }
 
+/// if lying on the edge of a chunk, return the offset of every neighbouring chunk sampling it.
+/// Ambient occlusion samples diagonally, so a voxel on a chunk edge is seen by three neighbouring chunks,
+/// and one on a chunk corner by seven.
+pub fn get_edging_chunks(pos: Position) -> impl Iterator<Item = ChunkPosition> {
+    let chunk_dir = get_edging_chunk(pos).map_or(IVec3::ZERO, |chunk_dir| chunk_dir.0);
+    // every combination of the axes the voxel lies on the edge of, as bits x, y and z
+    let edge_axes = chunk_dir.abs().dot(IVec3::new(1, 2, 4));
+    (1..8)
+        .filter(move |axes| axes & !edge_axes == 0)
+        .map(move |axes| ChunkPosition(chunk_dir * IVec3::new(axes & 1, (axes >> 1) & 1, axes >> 2)))
+}
+
 /// generate a vec of indices
 /// assumes vertices are made of quads, and counter clockwise ordered
 #[inline]
@@ -68,3 +85,40 @@ pub fn generate_indices(vertex_count: usize) -> Vec<u32> {
     });
     indices
 }
+
+#[test]
+fn index_to_ivec3_bounds_round_trips() {
+    use bevy::platform::collections::HashSet;
+
+    for bounds in [3, 34] {
+        let in_range = |v: IVec3| v.cmpge(IVec3::ZERO).all() && v.cmplt(IVec3::splat(bounds)).all();
+        let mut seen = HashSet::new();
+        let mut seen_reverse = HashSet::new();
+        for i in 0..bounds * bounds * bounds {
+            let v = index_to_ivec3_bounds(i, bounds);
+            assert!(in_range(v), "{v} out of range for {bounds}");
+            assert!(seen.insert(v));
+            assert_eq!(v.x + v.y * bounds + v.z * bounds * bounds, i);
+
+            let reverse = index_to_ivec3_bounds_reverse(i, bounds);
+            assert!(in_range(reverse), "{reverse} out of range for {bounds}");
+            assert!(seen_reverse.insert(reverse));
+            assert_eq!(reverse.z + reverse.y * bounds + reverse.x * bounds * bounds, i);
+            // the same cube, walked with the axes swapped
+            assert_eq!(reverse, IVec3::new(v.z, v.y, v.x));
+        }
+    }
+}
+
+#[test]
+fn edging_chunks_include_the_diagonals() {
+    let edging = |pos| get_edging_chunks(pos).map(|chunk_position| chunk_position.0).collect::<Vec<_>>();
+
+    assert!(edging(Position::new(5, 5, 5)).is_empty());
+    assert_eq!(edging(Position::new(5, 0, 5)), [IVec3::NEG_Y]);
+    assert_eq!(
+        edging(Position::new(0, 5, CHUNK_SIZE_I32 - 1)),
+        [IVec3::NEG_X, IVec3::Z, IVec3::new(-1, 0, 1)]
+    );
+    assert_eq!(edging(Position::new(0, 0, 0)).len(), 7);
+}